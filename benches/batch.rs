@@ -0,0 +1,80 @@
+//! Compares running many machines one at a time with `Batch` on one thread, on every core and on
+//! four threads, which shows what handing the machines to threads costs on machines with few cores.
+//!
+//! Run with `cargo bench --bench batch`, optionally followed by the number of machines.
+
+use chip8::chip8_batch::Batch;
+use chip8::chip8_env::bundled_game;
+use chip8::Chip8;
+use std::time::{Duration, Instant};
+
+const FRAMES: u32 = 600;
+
+fn machines(rom: &[u8], count: usize) -> Vec<Chip8> {
+    return (0..count)
+        .map(|index| {
+            let mut c = Chip8::new();
+            c.load_game_from_slice(rom)
+                .expect("the game fits in memory");
+            c.set_rng_seed(index as u32 + 1);
+            c.set_instructions_per_frame(100);
+            return c;
+        })
+        .collect();
+}
+
+/// Step each machine on its own and copy its screen out, as a caller without `Batch` would
+fn single(rom: &[u8], count: usize) -> Duration {
+    let mut machines = machines(rom, count);
+    let mut screens = Vec::new();
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        screens.clear();
+        for c in machines.iter_mut() {
+            c.set_key(4, frame % 60 < 30);
+            c.run_frame();
+            screens.extend_from_slice(c.get_gfx());
+        }
+    }
+    return start.elapsed();
+}
+
+fn batch(rom: &[u8], count: usize, threads: usize) -> Duration {
+    let mut batch = Batch::new(machines(rom, count));
+    batch.set_threads(threads);
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        let keys = if frame % 60 < 30 { 1 << 4 } else { 0 };
+        batch.keys_mut().fill(keys);
+        batch.run_frames(1);
+    }
+    return start.elapsed();
+}
+
+fn main() {
+    // cargo bench passes --bench, so take the first number as the machine count
+    let count = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(1024);
+    let rom = bundled_game("catch").expect("catch is bundled");
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    println!("{} machines, {} frames, {} cores", count, FRAMES, cores);
+    let base = single(&rom, count);
+    let runs = [
+        ("one machine at a time", base),
+        ("batch, 1 thread", batch(&rom, count, 1)),
+        ("batch, every core", batch(&rom, count, 0)),
+        ("batch, 4 threads", batch(&rom, count, 4)),
+    ];
+    for (name, time) in runs {
+        let frames_per_second = (count as u64 * FRAMES as u64) as f64 / time.as_secs_f64();
+        println!(
+            "{:24} {:>8.1?} {:>12.0} frames/s {:>6.2}x",
+            name,
+            time,
+            frames_per_second,
+            base.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,12 @@
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR");
+    let out_dir = std::env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("cbindgen.toml is valid");
+    // The checked-in include/chip8.h is compared against this copy by tests/c_api.rs
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("the C API can be expressed in C")
+        .write_to_file(format!("{}/chip8.h", out_dir));
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
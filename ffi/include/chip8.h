@@ -0,0 +1,145 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/* An interpreter instance, created by chip8_new and destroyed by chip8_free. */
+typedef struct Chip8 Chip8;
+
+/**
+ * The outcome of a call
+ */
+typedef enum Chip8Result {
+  CHIP8_RESULT_OK = 0,
+  /**
+   * A pointer argument was NULL
+   */
+  CHIP8_RESULT_NULL_POINTER = 1,
+  /**
+   * The rom has no bytes
+   */
+  CHIP8_RESULT_EMPTY_ROM = 2,
+  /**
+   * The rom does not fit in memory
+   */
+  CHIP8_RESULT_ROM_TOO_LARGE = 3,
+  /**
+   * Keys are numbered 0 to 15
+   */
+  CHIP8_RESULT_INVALID_KEY = 4,
+  /**
+   * The buffer does not hold a saved state
+   */
+  CHIP8_RESULT_INVALID_STATE = 5,
+  /**
+   * The buffer is smaller than `chip8_state_size()`
+   */
+  CHIP8_RESULT_BUFFER_TOO_SMALL = 6,
+  /**
+   * The interpreter panicked, the instance should be freed
+   */
+  CHIP8_RESULT_PANICKED = 7,
+} Chip8Result;
+
+/**
+ * Create an interpreter, free it with `chip8_free`
+ */
+Chip8 *chip8_new(void);
+
+/**
+ * Destroy an interpreter
+ * # Safety
+ * `chip8` must come from `chip8_new` and not be used again. NULL is ignored.
+ */
+void chip8_free(Chip8 *chip8);
+
+/**
+ * Copy a rom into memory at the load address and start running it from there
+ * # Safety
+ * `chip8` must come from `chip8_new`, `rom` must point to `len` readable bytes.
+ */
+enum Chip8Result chip8_load_rom(Chip8 *chip8, const uint8_t *rom, size_t len);
+
+/**
+ * Run a number of instructions
+ * # Safety
+ * `chip8` must come from `chip8_new`.
+ */
+enum Chip8Result chip8_step(Chip8 *chip8, uint32_t cycles);
+
+/**
+ * Run one 60Hz frame worth of instructions, ending early after a draw with the vblank quirk
+ * # Safety
+ * `chip8` must come from `chip8_new`.
+ */
+enum Chip8Result chip8_run_frame(Chip8 *chip8);
+
+/**
+ * Decrement the delay and sound timers once, callers of `chip8_step` do this at 60Hz
+ * # Safety
+ * `chip8` must come from `chip8_new`.
+ */
+enum Chip8Result chip8_tick_timers(Chip8 *chip8);
+
+/**
+ * Press or release a key of the hex keypad
+ * # Safety
+ * `chip8` must come from `chip8_new`.
+ */
+enum Chip8Result chip8_set_key(Chip8 *chip8, uint8_t key, bool pressed);
+
+/**
+ * The display width in pixels
+ */
+size_t chip8_screen_width(void);
+
+/**
+ * The display height in pixels
+ */
+size_t chip8_screen_height(void);
+
+/**
+ * The display, one byte per pixel row by row, 1 for lit
+ *
+ * The pointer stays valid until the next call that changes the interpreter.
+ * # Safety
+ * `chip8` must come from `chip8_new`. Returns NULL if it is NULL.
+ */
+const uint8_t *chip8_framebuffer(const Chip8 *chip8);
+
+/**
+ * Whether the buzzer should be sounding
+ * # Safety
+ * `chip8` must come from `chip8_new`. Returns false if it is NULL.
+ */
+bool chip8_sound_active(const Chip8 *chip8);
+
+/**
+ * The number of bytes `chip8_save_state` writes
+ */
+size_t chip8_state_size(void);
+
+/**
+ * Save the whole machine into a buffer of at least `chip8_state_size()` bytes
+ * # Safety
+ * `chip8` must come from `chip8_new`, `buffer` must point to `len` writable bytes.
+ */
+enum Chip8Result chip8_save_state(const Chip8 *chip8, uint8_t *buffer, size_t len);
+
+/**
+ * Restore a state saved by `chip8_save_state`, the machine is unchanged on failure
+ * # Safety
+ * `chip8` must come from `chip8_new`, `buffer` must point to `len` readable bytes.
+ */
+enum Chip8Result chip8_load_state(Chip8 *chip8, const uint8_t *buffer, size_t len);
+
+/**
+ * A description of a result, as a static NUL terminated string
+ */
+const char *chip8_result_message(enum Chip8Result result);
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,269 @@
+//! A C ABI for the interpreter, see `include/chip8.h`.
+//!
+//! Every function checks its pointers for NULL and reports failures as a `Chip8Result`.
+//! Panics are caught at the boundary and reported as `CHIP8_RESULT_PANICKED`.
+
+use chip8::chip8_frontend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip8::chip8_state::STATE_SIZE;
+use chip8::{Chip8, LoadError};
+use std::ffi::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// The outcome of a call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Result {
+    Ok = 0,
+    /// A pointer argument was NULL
+    NullPointer = 1,
+    /// The rom has no bytes
+    EmptyRom = 2,
+    /// The rom does not fit in memory
+    RomTooLarge = 3,
+    /// Keys are numbered 0 to 15
+    InvalidKey = 4,
+    /// The buffer does not hold a saved state
+    InvalidState = 5,
+    /// The buffer is smaller than `chip8_state_size()`
+    BufferTooSmall = 6,
+    /// The interpreter panicked, the instance should be freed
+    Panicked = 7,
+}
+
+fn guard(f: impl FnOnce() -> Chip8Result) -> Chip8Result {
+    return catch_unwind(AssertUnwindSafe(f)).unwrap_or(Chip8Result::Panicked);
+}
+
+/// Create an interpreter, free it with `chip8_free`
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    return Box::into_raw(Box::new(Chip8::new()));
+}
+
+/// Destroy an interpreter
+/// # Safety
+/// `chip8` must come from `chip8_new` and not be used again. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Copy a rom into memory at the load address and start running it from there
+/// # Safety
+/// `chip8` must come from `chip8_new`, `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(
+    chip8: *mut Chip8,
+    rom: *const u8,
+    len: usize,
+) -> Chip8Result {
+    let (Some(chip8), false) = (chip8.as_mut(), rom.is_null()) else {
+        return Chip8Result::NullPointer;
+    };
+    let rom = std::slice::from_raw_parts(rom, len);
+    return guard(|| match chip8.load_game_from_slice(rom) {
+        Ok(()) => Chip8Result::Ok,
+        Err(LoadError::Empty) => Chip8Result::EmptyRom,
+        Err(_) => Chip8Result::RomTooLarge,
+    });
+}
+
+/// Run a number of instructions
+/// # Safety
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8, cycles: u32) -> Chip8Result {
+    let Some(chip8) = chip8.as_mut() else {
+        return Chip8Result::NullPointer;
+    };
+    return guard(|| {
+        for _ in 0..cycles {
+            chip8.emulate_cycle();
+        }
+        return Chip8Result::Ok;
+    });
+}
+
+/// Run one 60Hz frame worth of instructions, ending early after a draw with the vblank quirk
+/// # Safety
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8) -> Chip8Result {
+    let Some(chip8) = chip8.as_mut() else {
+        return Chip8Result::NullPointer;
+    };
+    return guard(|| {
+        chip8.run_frame();
+        return Chip8Result::Ok;
+    });
+}
+
+/// Decrement the delay and sound timers once, callers of `chip8_step` do this at 60Hz
+/// # Safety
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip8: *mut Chip8) -> Chip8Result {
+    let Some(chip8) = chip8.as_mut() else {
+        return Chip8Result::NullPointer;
+    };
+    chip8.tick_timers();
+    return Chip8Result::Ok;
+}
+
+/// Press or release a key of the hex keypad
+/// # Safety
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) -> Chip8Result {
+    let Some(chip8) = chip8.as_mut() else {
+        return Chip8Result::NullPointer;
+    };
+    if key > 0xF {
+        return Chip8Result::InvalidKey;
+    }
+    chip8.set_key(key, pressed);
+    return Chip8Result::Ok;
+}
+
+/// The display width in pixels
+#[no_mangle]
+pub extern "C" fn chip8_screen_width() -> usize {
+    return SCREEN_WIDTH;
+}
+
+/// The display height in pixels
+#[no_mangle]
+pub extern "C" fn chip8_screen_height() -> usize {
+    return SCREEN_HEIGHT;
+}
+
+/// The display, one byte per pixel row by row, 1 for lit
+///
+/// The pointer stays valid until the next call that changes the interpreter.
+/// # Safety
+/// `chip8` must come from `chip8_new`. Returns NULL if it is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u8 {
+    return match chip8.as_ref() {
+        Some(chip8) => chip8.get_gfx().as_ptr(),
+        None => std::ptr::null(),
+    };
+}
+
+/// Whether the buzzer should be sounding
+/// # Safety
+/// `chip8` must come from `chip8_new`. Returns false if it is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Chip8) -> bool {
+    return chip8.as_ref().is_some_and(|c| c.get_sound_timer() > 0);
+}
+
+/// The number of bytes `chip8_save_state` writes
+#[no_mangle]
+pub extern "C" fn chip8_state_size() -> usize {
+    return STATE_SIZE;
+}
+
+/// Save the whole machine into a buffer of at least `chip8_state_size()` bytes
+/// # Safety
+/// `chip8` must come from `chip8_new`, `buffer` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(
+    chip8: *const Chip8,
+    buffer: *mut u8,
+    len: usize,
+) -> Chip8Result {
+    let (Some(chip8), false) = (chip8.as_ref(), buffer.is_null()) else {
+        return Chip8Result::NullPointer;
+    };
+    if len < STATE_SIZE {
+        return Chip8Result::BufferTooSmall;
+    }
+    let buffer = std::slice::from_raw_parts_mut(buffer, STATE_SIZE);
+    buffer.copy_from_slice(&chip8.save_state());
+    return Chip8Result::Ok;
+}
+
+/// Restore a state saved by `chip8_save_state`, the machine is unchanged on failure
+/// # Safety
+/// `chip8` must come from `chip8_new`, `buffer` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut Chip8,
+    buffer: *const u8,
+    len: usize,
+) -> Chip8Result {
+    let (Some(chip8), false) = (chip8.as_mut(), buffer.is_null()) else {
+        return Chip8Result::NullPointer;
+    };
+    let buffer = std::slice::from_raw_parts(buffer, len);
+    return guard(|| match chip8.load_state(buffer) {
+        Ok(()) => Chip8Result::Ok,
+        Err(_) => Chip8Result::InvalidState,
+    });
+}
+
+/// A description of a result, as a static NUL terminated string
+#[no_mangle]
+pub extern "C" fn chip8_result_message(result: Chip8Result) -> *const c_char {
+    let message: &'static [u8] = match result {
+        Chip8Result::Ok => b"ok\0",
+        Chip8Result::NullPointer => b"a pointer argument was NULL\0",
+        Chip8Result::EmptyRom => b"the rom is empty\0",
+        Chip8Result::RomTooLarge => b"the rom does not fit in memory\0",
+        Chip8Result::InvalidKey => b"keys are numbered 0 to 15\0",
+        Chip8Result::InvalidState => b"the buffer does not hold a saved state\0",
+        Chip8Result::BufferTooSmall => b"the buffer is too small for a saved state\0",
+        Chip8Result::Panicked => b"the interpreter panicked\0",
+    };
+    return message.as_ptr() as *const c_char;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_null_pointers() {
+        unsafe {
+            let null = std::ptr::null_mut();
+            assert_eq!(chip8_step(null, 1), Chip8Result::NullPointer);
+            assert_eq!(
+                chip8_load_rom(null, [0u8].as_ptr(), 1),
+                Chip8Result::NullPointer
+            );
+            assert!(chip8_framebuffer(null).is_null());
+            assert!(!chip8_sound_active(null));
+            chip8_free(null);
+        }
+    }
+    #[test]
+    fn test_panic_is_caught() {
+        unsafe {
+            let c = chip8_new();
+            // 0xFFFF is not an instruction
+            assert_eq!(chip8_load_rom(c, [0xFF, 0xFF].as_ptr(), 2), Chip8Result::Ok);
+            assert_eq!(chip8_step(c, 1), Chip8Result::Panicked);
+            assert_eq!(chip8_set_key(c, 16, true), Chip8Result::InvalidKey);
+            chip8_free(c);
+        }
+    }
+    #[test]
+    fn test_sound_lasts_a_second() {
+        unsafe {
+            let c = chip8_new();
+            // LD V0, 3C ; LD ST, V0 ; JP 204
+            let rom = [0x60, 0x3C, 0xF0, 0x18, 0x12, 0x04];
+            assert_eq!(chip8_load_rom(c, rom.as_ptr(), rom.len()), Chip8Result::Ok);
+            for _ in 0..59 {
+                assert_eq!(chip8_run_frame(c), Chip8Result::Ok);
+                assert!(chip8_sound_active(c));
+            }
+            assert_eq!(chip8_run_frame(c), Chip8Result::Ok);
+            assert!(!chip8_sound_active(c));
+            chip8_free(c);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+/* Exercises the C API, run by tests/c_api.rs. Exits non zero on the first failure. */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define CHECK(expr)                                                        \
+    do {                                                                   \
+        if (!(expr)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,         \
+                    __LINE__, #expr);                                      \
+            exit(1);                                                       \
+        }                                                                  \
+    } while (0)
+
+static size_t lit_pixels(const Chip8 *chip8) {
+    const uint8_t *gfx = chip8_framebuffer(chip8);
+    size_t count = 0;
+    for (size_t i = 0; i < chip8_screen_width() * chip8_screen_height(); i++) {
+        count += gfx[i];
+    }
+    return count;
+}
+
+int main(void) {
+    /* LD V0, 5 ; LD ST, V0 ; LD I, 20C ; DRW V1, V1, 1 ; SKP V1 ; JP 208 ; sprite */
+    const uint8_t rom[] = {0x60, 0x05, 0xF0, 0x18, 0xA2, 0x0C, 0xD1, 0x11,
+                           0xE1, 0x9E, 0x12, 0x08, 0xF0};
+    uint8_t too_large[4096] = {0};
+
+    Chip8 *chip8 = chip8_new();
+    CHECK(chip8 != NULL);
+    CHECK(chip8_screen_width() == 64);
+    CHECK(chip8_screen_height() == 32);
+
+    CHECK(chip8_load_rom(chip8, rom, 0) == CHIP8_RESULT_EMPTY_ROM);
+    CHECK(chip8_load_rom(chip8, too_large, sizeof too_large) == CHIP8_RESULT_ROM_TOO_LARGE);
+    CHECK(chip8_load_rom(chip8, NULL, 1) == CHIP8_RESULT_NULL_POINTER);
+    CHECK(chip8_load_rom(chip8, rom, sizeof rom) == CHIP8_RESULT_OK);
+
+    CHECK(!chip8_sound_active(chip8));
+    CHECK(chip8_step(chip8, 4) == CHIP8_RESULT_OK);
+    CHECK(chip8_sound_active(chip8));
+    CHECK(lit_pixels(chip8) == 4);
+    for (int i = 0; i < 5; i++) {
+        CHECK(chip8_tick_timers(chip8) == CHIP8_RESULT_OK);
+    }
+    CHECK(!chip8_sound_active(chip8));
+
+    /* Waiting for key 0 */
+    CHECK(chip8_run_frame(chip8) == CHIP8_RESULT_OK);
+    size_t size = chip8_state_size();
+    uint8_t *state = malloc(size);
+    CHECK(chip8_save_state(chip8, state, size - 1) == CHIP8_RESULT_BUFFER_TOO_SMALL);
+    CHECK(chip8_save_state(chip8, state, size) == CHIP8_RESULT_OK);
+
+    CHECK(chip8_set_key(chip8, 16, true) == CHIP8_RESULT_INVALID_KEY);
+    CHECK(chip8_set_key(chip8, 0, true) == CHIP8_RESULT_OK);
+    /* SKP V1 skips into the sprite data, which is not an instruction */
+    CHECK(chip8_run_frame(chip8) == CHIP8_RESULT_PANICKED);
+    printf("%s\n", chip8_result_message(CHIP8_RESULT_PANICKED));
+
+    Chip8 *restored = chip8_new();
+    CHECK(chip8_load_state(restored, state, size - 1) == CHIP8_RESULT_INVALID_STATE);
+    CHECK(chip8_load_state(restored, state, size) == CHIP8_RESULT_OK);
+    CHECK(lit_pixels(restored) == 4);
+    CHECK(chip8_run_frame(restored) == CHIP8_RESULT_OK);
+
+    CHECK(chip8_step(NULL, 1) == CHIP8_RESULT_NULL_POINTER);
+    CHECK(chip8_tick_timers(NULL) == CHIP8_RESULT_NULL_POINTER);
+    CHECK(chip8_framebuffer(NULL) == NULL);
+    CHECK(strcmp(chip8_result_message(CHIP8_RESULT_OK), "ok") == 0);
+
+    free(state);
+    chip8_free(restored);
+    chip8_free(chip8);
+    chip8_free(NULL);
+    printf("all checks passed\n");
+    return 0;
+}
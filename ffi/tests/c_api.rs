@@ -0,0 +1,57 @@
+#![allow(clippy::needless_return)]
+//! Builds tests/c/test_chip8.c against the static library and runs it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn library_dir() -> PathBuf {
+    // target/<profile>/deps/c_api-<hash>
+    let exe = std::env::current_exe().expect("the test knows where it is");
+    return exe
+        .ancestors()
+        .find(|dir| dir.join("libchip8_ffi.a").exists())
+        .expect("cargo builds the static library before the tests")
+        .to_path_buf();
+}
+
+#[test]
+fn test_c_program() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library = library_dir();
+    let program = library.join("test_chip8");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&program)
+        .arg(manifest.join("tests/c/test_chip8.c"))
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg(library.join("libchip8_ffi.a"))
+        .args(["-lpthread", "-ldl", "-lm"])
+        .status()
+        .expect("a C compiler is installed");
+    assert!(status.success());
+
+    let output = Command::new(&program).output().expect("the program runs");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("all checks passed"));
+}
+
+#[test]
+fn test_header_is_current() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/chip8.h"));
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/chip8.h");
+    let checked_in = std::fs::read_to_string(&path).expect("the header is checked in");
+    assert!(
+        checked_in == generated,
+        "{} is out of date, copy {}/chip8.h over it",
+        path.display(),
+        env!("OUT_DIR")
+    );
+}
@@ -0,0 +1,20 @@
+#![no_main]
+//! Disassembles arbitrary bytes with every disassembler entry point
+
+use chip8::chip8_disassembler::chip8_disassembler::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = dissasemble_op_code_from_bytes(data);
+    let listing = dissasemble_op_code_from_bytes_tolerant(data);
+    assert_eq!(listing.len(), data.len().div_ceil(2));
+    for (offset, word) in data.chunks_exact(2).enumerate() {
+        let code = u16::from_be_bytes([word[0], word[1]]);
+        let decoded = decode_instruction(PROGRAM_START + offset as u16 * 2, code);
+        assert_eq!(decoded.is_ok(), decode_op_code(code).is_some());
+    }
+    let _ = dissasemble_rom_listing(data);
+    if let Some(origin) = data.first() {
+        let _ = dissasemble_rom_at(data, *origin as u16 * 0x10);
+    }
+});
@@ -0,0 +1,73 @@
+#![no_main]
+//! Runs an arbitrary rom from an arbitrary register state with a key schedule
+//!
+//! The input is read front to back: V0 to VF (16 bytes), I (2), the quirks as bit flags (1),
+//! instructions per frame (1), the number of frames (1), the held keys of each frame as
+//! a 16 bit mask (2 per frame), then the rom itself. The seeds in `corpus/interpreter` start
+//! with such a header.
+
+use chip8::chip8_disassembler::chip8_disassembler::{decode_op_code, OpCodeKind};
+use chip8::{Chip8, Quirks};
+use libfuzzer_sys::arbitrary::{Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_INSTRUCTIONS_PER_FRAME: u32 = 64;
+const MAX_FRAMES: usize = 60;
+
+fn run(data: &[u8]) -> Result<()> {
+    let mut u = Unstructured::new(data);
+    let v: [u8; 16] = u.arbitrary()?;
+    let i: u16 = u.arbitrary()?;
+    let q: u8 = u.arbitrary()?;
+    let instructions_per_frame = u.int_in_range(1..=MAX_INSTRUCTIONS_PER_FRAME)?;
+    let frames = u.int_in_range(0..=MAX_FRAMES)?;
+    let mut keys = Vec::with_capacity(frames);
+    for _ in 0..frames {
+        keys.push(u.arbitrary::<u16>()?);
+    }
+    let rom = u.take_rest();
+
+    let mut c = Chip8::new();
+    if c.load_game_from_slice(rom).is_err() {
+        return Ok(());
+    }
+    c.set_rng_seed(1);
+    c.set_registers(v);
+    c.set_i(i);
+    c.set_quirks(Quirks {
+        shift: q & 0x01 != 0,
+        memory_increment_by_x: q & 0x02 != 0,
+        memory_leave_i_unchanged: q & 0x04 != 0,
+        wrap: q & 0x08 != 0,
+        jump: q & 0x10 != 0,
+        vblank: q & 0x20 != 0,
+        logic: match q >> 6 {
+            0 => None,
+            l => Some(l == 2),
+        },
+    });
+    for held in keys {
+        for key in 0..16 {
+            c.set_key(key, held & (1 << key) != 0);
+        }
+        for _ in 0..instructions_per_frame {
+            // Unknown opcodes and 0NNN machine code calls panic by design, so stop at the first one
+            let memory = c.get_memory();
+            let pc = c.get_pc() as usize;
+            let opcode =
+                (memory[pc % memory.len()] as u16) << 8 | memory[(pc + 1) % memory.len()] as u16;
+            if matches!(decode_op_code(opcode), None | Some(OpCodeKind::Sys)) {
+                return Ok(());
+            }
+            c.emulate_cycle();
+        }
+        let state = c.save_state();
+        let mut restored = Chip8::new();
+        restored.load_state(&state).expect("a saved state loads");
+    }
+    return Ok(());
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = run(data);
+});
@@ -0,0 +1,46 @@
+#![no_main]
+//! Loads arbitrary bytes as a saved state and runs the restored machine
+//!
+//! The input is first loaded as it is, to cover the size and header checks. Inputs of at
+//! least `STATE_SIZE` bytes are then loaded again with a valid header, so the fuzzer reaches
+//! the fields, and the machine runs for a few instructions and moves its font. The seeds in
+//! `corpus/state` are states saved while running `c8_test.c8`.
+
+use chip8::chip8_disassembler::chip8_disassembler::{decode_op_code, OpCodeKind};
+use chip8::chip8_state::STATE_SIZE;
+use chip8::Chip8;
+use libfuzzer_sys::fuzz_target;
+
+const MAX_INSTRUCTIONS: usize = 256;
+
+fuzz_target!(|data: &[u8]| {
+    let mut c = Chip8::new();
+    let _ = c.load_state(data);
+    if data.len() < STATE_SIZE {
+        return;
+    }
+    let mut state = [0; STATE_SIZE];
+    state.copy_from_slice(&data[..STATE_SIZE]);
+    // The magic bytes and the version
+    state[..5].copy_from_slice(&Chip8::new().save_state()[..5]);
+    if c.load_state(&state).is_err() {
+        return;
+    }
+    let saved = c.save_state();
+    let mut restored = Chip8::new();
+    restored.load_state(&saved).expect("a saved state loads");
+    assert_eq!(restored.save_state(), saved);
+    for _ in 0..MAX_INSTRUCTIONS {
+        // Unknown opcodes and 0NNN machine code calls panic by design, so stop at the first one
+        let memory = c.get_memory();
+        let pc = c.get_pc() as usize;
+        let opcode =
+            (memory[pc % memory.len()] as u16) << 8 | memory[(pc + 1) % memory.len()] as u16;
+        if matches!(decode_op_code(opcode), None | Some(OpCodeKind::Sys)) {
+            break;
+        }
+        c.emulate_cycle();
+    }
+    let _ = c.set_font_address(0);
+    c.load_state(&c.save_state()).expect("a saved state loads");
+});
@@ -0,0 +1,49 @@
+#![no_std]
+//! Uses the chip8 core from a `no_std` crate without `alloc`, the way firmware would.
+
+use chip8::chip8_disassembler::chip8_disassembler::{decode_op_code, OpCodeKind};
+use chip8::chip8_font::{BigFont, Font, SmallFont};
+use chip8::{Chip8, LoadError};
+
+/// Load a rom and run it for a number of cycles
+/// # Returns
+/// * The number of pixels lit at the end, or the load error
+pub fn run(rom: &[u8], cycles: usize) -> Result<usize, LoadError> {
+    let mut c = Chip8::new();
+    c.set_rng_seed(1);
+    c.set_font(&Font::new(SmallFont::CosmacVip, BigFont::Schip));
+    c.load_game_from_slice(rom)?;
+    for _ in 0..cycles {
+        c.emulate_cycle();
+    }
+    return Ok(c.get_gfx().iter().filter(|p| **p == 1).count());
+}
+
+/// Count the draw instructions in a rom without disassembling it to text
+pub fn count_draws(rom: &[u8]) -> usize {
+    return rom
+        .chunks_exact(2)
+        .filter(|word| {
+            decode_op_code(u16::from_be_bytes([word[0], word[1]])) == Some(OpCodeKind::Drw)
+        })
+        .count();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD V0, 0A ; LD F, V0 ; DRW V1, V1, 5 ; JP 206
+    const ROM: [u8; 8] = [0x60, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+
+    #[test]
+    fn test_run() {
+        // The COSMAC VIP "A" has 14 pixels
+        assert_eq!(run(&ROM, 4).ok(), Some(14));
+        assert!(matches!(run(&[], 1), Err(LoadError::Empty)));
+    }
+    #[test]
+    fn test_count_draws() {
+        assert_eq!(count_draws(&ROM), 1);
+    }
+}
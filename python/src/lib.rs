@@ -0,0 +1,275 @@
+//! The `chip8` Python module
+
+use chip8::chip8_frontend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip8::chip8_state::STATE_SIZE;
+use chip8::{Chip8, Quirks};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList, PyMemoryView};
+
+/// Interpreter behaviours that differ between CHIP-8 platforms, unset fields take the defaults
+#[pyclass(
+    name = "Quirks",
+    module = "chip8",
+    get_all,
+    set_all,
+    eq,
+    from_py_object
+)]
+#[derive(Clone, Copy, PartialEq)]
+struct PyQuirks {
+    shift: bool,
+    memory_increment_by_x: bool,
+    memory_leave_i_unchanged: bool,
+    wrap: bool,
+    jump: bool,
+    vblank: bool,
+    logic: Option<bool>,
+}
+
+impl From<Quirks> for PyQuirks {
+    fn from(q: Quirks) -> Self {
+        return Self {
+            shift: q.shift,
+            memory_increment_by_x: q.memory_increment_by_x,
+            memory_leave_i_unchanged: q.memory_leave_i_unchanged,
+            wrap: q.wrap,
+            jump: q.jump,
+            vblank: q.vblank,
+            logic: q.logic,
+        };
+    }
+}
+
+impl From<PyQuirks> for Quirks {
+    fn from(q: PyQuirks) -> Self {
+        return Self {
+            shift: q.shift,
+            memory_increment_by_x: q.memory_increment_by_x,
+            memory_leave_i_unchanged: q.memory_leave_i_unchanged,
+            wrap: q.wrap,
+            jump: q.jump,
+            vblank: q.vblank,
+            logic: q.logic,
+        };
+    }
+}
+
+#[pymethods]
+impl PyQuirks {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        shift = None,
+        memory_increment_by_x = None,
+        memory_leave_i_unchanged = None,
+        wrap = None,
+        jump = None,
+        vblank = None,
+        logic = None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        shift: Option<bool>,
+        memory_increment_by_x: Option<bool>,
+        memory_leave_i_unchanged: Option<bool>,
+        wrap: Option<bool>,
+        jump: Option<bool>,
+        vblank: Option<bool>,
+        logic: Option<bool>,
+    ) -> Self {
+        let q = Quirks::default();
+        return Self {
+            shift: shift.unwrap_or(q.shift),
+            memory_increment_by_x: memory_increment_by_x.unwrap_or(q.memory_increment_by_x),
+            memory_leave_i_unchanged: memory_leave_i_unchanged
+                .unwrap_or(q.memory_leave_i_unchanged),
+            wrap: wrap.unwrap_or(q.wrap),
+            jump: jump.unwrap_or(q.jump),
+            vblank: vblank.unwrap_or(q.vblank),
+            logic: logic.or(q.logic),
+        };
+    }
+
+    fn __repr__(&self) -> String {
+        let name = |b: bool| if b { "True" } else { "False" };
+        return format!(
+            "Quirks(shift={}, memory_increment_by_x={}, memory_leave_i_unchanged={}, wrap={}, jump={}, vblank={}, logic={})",
+            name(self.shift),
+            name(self.memory_increment_by_x),
+            name(self.memory_leave_i_unchanged),
+            name(self.wrap),
+            name(self.jump),
+            name(self.vblank),
+            self.logic.map_or("None", name)
+        );
+    }
+}
+
+/// A CHIP-8 interpreter
+///
+/// An unknown opcode raises `pyo3_runtime.PanicException`.
+// The optional flag store is Send but not Sync, so instances stay on the thread that made them
+#[pyclass(name = "Chip8", module = "chip8", unsendable)]
+struct PyChip8 {
+    chip8: Chip8,
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    #[pyo3(signature = (quirks = None, instructions_per_frame = None))]
+    fn new(quirks: Option<PyQuirks>, instructions_per_frame: Option<u32>) -> Self {
+        let mut chip8 = Chip8::new();
+        if let Some(quirks) = quirks {
+            chip8.set_quirks(quirks.into());
+        }
+        if let Some(count) = instructions_per_frame {
+            chip8.set_instructions_per_frame(count);
+        }
+        return Self { chip8 };
+    }
+
+    /// Load a rom from bytes at the load address, raises ValueError if it is empty or too large
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        return self
+            .chip8
+            .load_game_from_slice(rom)
+            .map_err(|e| PyValueError::new_err(e.to_string()));
+    }
+
+    /// Load a rom file in any format `chip8_loader` understands
+    fn load_rom_file(&mut self, path: &str) -> PyResult<()> {
+        return self
+            .chip8
+            .load_rom(path)
+            .map(|_| ())
+            .map_err(|e| PyValueError::new_err(e.to_string()));
+    }
+
+    /// Run a number of instructions
+    #[pyo3(signature = (cycles = 1))]
+    fn step(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.chip8.emulate_cycle();
+        }
+    }
+
+    /// Run one 60Hz frame worth of instructions
+    fn run_frame(&mut self) {
+        self.chip8.run_frame();
+    }
+
+    /// Decrement the delay and sound timers once, call at 60Hz when running with `step`
+    fn tick_timers(&mut self) {
+        self.chip8.tick_timers();
+    }
+
+    /// Press or release a key of the hex keypad
+    #[pyo3(signature = (key, pressed = true))]
+    fn set_key(&mut self, key: u8, pressed: bool) -> PyResult<()> {
+        if key > 0xF {
+            return Err(PyValueError::new_err("keys are numbered 0 to 15"));
+        }
+        self.chip8.set_key(key, pressed);
+        return Ok(());
+    }
+
+    fn get_key(&self, key: u8) -> PyResult<bool> {
+        if key > 0xF {
+            return Err(PyValueError::new_err("keys are numbered 0 to 15"));
+        }
+        return Ok(self.chip8.get_key(key));
+    }
+
+    /// The display as a read only 32x64 memoryview of bytes, 1 for lit
+    ///
+    /// `numpy.asarray(c.framebuffer())` gives a `(32, 64)` uint8 array.
+    fn framebuffer<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let bytes = PyBytes::new(py, self.chip8.get_gfx());
+        let view = PyMemoryView::from(bytes.as_any())?;
+        return view.call_method1("cast", ("B", (SCREEN_HEIGHT, SCREEN_WIDTH)));
+    }
+
+    /// A copy of the 4KB of memory
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        return PyBytes::new(py, self.chip8.get_memory());
+    }
+
+    /// The registers V0 to VF
+    #[getter]
+    fn v<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        return PyList::new(py, self.chip8.get_registers());
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        return self.chip8.get_i();
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        return self.chip8.get_pc();
+    }
+
+    /// The return addresses on the stack, the most recent call last
+    #[getter]
+    fn stack(&self) -> Vec<u16> {
+        return self.chip8.get_stack().to_vec();
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        return self.chip8.get_delay_timer();
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        return self.chip8.get_sound_timer();
+    }
+
+    #[getter]
+    fn quirks(&self) -> PyQuirks {
+        return self.chip8.get_quirks().into();
+    }
+
+    #[setter]
+    fn set_quirks(&mut self, quirks: PyQuirks) {
+        self.chip8.set_quirks(quirks.into());
+    }
+
+    #[getter]
+    fn instructions_per_frame(&self) -> u32 {
+        return self.chip8.get_instructions_per_frame();
+    }
+
+    #[setter]
+    fn set_instructions_per_frame(&mut self, count: u32) {
+        self.chip8.set_instructions_per_frame(count);
+    }
+
+    /// Save the whole machine as bytes
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        return PyBytes::new(py, &self.chip8.save_state());
+    }
+
+    /// Restore a state from `save_state`, raises ValueError and leaves the machine unchanged if it is invalid
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        return self
+            .chip8
+            .load_state(state)
+            .map_err(|e| PyValueError::new_err(e.to_string()));
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "chip8")]
+fn chip8_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyChip8>()?;
+    m.add_class::<PyQuirks>()?;
+    m.add("SCREEN_WIDTH", SCREEN_WIDTH)?;
+    m.add("SCREEN_HEIGHT", SCREEN_HEIGHT)?;
+    m.add("STATE_SIZE", STATE_SIZE)?;
+    return Ok(());
+}
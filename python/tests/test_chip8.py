@@ -0,0 +1,111 @@
+from pathlib import Path
+
+import pytest
+
+import chip8
+
+TEST_ROM = Path(__file__).resolve().parents[2] / "c8_test.c8"
+
+# What c8_test.c8 draws when every check passes, at rows 16 to 20 and columns 24 to 39
+OK = [
+    "..##....#..#....",
+    ".#..#...#.#.....",
+    ".#..#...##......",
+    ".#..#...#.#.....",
+    "..##....#..#....",
+]
+
+
+def run_test_rom():
+    c = chip8.Chip8()
+    c.load_rom(TEST_ROM.read_bytes())
+    for _ in range(40):
+        c.run_frame()
+    return c
+
+
+def test_test_rom_shows_ok():
+    screen = run_test_rom().framebuffer()
+    assert screen.shape == (chip8.SCREEN_HEIGHT, chip8.SCREEN_WIDTH)
+    drawn = ["".join("#" if screen[y, x] else "." for x in range(24, 40)) for y in range(16, 21)]
+    assert drawn == OK
+    assert sum(screen.tobytes()) == sum(row.count("#") for row in OK)
+
+
+def test_framebuffer_with_numpy():
+    np = pytest.importorskip("numpy")
+    screen = np.asarray(run_test_rom().framebuffer())
+    assert screen.shape == (32, 64)
+    assert screen.dtype == np.uint8
+    assert screen[16:21, 24:40].sum() == sum(row.count("#") for row in OK)
+    assert not screen.flags.writeable
+
+
+def test_load_rom_file():
+    c = chip8.Chip8()
+    c.load_rom_file(str(TEST_ROM))
+    assert c.memory[0x200:0x200 + 4] == TEST_ROM.read_bytes()[:4]
+
+
+def test_quirks():
+    assert chip8.Quirks() == chip8.Chip8().quirks
+    quirks = chip8.Quirks(wrap=True, shift=False)
+    assert quirks.wrap and not quirks.shift and quirks.logic is None
+    c = chip8.Chip8(quirks=quirks, instructions_per_frame=7)
+    assert c.quirks == quirks
+    assert c.instructions_per_frame == 7
+    c.quirks = chip8.Quirks(vblank=True)
+    assert c.quirks.vblank and not c.quirks.wrap
+
+
+def test_step_and_registers():
+    # LD V3, 42 ; LD I, 300 ; CALL 208 ; JP 206 ; LD ST, V3
+    c = chip8.Chip8()
+    c.load_rom(bytes([0x63, 0x2A, 0xA3, 0x00, 0x22, 0x08, 0x12, 0x06, 0xF3, 0x18]))
+    c.step()
+    assert c.v[3] == 42
+    c.step(3)
+    assert c.i == 0x300
+    assert c.pc == 0x20A
+    assert c.stack == [0x206]
+    assert c.sound_timer == 42
+    c.tick_timers()
+    assert c.sound_timer == 41
+    assert len(c.memory) == 4096
+
+
+def test_keys():
+    # SKP V0 ; JP 200 ; JP 204
+    c = chip8.Chip8()
+    c.load_rom(bytes([0xE0, 0x9E, 0x12, 0x00, 0x12, 0x04]))
+    c.step(2)
+    assert c.pc == 0x200
+    c.set_key(0)
+    assert c.get_key(0)
+    c.step()
+    assert c.pc == 0x204
+    c.set_key(0, False)
+    assert not c.get_key(0)
+
+
+def test_save_and_load_state():
+    c = run_test_rom()
+    state = c.save_state()
+    assert len(state) == chip8.STATE_SIZE
+    other = chip8.Chip8()
+    other.load_state(state)
+    assert other.pc == c.pc
+    assert other.v == c.v
+    assert other.framebuffer().tobytes() == c.framebuffer().tobytes()
+
+
+def test_errors():
+    c = chip8.Chip8()
+    with pytest.raises(ValueError):
+        c.load_rom(b"")
+    with pytest.raises(ValueError):
+        c.load_rom(bytes(4096))
+    with pytest.raises(ValueError):
+        c.set_key(16)
+    with pytest.raises(ValueError):
+        c.load_state(b"not a state")
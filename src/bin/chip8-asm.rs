@@ -0,0 +1,39 @@
+use chip8::chip8_assembler::assemble;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+/// Assemble a Chip-8 source file into a rom
+/// # Usage
+/// ```bash
+/// chip8-asm game.asm [game.ch8]
+/// ```
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("usage: chip8-asm <input.asm> [output.ch8]");
+        exit(2);
+    }
+    let input = &args[1];
+    let output = match args.get(2) {
+        Some(output) => PathBuf::from(output),
+        None => Path::new(input).with_extension("ch8"),
+    };
+    let source = match std::fs::read_to_string(input) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            exit(1);
+        }
+    };
+    let rom = match assemble(&source) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("{}:{}", input, e);
+            exit(1);
+        }
+    };
+    if let Err(e) = std::fs::write(&output, rom) {
+        eprintln!("{}: {}", output.display(), e);
+        exit(1);
+    }
+}
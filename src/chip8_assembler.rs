@@ -0,0 +1,532 @@
+use crate::chip8_disassembler::chip8_disassembler::{OpCode, OP_CODES, PROGRAM_START};
+use std::collections::HashMap;
+use std::fmt;
+
+/// An error found while assembling, with the 1-based line and column it was found at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}:{}: {}", self.line, self.column, self.message);
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+/// A numeric literal or a label reference
+#[derive(Debug, Clone)]
+enum Value {
+    Number(u32),
+    Label(String),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Register(u8),
+    Keyword(String),
+    Value(Value),
+}
+
+/// A piece of source text and the column it starts at
+#[derive(Debug, Clone)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+enum Statement {
+    Instruction {
+        op_code: &'static OpCode,
+        operands: Vec<(Operand, usize)>,
+    },
+    Bytes(Vec<(Value, usize)>),
+    Words(Vec<(Value, usize)>),
+    Org(u16),
+}
+
+struct Line {
+    line: usize,
+    statement: Statement,
+}
+
+fn error(line: usize, column: usize, message: String) -> AssemblerError {
+    return AssemblerError {
+        line,
+        column,
+        message,
+    };
+}
+
+/// Parse a numeric literal, `0x` for hex, `0b` for binary and decimal otherwise
+fn parse_number(text: &str) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    if let Some(binary) = lower.strip_prefix("0b") {
+        return u32::from_str_radix(binary, 2).ok();
+    }
+    return lower.parse().ok();
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    return match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+}
+
+fn parse_value(line: usize, token: &Token) -> Result<Value, AssemblerError> {
+    if token.text.starts_with(|c: char| c.is_ascii_digit()) {
+        return match parse_number(token.text) {
+            Some(n) => Ok(Value::Number(n)),
+            None => Err(error(
+                line,
+                token.column,
+                format!("Invalid number {}", token.text),
+            )),
+        };
+    }
+    if is_label(token.text) {
+        return Ok(Value::Label(token.text.to_string()));
+    }
+    return Err(error(
+        line,
+        token.column,
+        format!("Invalid operand {}", token.text),
+    ));
+}
+
+fn parse_operand(line: usize, token: &Token) -> Result<Operand, AssemblerError> {
+    let upper = token.text.to_ascii_uppercase();
+    if upper.len() == 2 && upper.starts_with('V') {
+        if let Ok(register) = u8::from_str_radix(&upper[1..], 16) {
+            return Ok(Operand::Register(register));
+        }
+    }
+    match upper.as_str() {
+        "I" | "[I]" | "DT" | "ST" | "K" | "F" | "HF" | "B" | "R" => {
+            return Ok(Operand::Keyword(upper));
+        }
+        _ => {}
+    }
+    return Ok(Operand::Value(parse_value(line, token)?));
+}
+
+/// Split `text` on `separator`, trimming each piece and keeping track of its column
+fn split_tokens<'a>(text: &'a str, column: usize, separator: char) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for piece in text.split(separator) {
+        let trimmed = piece.trim_start();
+        tokens.push(Token {
+            text: trimmed.trim_end(),
+            column: column + start + piece.len() - trimmed.len(),
+        });
+        start += piece.len() + 1;
+    }
+    return tokens;
+}
+
+/// Check whether the operands fit an opcode template such as `DRW VX, VY, N`
+fn matches_template(op_code: &OpCode, operands: &[(Operand, usize)]) -> bool {
+    let template: Vec<&str> = match op_code.mnemonic.split_once(' ') {
+        Some((_, template)) => template.split(", ").collect(),
+        None => Vec::new(),
+    };
+    let name = template_name(op_code);
+    // SHR and SHL may name the unused VY register
+    let optional_vy = (name == "SHR" || name == "SHL") && operands.len() == 2;
+    if template.len() != operands.len() && !optional_vy {
+        return false;
+    }
+    let template = if optional_vy {
+        vec!["VX", "VY"]
+    } else {
+        template
+    };
+    return template
+        .iter()
+        .zip(operands.iter())
+        .all(|(expected, (operand, _))| match (*expected, operand) {
+            ("VX", Operand::Register(_)) | ("VY", Operand::Register(_)) => true,
+            ("V0", Operand::Register(0)) => true,
+            ("NNN", Operand::Value(_)) | ("KK", Operand::Value(_)) | ("N", Operand::Value(_)) => {
+                true
+            }
+            (expected, Operand::Keyword(keyword)) => expected == keyword,
+            _ => false,
+        });
+}
+
+fn template_name(op_code: &OpCode) -> &'static str {
+    return op_code
+        .mnemonic
+        .split_once(' ')
+        .map_or(op_code.mnemonic, |(name, _)| name);
+}
+
+/// Parse one line of source into its optional label and statement
+fn parse_line(
+    line: usize,
+    text: &str,
+) -> Result<(Option<Token<'_>>, Option<Statement>), AssemblerError> {
+    let text = match text.find(';') {
+        Some(comment) => &text[..comment],
+        None => text,
+    };
+    let mut column = 1;
+    let mut rest = text;
+    let mut label = None;
+    if let Some(colon) = rest.find(':') {
+        let token = split_tokens(&rest[..colon], column, ',').remove(0);
+        if !is_label(token.text) {
+            return Err(error(
+                line,
+                token.column,
+                format!("Invalid label {}", token.text),
+            ));
+        }
+        label = Some(token);
+        column += colon + 1;
+        rest = &rest[colon + 1..];
+    }
+    let trimmed = rest.trim_start();
+    column += rest.len() - trimmed.len();
+    let rest = trimmed.trim_end();
+    if rest.is_empty() {
+        return Ok((label, None));
+    }
+    let (name, operands) = match rest.find(char::is_whitespace) {
+        Some(space) => (&rest[..space], &rest[space..]),
+        None => (rest, ""),
+    };
+    let name_column = column;
+    let operands: Vec<Token> = if operands.trim().is_empty() {
+        Vec::new()
+    } else {
+        split_tokens(operands, column + name.len(), ',')
+    };
+    if let Some(empty) = operands.iter().find(|t| t.text.is_empty()) {
+        return Err(error(line, empty.column, String::from("Missing operand")));
+    }
+    let upper = name.to_ascii_uppercase();
+    let statement = match upper.as_str() {
+        "DB" | "DW" => {
+            let mut values = Vec::new();
+            for token in operands.iter() {
+                values.push((parse_value(line, token)?, token.column));
+            }
+            if values.is_empty() {
+                return Err(error(line, name_column, format!("{} needs a value", upper)));
+            }
+            if upper == "DB" {
+                Statement::Bytes(values)
+            } else {
+                Statement::Words(values)
+            }
+        }
+        "ORG" => {
+            let address = match operands.as_slice() {
+                [token] => match parse_number(token.text) {
+                    Some(address) if address <= 0xFFF => address as u16,
+                    _ => {
+                        return Err(error(
+                            line,
+                            token.column,
+                            format!("Invalid origin {}", token.text),
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(error(
+                        line,
+                        name_column,
+                        String::from("ORG needs an address"),
+                    ))
+                }
+            };
+            Statement::Org(address)
+        }
+        _ => {
+            let mut parsed = Vec::new();
+            for token in operands.iter() {
+                parsed.push((parse_operand(line, token)?, token.column));
+            }
+            let mut candidates = OP_CODES
+                .iter()
+                .filter(|op_code| template_name(op_code) == upper)
+                .peekable();
+            if candidates.peek().is_none() {
+                return Err(error(
+                    line,
+                    name_column,
+                    format!("Unknown mnemonic {}", name),
+                ));
+            }
+            match candidates.find(|op_code| matches_template(op_code, &parsed)) {
+                Some(op_code) => Statement::Instruction {
+                    op_code,
+                    operands: parsed,
+                },
+                None => {
+                    let column = operands.first().map_or(name_column, |t| t.column);
+                    return Err(error(
+                        line,
+                        column,
+                        format!("Invalid operands for {}", upper),
+                    ));
+                }
+            }
+        }
+    };
+    return Ok((label, Some(statement)));
+}
+
+fn statement_size(statement: &Statement) -> usize {
+    return match statement {
+        Statement::Instruction { .. } => 2,
+        Statement::Bytes(values) => values.len(),
+        Statement::Words(values) => values.len() * 2,
+        Statement::Org(_) => 0,
+    };
+}
+
+fn resolve(
+    labels: &HashMap<String, u16>,
+    line: usize,
+    column: usize,
+    value: &Value,
+    max: u32,
+) -> Result<u32, AssemblerError> {
+    let n = match value {
+        Value::Number(n) => *n,
+        Value::Label(name) => match labels.get(name) {
+            Some(address) => *address as u32,
+            None => return Err(error(line, column, format!("Undefined label {}", name))),
+        },
+    };
+    if n > max {
+        return Err(error(
+            line,
+            column,
+            format!("Value 0x{:X} is larger than 0x{:X}", n, max),
+        ));
+    }
+    return Ok(n);
+}
+
+fn encode(
+    labels: &HashMap<String, u16>,
+    line: usize,
+    op_code: &OpCode,
+    operands: &[(Operand, usize)],
+) -> Result<u16, AssemblerError> {
+    let mut code = op_code.code;
+    let mut registers = 0;
+    for (operand, column) in operands.iter() {
+        match operand {
+            Operand::Register(r) if op_code.code & 0xF000 == 0xB000 => {
+                // JP V0, NNN names V0 explicitly
+                debug_assert_eq!(*r, 0);
+            }
+            Operand::Register(r) => {
+                code |= (*r as u16) << (8 - 4 * registers);
+                registers += 1;
+            }
+            Operand::Keyword(_) => {}
+            Operand::Value(value) => {
+                let max = match op_code.code_string.chars().filter(|c| *c == 'N').count() {
+                    3 => 0xFFF,
+                    1 => 0xF,
+                    _ => 0xFF,
+                };
+                code |= resolve(labels, line, *column, value, max)? as u16;
+            }
+        }
+    }
+    return Ok(code);
+}
+
+/// Assemble Chip-8 source into rom bytes
+///
+/// The syntax is the one produced by the disassembler: one instruction per line such as
+/// `LD VX, KK` or `DRW VX, VY, N`, `label:` definitions, `;` comments, numbers in hex
+/// (`0x1F`), binary (`0b0101`) or decimal, `DB`/`DW` data and an `ORG` directive. The rom
+/// starts at the first `ORG` address, or 0x200 when there is none.
+/// # Arguments
+/// * `source` - The assembly source
+/// # Returns
+/// * The assembled rom, or the first error found
+/// # Example
+/// ```
+/// use chip8::chip8_assembler::assemble;
+/// let rom = assemble("start: CLS\n JP start").expect("fail");
+/// assert_eq!(rom, [0x00, 0xE0, 0x12, 0x00]);
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let mut lines = Vec::new();
+    let mut labels = HashMap::new();
+    let mut origin = None;
+    let mut address = PROGRAM_START as usize;
+    for (number, text) in source.lines().enumerate() {
+        let number = number + 1;
+        let (label, statement) = parse_line(number, text)?;
+        if let Some(Statement::Org(to)) = statement {
+            // Only an ORG before the first byte moves the start of the rom
+            if lines
+                .iter()
+                .all(|l: &Line| statement_size(&l.statement) == 0)
+            {
+                origin = Some(to);
+            }
+            address = to as usize;
+        }
+        if let Some(label) = label {
+            if labels
+                .insert(label.text.to_string(), address as u16)
+                .is_some()
+            {
+                return Err(error(
+                    number,
+                    label.column,
+                    format!("Duplicate label {}", label.text),
+                ));
+            }
+        }
+        if let Some(statement) = statement {
+            address += statement_size(&statement);
+            if address > 0x1000 {
+                return Err(error(
+                    number,
+                    1,
+                    String::from("Program does not fit in memory"),
+                ));
+            }
+            lines.push(Line {
+                line: number,
+                statement,
+            });
+        }
+    }
+    let origin = origin.unwrap_or(PROGRAM_START) as usize;
+    let mut rom = Vec::new();
+    for Line { line, statement } in lines.iter() {
+        match statement {
+            Statement::Org(to) => {
+                let to = *to as usize;
+                if to < origin + rom.len() {
+                    return Err(error(*line, 1, format!("ORG 0x{:03X} moves backwards", to)));
+                }
+                rom.resize(to - origin, 0);
+            }
+            Statement::Instruction { op_code, operands } => {
+                let code = encode(&labels, *line, op_code, operands)?;
+                rom.extend_from_slice(&code.to_be_bytes());
+            }
+            Statement::Bytes(values) => {
+                for (value, column) in values.iter() {
+                    rom.push(resolve(&labels, *line, *column, value, 0xFF)? as u8);
+                }
+            }
+            Statement::Words(values) => {
+                for (value, column) in values.iter() {
+                    let word = resolve(&labels, *line, *column, value, 0xFFFF)? as u16;
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+        }
+    }
+    return Ok(rom);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8_disassembler::chip8_disassembler::dissasemble_rom_listing;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_assemble_instructions() {
+        let source = "
+            CLS
+            LD V1, 0x12
+            LD VA, VB
+            DRW V0, V1, 5
+            LD [I], VF
+            LD VF, [I]
+            JP V0, 0x300
+            SHR V3
+            SHR V3, V4
+            LD HF, V2
+            SCD 0b0011
+        ";
+        let rom = assemble(source).expect("fail");
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x61, 0x12, 0x8A, 0xB0, 0xD0, 0x15, 0xFF, 0x55, 0xFF, 0x65, 0xB3, 0x00,
+                0x83, 0x06, 0x83, 0x46, 0xF2, 0x30, 0x00, 0xC3
+            ]
+        );
+    }
+    #[test]
+    fn test_assemble_labels_and_data() {
+        let source = "
+            ORG 0x600
+            LD I, sprite ; forward reference
+        loop:
+            JP loop
+        sprite:
+            DB 0xF0, 144, 0b11110000
+            DW 0x1234
+        ";
+        let rom = assemble(source).expect("fail");
+        assert_eq!(rom, [0xA6, 0x04, 0x16, 0x02, 0xF0, 0x90, 0xF0, 0x12, 0x34]);
+    }
+    #[test]
+    fn test_assemble_org_pads() {
+        let rom = assemble("DB 1\nORG 0x204\nDB 2").expect("fail");
+        assert_eq!(rom, [1, 0, 0, 0, 2]);
+    }
+    #[test]
+    fn test_assemble_errors() {
+        let err = assemble("CLS\n  FOO V1").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        let err = assemble("LD V1, 0x100").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 8));
+        let err = assemble("JP nowhere").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 4));
+        assert_eq!(err.to_string(), "1:4: Undefined label nowhere");
+        let err = assemble("a: CLS\na: CLS").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        let err = assemble("LD DT, 5").unwrap_err();
+        assert_eq!(err.message, "Invalid operands for LD");
+        let err = assemble("DRW V1, , 5").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 9));
+    }
+    #[test]
+    fn test_round_trip_test_rom() {
+        let bytes = std::fs::read("c8_test.c8").expect("Failed to load game");
+        let listing = dissasemble_rom_listing(&bytes);
+        assert_eq!(assemble(&listing).expect("fail"), bytes);
+    }
+    #[test]
+    fn test_round_trip_random_roms() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(8);
+        for _ in 0..200 {
+            let len = rng.gen_range(1..256);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let listing = dissasemble_rom_listing(&bytes);
+            assert_eq!(assemble(&listing).expect("fail"), bytes, "{}", listing);
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use crate::chip8_frontend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::chip8_observer::NoObserver;
+use crate::{Chip8, Core, LoadError, Quirks, FLAG_COUNT};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// The size of one machine's framebuffer in `Batch::framebuffers`
+pub const FRAMEBUFFER_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+/// Many interpreters run together, for training agents and mass regression testing
+///
+/// Machines are kept a field at a time: the memories of every machine in one array, the
+/// program counters in another, then I, the registers, the timers, the screens and so on.
+/// `set_threads` splits the machines into one group per thread and starts threads that live as
+/// long as the batch, so `run_frames` only hands each group over and waits for it to come back.
+///
+/// `run_frames` is not strict lockstep: each thread runs one machine through all of the frames
+/// before it starts the next. Machines are independent, so a batch gives the same results as
+/// running each machine on its own, whatever the number of threads.
+/// # Example
+/// ```
+/// use chip8::chip8_batch::{Batch, FRAMEBUFFER_SIZE};
+/// // CLS ; LD F, V0 ; DRW V0, V0, 5 ; JP 206
+/// let mut batch = Batch::from_rom(&[0x00, 0xE0, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06], 100)
+///     .expect("fail");
+/// batch.set_threads(4);
+/// batch.run_frames(1);
+/// assert_eq!(batch.framebuffers().len(), 100 * FRAMEBUFFER_SIZE);
+/// assert_eq!(batch.framebuffer(99)[..4], [1, 1, 1, 1]);
+/// ```
+pub struct Batch {
+    /// The machines, split into one shard per thread
+    shards: Vec<Shard>,
+    /// The number of machines in every shard but the last
+    shard_size: usize,
+    /// The keys each machine holds, bit N for key N
+    keys: Vec<u16>,
+    /// Every machine's display, copied out of the shards after each `run_frames`
+    framebuffers: Vec<u8>,
+    /// One worker per shard after the first, which runs on the calling thread
+    workers: Vec<Worker>,
+}
+
+/// A group of machines with each field in its own array, indexed by machine
+#[derive(Default)]
+struct Shard {
+    memory: Vec<[u8; 4096]>,
+    v: Vec<[u8; 16]>,
+    i: Vec<u16>,
+    pc: Vec<u16>,
+    gfx: Vec<[u8; FRAMEBUFFER_SIZE]>,
+    extended_gfx_mode: Vec<bool>,
+    delay_timer: Vec<u8>,
+    sound_timer: Vec<u8>,
+    stack: Vec<[u16; 16]>,
+    sp: Vec<u16>,
+    keys: Vec<u16>,
+    awaited_key: Vec<Option<u8>>,
+    quirks: Vec<Quirks>,
+    instructions_per_frame: Vec<u32>,
+    load_address: Vec<u16>,
+    font_address: Vec<u16>,
+    flags: Vec<[u8; FLAG_COUNT]>,
+    rom_hash: Vec<Option<String>>,
+    rng_state: Vec<u32>,
+}
+
+/// A thread that runs the shards it is sent and sends them back, until the batch is dropped
+struct Worker {
+    jobs: Sender<(Shard, u32)>,
+    done: Receiver<Shard>,
+}
+
+impl Batch {
+    /// Batch up machines that are already set up
+    ///
+    /// Flag stores are not carried over, `FX75` keeps the flags in the batch.
+    pub fn new(machines: Vec<Chip8>) -> Self {
+        let mut batch = Self {
+            shards: Vec::new(),
+            shard_size: 0,
+            keys: Vec::new(),
+            framebuffers: Vec::new(),
+            workers: Vec::new(),
+        };
+        batch.split(machines, 1);
+        return batch;
+    }
+
+    /// Load the same rom into `count` new machines, each with its own random seed
+    pub fn from_rom(rom: &[u8], count: usize) -> Result<Self, LoadError> {
+        let mut machines = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut c = Chip8::new();
+            c.load_game_from_slice(rom)?;
+            machines.push(c);
+        }
+        return Ok(Self::new(machines));
+    }
+
+    /// Set how many threads `run_frames` splits the machines between
+    ///
+    /// The threads are started here and reused by every `run_frames` until this is called again
+    /// or the batch is dropped.
+    /// # Arguments
+    /// * `threads` - 1 to run on the calling thread, 0 for one thread per available core
+    pub fn set_threads(&mut self, threads: usize) {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let machines = (0..self.len()).map(|index| self.machine(index)).collect();
+        self.split(machines, threads);
+    }
+
+    /// Lay the machines out in shards for `threads` threads and start the workers
+    fn split(&mut self, machines: Vec<Chip8>, threads: usize) {
+        let count = machines.len();
+        self.shard_size = count.div_ceil(threads.clamp(1, count.max(1))).max(1);
+        self.keys = machines.iter().map(|c| key_bits(&c.key)).collect();
+        self.shards.clear();
+        for (index, c) in machines.into_iter().enumerate() {
+            if index % self.shard_size == 0 {
+                self.shards.push(Shard::default());
+            }
+            self.shards.last_mut().expect("pushed above").push(c);
+        }
+        // Dropping the old workers' channels ends their threads
+        self.workers = (1..self.shards.len()).map(|_| Worker::spawn()).collect();
+        self.copy_framebuffers();
+    }
+
+    /// Copy every machine's display into `framebuffers`
+    fn copy_framebuffers(&mut self) {
+        self.framebuffers.resize(self.len() * FRAMEBUFFER_SIZE, 0);
+        let gfx = self.shards.iter().flat_map(|shard| shard.gfx.iter());
+        for (to, from) in self.framebuffers.chunks_mut(FRAMEBUFFER_SIZE).zip(gfx) {
+            to.copy_from_slice(from);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.keys.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.keys.is_empty();
+    }
+
+    /// Set the keys a machine holds from the next frame on
+    /// # Arguments
+    /// * `index` - The machine
+    /// * `keys` - Bit N set to hold key N
+    pub fn set_keys(&mut self, index: usize, keys: u16) {
+        self.keys[index] = keys;
+    }
+
+    /// The keys of every machine, to set them all at once
+    pub fn keys_mut(&mut self) -> &mut [u16] {
+        return &mut self.keys;
+    }
+
+    /// Run every machine for a number of frames, holding its keys
+    /// # Panics
+    /// If a machine meets an unknown opcode, the batch should not be used afterwards
+    pub fn run_frames(&mut self, frames: u32) {
+        for (shard, keys) in self
+            .shards
+            .iter_mut()
+            .zip(self.keys.chunks(self.shard_size))
+        {
+            shard.keys.copy_from_slice(keys);
+        }
+        let others = self.shards.split_off(self.shards.len().min(1));
+        for (worker, shard) in self.workers.iter().zip(others) {
+            worker
+                .jobs
+                .send((shard, frames))
+                .expect("the worker is running");
+        }
+        if let Some(shard) = self.shards.first_mut() {
+            shard.run(frames);
+        }
+        for worker in self.workers.iter() {
+            let shard = worker.done.recv().expect("a machine in the batch panicked");
+            self.shards.push(shard);
+        }
+        self.copy_framebuffers();
+    }
+
+    /// Every machine's display in one slice, `FRAMEBUFFER_SIZE` bytes per machine in order, each
+    /// laid out as `Chip8::get_gfx`
+    pub fn framebuffers(&self) -> &[u8] {
+        return &self.framebuffers;
+    }
+
+    /// One machine's display
+    pub fn framebuffer(&self, index: usize) -> &[u8] {
+        let (shard, m) = self.locate(index);
+        return &self.shards[shard].gfx[m];
+    }
+
+    /// Whether a machine's buzzer is sounding
+    pub fn sound(&self, index: usize) -> bool {
+        let (shard, m) = self.locate(index);
+        return self.shards[shard].sound_timer[m] > 0;
+    }
+
+    /// A copy of one machine, to save its state or inspect it
+    pub fn machine(&self, index: usize) -> Chip8 {
+        let (shard, m) = self.locate(index);
+        let mut c = self.shards[shard].get(m);
+        c.key = key_array(self.keys[index]);
+        return c;
+    }
+
+    /// Replace one machine, to load a state or change its settings between frames
+    pub fn set_machine(&mut self, index: usize, c: Chip8) {
+        let (shard, m) = self.locate(index);
+        self.keys[index] = key_bits(&c.key);
+        let framebuffer = index * FRAMEBUFFER_SIZE;
+        self.framebuffers[framebuffer..framebuffer + FRAMEBUFFER_SIZE].copy_from_slice(&c.gfx);
+        self.shards[shard].set(m, c);
+    }
+
+    /// The shard holding a machine and its index there
+    fn locate(&self, index: usize) -> (usize, usize) {
+        assert!(index < self.len(), "machine {} of {}", index, self.len());
+        return (index / self.shard_size, index % self.shard_size);
+    }
+}
+
+impl Shard {
+    fn push(&mut self, c: Chip8) {
+        self.memory.push(c.memory);
+        self.v.push(c.v);
+        self.i.push(c.i);
+        self.pc.push(c.pc);
+        self.gfx.push(c.gfx);
+        self.extended_gfx_mode.push(c.extended_gfx_mode);
+        self.delay_timer.push(c.delay_timer);
+        self.sound_timer.push(c.sound_timer);
+        self.stack.push(c.stack);
+        self.sp.push(c.sp);
+        self.keys.push(key_bits(&c.key));
+        self.awaited_key.push(c.awaited_key);
+        self.quirks.push(c.quirks);
+        self.instructions_per_frame.push(c.instructions_per_frame);
+        self.load_address.push(c.load_address);
+        self.font_address.push(c.font_address);
+        self.flags.push(c.flags);
+        self.rom_hash.push(c.rom_hash);
+        self.rng_state.push(c.rng_state);
+    }
+
+    fn set(&mut self, m: usize, c: Chip8) {
+        self.memory[m] = c.memory;
+        self.v[m] = c.v;
+        self.i[m] = c.i;
+        self.pc[m] = c.pc;
+        self.gfx[m] = c.gfx;
+        self.extended_gfx_mode[m] = c.extended_gfx_mode;
+        self.delay_timer[m] = c.delay_timer;
+        self.sound_timer[m] = c.sound_timer;
+        self.stack[m] = c.stack;
+        self.sp[m] = c.sp;
+        self.keys[m] = key_bits(&c.key);
+        self.awaited_key[m] = c.awaited_key;
+        self.quirks[m] = c.quirks;
+        self.instructions_per_frame[m] = c.instructions_per_frame;
+        self.load_address[m] = c.load_address;
+        self.font_address[m] = c.font_address;
+        self.flags[m] = c.flags;
+        self.rom_hash[m] = c.rom_hash;
+        self.rng_state[m] = c.rng_state;
+    }
+
+    fn get(&self, m: usize) -> Chip8 {
+        let mut c = Chip8::new();
+        c.memory = self.memory[m];
+        c.v = self.v[m];
+        c.i = self.i[m];
+        c.pc = self.pc[m];
+        c.gfx = self.gfx[m];
+        c.extended_gfx_mode = self.extended_gfx_mode[m];
+        c.delay_timer = self.delay_timer[m];
+        c.sound_timer = self.sound_timer[m];
+        c.stack = self.stack[m];
+        c.sp = self.sp[m];
+        c.key = key_array(self.keys[m]);
+        c.awaited_key = self.awaited_key[m];
+        c.quirks = self.quirks[m];
+        c.instructions_per_frame = self.instructions_per_frame[m];
+        c.load_address = self.load_address[m];
+        c.font_address = self.font_address[m];
+        c.flags = self.flags[m];
+        c.rom_hash = self.rom_hash[m].clone();
+        c.rng_state = self.rng_state[m];
+        return c;
+    }
+
+    /// Run every machine of the shard for a number of frames
+    fn run(&mut self, frames: u32) {
+        let mut flag_store = None;
+        for m in 0..self.pc.len() {
+            let key = key_array(self.keys[m]);
+            let mut core = Core {
+                memory: &mut self.memory[m],
+                v: &mut self.v[m],
+                i: &mut self.i[m],
+                pc: &mut self.pc[m],
+                gfx: &mut self.gfx[m],
+                extended_gfx_mode: &mut self.extended_gfx_mode[m],
+                delay_timer: &mut self.delay_timer[m],
+                sound_timer: &mut self.sound_timer[m],
+                stack: &mut self.stack[m],
+                sp: &mut self.sp[m],
+                key: &key,
+                awaited_key: &mut self.awaited_key[m],
+                quirks: self.quirks[m],
+                instructions_per_frame: self.instructions_per_frame[m],
+                load_address: self.load_address[m],
+                font_address: self.font_address[m],
+                flags: &mut self.flags[m],
+                flag_store: &mut flag_store,
+                rom_hash: &self.rom_hash[m],
+                rng_state: &mut self.rng_state[m],
+            };
+            for _ in 0..frames {
+                core.run_frame_with(&mut NoObserver);
+            }
+        }
+    }
+}
+
+impl Worker {
+    fn spawn() -> Self {
+        let (jobs, inbox) = channel::<(Shard, u32)>();
+        let (outbox, done) = channel();
+        std::thread::spawn(move || {
+            for (mut shard, frames) in inbox {
+                shard.run(frames);
+                if outbox.send(shard).is_err() {
+                    return;
+                }
+            }
+        });
+        return Self { jobs, done };
+    }
+}
+
+/// The keys held as `Chip8::set_key` stores them
+fn key_array(keys: u16) -> [u8; 16] {
+    return std::array::from_fn(|key| (keys >> key & 1) as u8);
+}
+
+fn key_bits(key: &[u8; 16]) -> u16 {
+    return (0..16).filter(|k| key[*k] != 0).map(|k| 1 << k).sum();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8_env::bundled_game;
+
+    fn machines(rom: &[u8], count: usize) -> Vec<Chip8> {
+        return (0..count)
+            .map(|index| {
+                let mut c = Chip8::new();
+                c.load_game_from_slice(rom).expect("fail");
+                c.set_rng_seed(index as u32 + 1);
+                c.set_instructions_per_frame(100);
+                return c;
+            })
+            .collect();
+    }
+    #[test]
+    fn test_matches_single_machines() {
+        let rom = bundled_game("catch").expect("fail");
+        let count = 37;
+        let mut singles = machines(&rom, count);
+        let mut batches: Vec<Batch> = [1, 4, 64]
+            .iter()
+            .map(|threads| {
+                let mut batch = Batch::new(machines(&rom, count));
+                batch.set_threads(*threads);
+                return batch;
+            })
+            .collect();
+        for step in 0..50 {
+            let keys = |index: usize| match (index + step) % 3 {
+                0 => 0,
+                1 => 1 << 4,
+                _ => 1 << 6,
+            };
+            for (index, c) in singles.iter_mut().enumerate() {
+                for key in 0..16 {
+                    c.set_key(key, keys(index) & 1 << key != 0);
+                }
+                for _ in 0..3 {
+                    c.run_frame();
+                }
+            }
+            for batch in batches.iter_mut() {
+                for (index, k) in batch.keys_mut().iter_mut().enumerate() {
+                    *k = keys(index);
+                }
+                batch.run_frames(3);
+                for (index, c) in singles.iter().enumerate() {
+                    assert_eq!(batch.framebuffer(index), c.get_gfx());
+                    let framebuffer = &batch.framebuffers()[index * FRAMEBUFFER_SIZE..];
+                    assert_eq!(&framebuffer[..FRAMEBUFFER_SIZE], c.get_gfx());
+                    assert_eq!(batch.sound(index), c.get_sound_timer() > 0);
+                    assert_eq!(batch.machine(index).save_state(), c.save_state());
+                }
+            }
+        }
+        // Different seeds lead to different games
+        assert_ne!(batches[0].framebuffer(0), batches[0].framebuffer(1));
+    }
+    #[test]
+    fn test_set_machine() {
+        let rom = bundled_game("catch").expect("fail");
+        let mut batch = Batch::new(machines(&rom, 10));
+        batch.set_threads(3);
+        batch.run_frames(30);
+        let saved = batch.machine(7).save_state();
+        batch.set_threads(2);
+        batch.run_frames(30);
+        assert_ne!(batch.machine(7).save_state(), saved);
+        let mut c = Chip8::new();
+        c.load_state(&saved).expect("fail");
+        batch.set_machine(7, c);
+        assert_eq!(batch.machine(7).save_state(), saved);
+        let framebuffers = batch.framebuffers().chunks(FRAMEBUFFER_SIZE);
+        assert_eq!(framebuffers.clone().nth(7), Some(batch.framebuffer(7)));
+        assert_eq!(framebuffers.count(), 10);
+    }
+    #[test]
+    fn test_empty() {
+        let mut batch = Batch::new(Vec::new());
+        batch.set_threads(0);
+        batch.run_frames(1);
+        assert!(batch.is_empty());
+        assert!(batch.framebuffers().is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+use crate::chip8_disassembler::chip8_disassembler::{
+    decode_instruction, find_code, flow_of, CowgodSyntax, DisassembledInstruction, Flow, Syntax,
+    PROGRAM_START,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How control gets from one basic block to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Execution runs on into the next block
+    Fallthrough,
+    /// An unconditional `JP`
+    Jump,
+    /// A skip instruction whose condition held
+    Skip,
+    /// A `CALL` into a subroutine
+    Call,
+    /// A `RET` back to the instruction after a call
+    Return,
+}
+
+/// A run of instructions that is only entered at the top and only left at the bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: u16,
+    pub instructions: Vec<DisassembledInstruction>,
+    /// The block ends in a `JP V0, NNN` whose targets are not known
+    pub unresolved: bool,
+}
+
+impl BasicBlock {
+    fn last(&self) -> &DisassembledInstruction {
+        return self.instructions.last().expect("blocks are never empty");
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: u16,
+    pub to: u16,
+    pub kind: EdgeKind,
+}
+
+/// The control flow graph of the reachable code in a rom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl ControlFlowGraph {
+    /// Build the control flow graph of a rom loaded at 0x200
+    /// # Arguments
+    /// * `bytes` - The rom
+    /// # Example
+    /// ```
+    /// use chip8::chip8_cfg::{ControlFlowGraph, EdgeKind};
+    /// // SE V0, 1 ; CLS ; JP 200
+    /// let cfg = ControlFlowGraph::from_rom(&[0x30, 0x01, 0x00, 0xE0, 0x12, 0x00]);
+    /// assert_eq!(cfg.blocks.len(), 3);
+    /// assert!(cfg.edges.iter().any(|e| e.kind == EdgeKind::Skip && e.to == 0x204));
+    /// ```
+    pub fn from_rom(bytes: &[u8]) -> Self {
+        return Self::from_rom_at(bytes, PROGRAM_START);
+    }
+
+    /// Build the control flow graph of a rom loaded at `origin`
+    /// # Arguments
+    /// * `bytes` - The rom
+    /// * `origin` - The address the rom is loaded at, which is also the entry point
+    pub fn from_rom_at(bytes: &[u8], origin: u16) -> Self {
+        let is_code = find_code(bytes, origin);
+        let mut instructions = BTreeMap::new();
+        for offset in (0..bytes.len()).filter(|o| is_code[*o]).step_by(2) {
+            let Some(address) = u16::try_from(offset)
+                .ok()
+                .and_then(|o| origin.checked_add(o))
+            else {
+                break;
+            };
+            let code = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+            let instruction = decode_instruction(address, code).expect("reachable code is valid");
+            instructions.insert(address, instruction);
+        }
+
+        // Leaders past the end of the address space are dropped, as no block can start there
+        let mut leaders = BTreeSet::from([origin]);
+        for (address, instruction) in instructions.iter() {
+            let next = address.checked_add(2);
+            match flow_of(instruction.code()) {
+                Flow::Next => {}
+                Flow::Skip => {
+                    leaders.extend(next);
+                    leaders.extend(address.checked_add(4));
+                }
+                Flow::Jump(target) | Flow::Call(target) => {
+                    leaders.insert(target);
+                    leaders.extend(next);
+                }
+                Flow::ComputedJump(_) | Flow::Return | Flow::Exit => {
+                    leaders.extend(next);
+                }
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (address, instruction) in instructions.iter() {
+            let continues = blocks.last().is_some_and(|block| {
+                block.last().address.checked_add(2) == Some(*address)
+                    && !leaders.contains(address)
+                    && flow_of(block.last().code()) == Flow::Next
+            });
+            if continues {
+                blocks
+                    .last_mut()
+                    .expect("continues needs a block")
+                    .instructions
+                    .push(instruction.clone());
+            } else {
+                blocks.push(BasicBlock {
+                    start: *address,
+                    instructions: vec![instruction.clone()],
+                    unresolved: false,
+                });
+            }
+        }
+
+        let starts: BTreeSet<u16> = blocks.iter().map(|b| b.start).collect();
+        let mut edges = Vec::new();
+        let mut add = |from: u16, to: Option<u16>, kind: EdgeKind| {
+            if let Some(to) = to.filter(|to| starts.contains(to)) {
+                edges.push(Edge { from, to, kind });
+            }
+        };
+        let mut return_sites: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        for block in blocks.iter_mut() {
+            let last = block.last().address;
+            let next = last.checked_add(2);
+            match flow_of(block.last().code()) {
+                Flow::Next => add(block.start, next, EdgeKind::Fallthrough),
+                Flow::Skip => {
+                    add(block.start, next, EdgeKind::Fallthrough);
+                    add(block.start, last.checked_add(4), EdgeKind::Skip);
+                }
+                Flow::Jump(target) => add(block.start, Some(target), EdgeKind::Jump),
+                Flow::Call(target) => {
+                    add(block.start, next, EdgeKind::Fallthrough);
+                    add(block.start, Some(target), EdgeKind::Call);
+                    return_sites.entry(target).or_default().extend(next);
+                }
+                Flow::ComputedJump(_) => block.unresolved = true,
+                Flow::Return | Flow::Exit => {}
+            }
+        }
+
+        // A RET returns to every call site of the subroutines it can be reached from. Only the
+        // edges within a subroutine are followed, never the returns found for another one.
+        let mut returns = Vec::new();
+        for (subroutine, sites) in return_sites.iter() {
+            let mut seen = BTreeSet::new();
+            let mut pending = vec![*subroutine];
+            while let Some(start) = pending.pop() {
+                if !seen.insert(start) {
+                    continue;
+                }
+                pending.extend(
+                    edges
+                        .iter()
+                        .filter(|e| e.from == start && e.kind != EdgeKind::Call)
+                        .map(|e| e.to),
+                );
+            }
+            for block in blocks.iter().filter(|b| seen.contains(&b.start)) {
+                if flow_of(block.last().code()) == Flow::Return {
+                    for site in sites.iter().filter(|s| starts.contains(s)) {
+                        returns.push(Edge {
+                            from: block.start,
+                            to: *site,
+                            kind: EdgeKind::Return,
+                        });
+                    }
+                }
+            }
+        }
+        edges.extend(returns);
+        edges.sort_by_key(|e| (e.from, e.to));
+        return Self { blocks, edges };
+    }
+
+    /// Export the graph in Graphviz DOT format
+    ///
+    /// Each node lists the disassembled instructions of a block. Fallthrough edges are solid,
+    /// jumps bold, taken skips dashed, calls blue and returns dotted. Blocks that end in an
+    /// unresolved `JP V0, NNN` are drawn in red.
+    /// # Example
+    /// ```
+    /// use chip8::chip8_cfg::ControlFlowGraph;
+    /// let cfg = ControlFlowGraph::from_rom(&[0x00, 0xE0, 0x12, 0x00]);
+    /// let dot = cfg.to_dot();
+    /// assert!(dot.starts_with("digraph cfg {"));
+    /// assert!(dot.contains("\"L200\" -> \"L200\" [style=bold];"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut ret = String::from("digraph cfg {\n");
+        ret += "    node [shape=box, fontname=\"monospace\"];\n";
+        for block in self.blocks.iter() {
+            let mut label = String::new();
+            for instruction in block.instructions.iter() {
+                label += &format!(
+                    "{:03X}: {}\\l",
+                    instruction.address,
+                    CowgodSyntax::default().format(instruction)
+                );
+            }
+            if block.unresolved {
+                label += "unresolved computed jump\\l";
+                ret += &format!(
+                    "    \"L{:03X}\" [label=\"{}\", color=red];\n",
+                    block.start, label
+                );
+            } else {
+                ret += &format!("    \"L{:03X}\" [label=\"{}\"];\n", block.start, label);
+            }
+        }
+        for edge in self.edges.iter() {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "style=solid",
+                EdgeKind::Jump => "style=bold",
+                EdgeKind::Skip => "style=dashed, label=\"skip\"",
+                EdgeKind::Call => "color=blue, label=\"call\"",
+                EdgeKind::Return => "style=dotted, label=\"ret\"",
+            };
+            ret += &format!(
+                "    \"L{:03X}\" -> \"L{:03X}\" [{}];\n",
+                edge.from, edge.to, style
+            );
+        }
+        ret += "}\n";
+        return ret;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: u16, to: u16, kind: EdgeKind) -> Edge {
+        return Edge { from, to, kind };
+    }
+
+    #[test]
+    fn test_blocks_and_edges() {
+        let bytes = [
+            0x22, 0x0A, // 200: CALL 20A
+            0x30, 0x01, // 202: SE V0, 01
+            0x00, 0xE0, // 204: CLS
+            0x12, 0x00, // 206: JP 200
+            0xFF, 0xFF, // 208: data
+            0x60, 0x01, // 20A: LD V0, 01
+            0x00, 0xEE, // 20C: RET
+        ];
+        let cfg = ControlFlowGraph::from_rom(&bytes);
+        let starts: Vec<u16> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(starts, [0x200, 0x202, 0x204, 0x206, 0x20A]);
+        assert_eq!(cfg.blocks[4].instructions.len(), 2);
+        assert_eq!(
+            cfg.edges,
+            [
+                edge(0x200, 0x202, EdgeKind::Fallthrough),
+                edge(0x200, 0x20A, EdgeKind::Call),
+                edge(0x202, 0x204, EdgeKind::Fallthrough),
+                edge(0x202, 0x206, EdgeKind::Skip),
+                edge(0x204, 0x206, EdgeKind::Fallthrough),
+                edge(0x206, 0x200, EdgeKind::Jump),
+                edge(0x20A, 0x202, EdgeKind::Return),
+            ]
+        );
+    }
+    #[test]
+    fn test_shared_return_tail() {
+        let bytes = [
+            0x22, 0x08, // 200: CALL 208
+            0x22, 0x0E, // 202: CALL 20E
+            0x12, 0x04, // 204: JP 204
+            0xFF, 0xFF, // 206: data
+            0x22, 0x0C, // 208: CALL 20C
+            0x00, 0xEE, // 20A: RET
+            0x60, 0x01, // 20C: LD V0, 01
+            0x00, 0xEE, // 20E: RET, shared by the subroutines at 20C and 20E
+        ];
+        let cfg = ControlFlowGraph::from_rom(&bytes);
+        let returns: Vec<Edge> = cfg
+            .edges
+            .into_iter()
+            .filter(|e| e.kind == EdgeKind::Return)
+            .collect();
+        assert_eq!(
+            returns,
+            [
+                edge(0x20A, 0x202, EdgeKind::Return),
+                edge(0x20E, 0x204, EdgeKind::Return),
+                edge(0x20E, 0x20A, EdgeKind::Return),
+            ]
+        );
+    }
+    #[test]
+    fn test_end_of_address_space() {
+        // SE V0, 01 ; CLS, with the skip and the fallthrough from the CLS both past FFFF
+        let cfg = ControlFlowGraph::from_rom_at(&[0x30, 0x01, 0x00, 0xE0], 0xFFFC);
+        let starts: Vec<u16> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(starts, [0xFFFC, 0xFFFE]);
+        assert_eq!(cfg.edges, [edge(0xFFFC, 0xFFFE, EdgeKind::Fallthrough)]);
+    }
+    #[test]
+    fn test_unresolved_computed_jump() {
+        // JP V0, 204 ; RET ; JP 200
+        let cfg = ControlFlowGraph::from_rom(&[0xB2, 0x04, 0x00, 0xEE, 0x12, 0x00]);
+        assert!(cfg.blocks[0].unresolved);
+        assert!(cfg.edges.iter().all(|e| e.from != 0x200));
+        let dot = cfg.to_dot();
+        assert!(dot.contains(
+            "    \"L200\" [label=\"200: JP V0, 204\\lunresolved computed jump\\l\", color=red];"
+        ));
+    }
+    #[test]
+    fn test_dot_edge_styles() {
+        let bytes = [0x22, 0x06, 0x30, 0x01, 0x00, 0xFD, 0x00, 0xEE];
+        let dot = ControlFlowGraph::from_rom(&bytes).to_dot();
+        assert!(dot.contains("\"L200\" -> \"L206\" [color=blue, label=\"call\"];"));
+        assert!(dot.contains("\"L200\" -> \"L202\" [style=solid];"));
+        assert!(dot.contains("\"L202\" -> \"L206\" [style=dashed, label=\"skip\"];"));
+        assert!(dot.contains("\"L206\" -> \"L202\" [style=dotted, label=\"ret\"];"));
+    }
+    #[test]
+    fn test_test_rom_graph() {
+        let bytes = std::fs::read("c8_test.c8").expect("Failed to load game");
+        let cfg = ControlFlowGraph::from_rom(&bytes);
+        let starts: BTreeSet<u16> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert!(cfg
+            .edges
+            .iter()
+            .all(|e| starts.contains(&e.from) && starts.contains(&e.to)));
+        assert_eq!(cfg.blocks[0].start, 0x200);
+    }
+}
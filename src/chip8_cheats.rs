@@ -0,0 +1,364 @@
+use crate::Chip8;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// How a byte must compare with the last snapshot to stay a candidate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Search {
+    /// The byte now holds this value
+    Equal(u8),
+    /// The byte differs from the last snapshot
+    Changed,
+    /// The byte is the same as in the last snapshot
+    Unchanged,
+    /// The byte is larger than in the last snapshot
+    Increased,
+    /// The byte is smaller than in the last snapshot
+    Decreased,
+    /// The byte grew by exactly this much, wrapping around at 256
+    IncreasedBy(u8),
+    /// The byte shrank by exactly this much, wrapping around at 0
+    DecreasedBy(u8),
+}
+
+impl Search {
+    fn matches(&self, before: u8, now: u8) -> bool {
+        return match *self {
+            Search::Equal(value) => now == value,
+            Search::Changed => now != before,
+            Search::Unchanged => now == before,
+            Search::Increased => now > before,
+            Search::Decreased => now < before,
+            Search::IncreasedBy(n) => now == before.wrapping_add(n),
+            Search::DecreasedBy(n) => now == before.wrapping_sub(n),
+        };
+    }
+}
+
+/// Narrows down where a game keeps a value, such as the number of lives
+///
+/// Start a search, play until the value changes, narrow the candidates, and repeat
+/// until only a few addresses are left.
+/// # Example
+/// ```
+/// use chip8::chip8_cheats::{MemorySearch, Search};
+/// use chip8::Chip8;
+/// let mut c = Chip8::new();
+/// c.set_memory(0x300, 3);
+/// let mut search = MemorySearch::new(&c);
+/// search.narrow(&c, Search::Equal(3));
+/// c.set_memory(0x300, 2);
+/// search.narrow(&c, Search::DecreasedBy(1));
+/// assert_eq!(search.candidates(), [0x300]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemorySearch {
+    candidates: Vec<u16>,
+    snapshot: Vec<u8>,
+}
+
+impl MemorySearch {
+    /// Start a search with every address as a candidate and a snapshot of memory
+    pub fn new(c: &Chip8) -> Self {
+        return Self {
+            candidates: (0..c.get_memory().len() as u16).collect(),
+            snapshot: c.get_memory().to_vec(),
+        };
+    }
+
+    /// Keep the candidates that match, then take a new snapshot to compare the next search with
+    /// # Returns
+    /// * The number of candidates left
+    pub fn narrow(&mut self, c: &Chip8, search: Search) -> usize {
+        let memory = c.get_memory();
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|address| {
+            let address = *address as usize;
+            return search.matches(snapshot[address], memory[address]);
+        });
+        self.snapshot = memory.to_vec();
+        return self.candidates.len();
+    }
+
+    /// The addresses that matched every search so far, in order
+    pub fn candidates(&self) -> &[u16] {
+        return &self.candidates;
+    }
+}
+
+/// A byte of memory held at a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+    pub description: String,
+}
+
+impl Cheat {
+    /// Write the value, call this every frame to freeze the byte
+    pub fn apply(&self, c: &mut Chip8) {
+        c.set_memory(self.address, self.value);
+    }
+}
+
+/// Freeze every cheat in a list, call this before each frame
+///
+/// Cheats only hold at the start of the frame, a byte the game writes during the frame keeps the
+/// game's value until the next call.
+pub fn apply_cheats(c: &mut Chip8, cheats: &[Cheat]) {
+    for cheat in cheats {
+        cheat.apply(c);
+    }
+}
+
+/// A line of a cheat file that could not be read, the line is 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}: {}", self.line, self.message);
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+/// Cheats for any number of roms, keyed by the SHA-1 hash of the rom
+///
+/// As text, each rom's cheats follow its hash in square brackets, one cheat per line as a
+/// hexadecimal address, a hexadecimal value and a description. Lines starting with `#` are
+/// comments.
+/// ```text
+/// # c8_test.c8
+/// [8e592d3620481e00ea36d29765b95287c7349a70]
+/// 3F0 09 Infinite lives
+/// 3F2 05 Start on level 5
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheatFile {
+    roms: BTreeMap<String, Vec<Cheat>>,
+}
+
+impl CheatFile {
+    /// Read cheats from text
+    /// # Example
+    /// ```
+    /// use chip8::chip8_cheats::CheatFile;
+    /// let cheats = CheatFile::parse("[abc]\n3F0 09 Infinite lives\n").expect("fail");
+    /// assert_eq!(cheats.get("abc")[0].address, 0x3F0);
+    /// assert!(cheats.get("def").is_empty());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, CheatError> {
+        let mut ret = Self::default();
+        let mut rom: Option<String> = None;
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| CheatError {
+                line: index + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(hash) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let hash = hash.trim().to_ascii_lowercase();
+                if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(error("expected a rom hash in hexadecimal"));
+                }
+                ret.roms.entry(hash.clone()).or_default();
+                rom = Some(hash);
+                continue;
+            }
+            let Some(hash) = rom.as_ref() else {
+                return Err(error("a cheat must follow a [rom hash] line"));
+            };
+            // Any run of whitespace separates the fields, the description keeps its own spacing
+            let mut fields = line.split_whitespace();
+            let address = fields.next().unwrap_or("");
+            let value = fields.next().unwrap_or("");
+            let description = line[address.len()..].trim_start()[value.len()..]
+                .trim()
+                .to_string();
+            // from_str_radix takes a leading sign, which the format does not
+            let hex = |field: &str| field.chars().all(|c| c.is_ascii_hexdigit());
+            let address = u16::from_str_radix(address, 16)
+                .ok()
+                .filter(|a| *a < 0x1000 && hex(address))
+                .ok_or_else(|| error("expected an address from 000 to FFF"))?;
+            let value = u8::from_str_radix(value, 16)
+                .ok()
+                .filter(|_| hex(value))
+                .ok_or_else(|| error("expected a value from 00 to FF"))?;
+            ret.add(
+                hash,
+                Cheat {
+                    address,
+                    value,
+                    description,
+                },
+            );
+        }
+        return Ok(ret);
+    }
+
+    /// Read a cheat file
+    /// # Returns
+    /// * An `InvalidData` error wrapping a `CheatError` if the file cannot be parsed
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        return Self::parse(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+    }
+
+    /// Write the cheats to a file in the text format
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        return std::fs::write(path, self.to_string());
+    }
+
+    /// The cheats for a rom, empty if there are none
+    pub fn get(&self, rom_hash: &str) -> &[Cheat] {
+        return self
+            .roms
+            .get(&rom_hash.to_ascii_lowercase())
+            .map_or(&[], |cheats| cheats.as_slice());
+    }
+
+    /// The cheats for the rom an interpreter has loaded
+    pub fn for_chip8(&self, c: &Chip8) -> &[Cheat] {
+        return c.get_rom_hash().map_or(&[], |hash| self.get(hash));
+    }
+
+    /// Add a cheat for a rom
+    pub fn add(&mut self, rom_hash: &str, cheat: Cheat) {
+        self.roms
+            .entry(rom_hash.to_ascii_lowercase())
+            .or_default()
+            .push(cheat);
+    }
+
+    /// Remove every cheat for a rom
+    pub fn clear(&mut self, rom_hash: &str) {
+        self.roms.remove(&rom_hash.to_ascii_lowercase());
+    }
+}
+
+impl fmt::Display for CheatFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (hash, cheats)) in self.roms.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", hash)?;
+            for cheat in cheats {
+                write!(f, "{:03X} {:02X}", cheat.address, cheat.value)?;
+                if !cheat.description.is_empty() {
+                    write!(f, " {}", cheat.description)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mut c = Chip8::new();
+        c.set_memory(0x300, 5);
+        c.set_memory(0x301, 5);
+        c.set_memory(0x302, 5);
+        let mut search = MemorySearch::new(&c);
+        assert_eq!(search.narrow(&c, Search::Equal(5)), 3);
+        c.set_memory(0x300, 6);
+        c.set_memory(0x301, 8);
+        assert_eq!(search.narrow(&c, Search::Changed), 2);
+        c.set_memory(0x301, 7);
+        assert_eq!(search.narrow(&c, Search::Unchanged), 1);
+        assert_eq!(search.candidates(), [0x300]);
+    }
+    #[test]
+    fn test_search_comparisons() {
+        assert!(Search::Increased.matches(1, 2));
+        assert!(!Search::Increased.matches(2, 2));
+        assert!(Search::Decreased.matches(2, 1));
+        assert!(Search::IncreasedBy(2).matches(0xFF, 1));
+        assert!(Search::DecreasedBy(1).matches(0, 0xFF));
+        assert!(!Search::DecreasedBy(1).matches(3, 1));
+    }
+    #[test]
+    fn test_freeze() {
+        // LD I, 300 ; LD V0, [I] ; LD V1, V0 ; LD V0, 0 ; LD [I], V0 ; JP 200
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[
+            0xA3, 0x00, 0xF0, 0x65, 0x81, 0x00, 0x60, 0x00, 0xF0, 0x55, 0x12, 0x00,
+        ])
+        .expect("fail");
+        // One pass of the loop per frame, so V1 holds the value the game read this frame
+        c.set_instructions_per_frame(6);
+        let mut cheats = CheatFile::default();
+        let hash = c.get_rom_hash().expect("fail").to_string();
+        cheats.add(
+            &hash,
+            Cheat {
+                address: 0x300,
+                value: 9,
+                description: "Infinite lives".to_string(),
+            },
+        );
+        let frozen = cheats.for_chip8(&c).to_vec();
+        for _ in 0..3 {
+            apply_cheats(&mut c, &frozen);
+            c.run_frame();
+            assert_eq!(c.get_registers()[1], 9);
+            // The game's own write lasts until the cheats are applied again
+            assert_eq!(c.get_memory()[0x300], 0);
+        }
+    }
+    #[test]
+    fn test_text_format() {
+        let text = "# comment\n[ABC]\n3F0 09 Infinite lives\n\n[def]\n200 00\n";
+        let cheats = CheatFile::parse(text).expect("fail");
+        assert_eq!(
+            cheats.get("abc"),
+            [Cheat {
+                address: 0x3F0,
+                value: 9,
+                description: "Infinite lives".to_string(),
+            }]
+        );
+        assert_eq!(cheats.get("DEF")[0].description, "");
+        let spaced = CheatFile::parse("[abc]\n3F0  3F\t Infinite  lives\n").expect("fail");
+        assert_eq!(spaced.get("abc")[0].value, 0x3F);
+        assert_eq!(spaced.get("abc")[0].description, "Infinite  lives");
+        assert_eq!(
+            cheats.to_string(),
+            "[abc]\n3F0 09 Infinite lives\n\n[def]\n200 00\n"
+        );
+        assert_eq!(CheatFile::parse(&cheats.to_string()), Ok(cheats.clone()));
+
+        let path = std::env::temp_dir().join(format!("chip8_cheats_{}.txt", std::process::id()));
+        cheats.save(&path).expect("fail");
+        assert_eq!(CheatFile::load(&path).expect("fail"), cheats);
+        let _ = std::fs::remove_file(&path);
+    }
+    #[test]
+    fn test_parse_errors() {
+        let line = |text: &str| CheatFile::parse(text).expect_err("fail").line;
+        assert_eq!(line("3F0 09\n"), 1);
+        assert_eq!(line("[xyz]\n"), 1);
+        assert_eq!(line("[abc]\n1000 09\n"), 2);
+        assert_eq!(line("[abc]\n3F0 100\n"), 2);
+        assert_eq!(line("[abc]\n3F0\n"), 2);
+        assert_eq!(line("[abc]\n+3F0 09\n"), 2);
+        assert_eq!(line("[abc]\n3F0 +9\n"), 2);
+        assert_eq!(line("[abc]\n3F0 -1\n"), 2);
+    }
+}
@@ -1,291 +1,499 @@
 pub mod chip8_disassembler {
+    #[cfg(feature = "std")]
+    use serde_json::json;
+    #[cfg(feature = "std")]
+    use std::collections::BTreeSet;
+    #[cfg(feature = "std")]
+    use std::fmt;
+    #[cfg(feature = "std")]
     use std::str;
 
-    struct OpCode {
-        code: u16,
-        code_string: &'static str,
-        bit_mask: u16,
-        mnemonic: &'static str,
+    /// Which instruction an opcode is, named after its Cowgod mnemonic and operands
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum OpCodeKind {
+        Cls,
+        Ret,
+        Sys,
+        Jp,
+        Call,
+        SeVxByte,
+        SneVxByte,
+        SeVxVy,
+        LdVxByte,
+        AddVxByte,
+        LdVxVy,
+        OrVxVy,
+        AndVxVy,
+        XorVxVy,
+        AddVxVy,
+        SubVxVy,
+        ShrVx,
+        SubnVxVy,
+        ShlVx,
+        SneVxVy,
+        LdI,
+        JpV0,
+        Rnd,
+        Drw,
+        Skp,
+        Sknp,
+        LdVxDt,
+        LdVxK,
+        LdDtVx,
+        LdStVx,
+        AddIVx,
+        LdFVx,
+        LdBVx,
+        LdIndirectIVx,
+        LdVxIndirectI,
+        Scd,
+        Scr,
+        Scl,
+        Exit,
+        Low,
+        High,
+        LdHfVx,
+        LdRVx,
+        LdVxR,
+    }
+
+    /// A typed instruction operand
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Operand {
+        /// A V register, 0x0 to 0xF
+        Register(u8),
+        /// An 8 bit immediate value
+        Byte(u8),
+        /// A 4 bit immediate value
+        Nibble(u8),
+        /// A 12 bit address
+        Address(u16),
+        /// The I register
+        I,
+        /// The memory pointed to by I
+        IndirectI,
+        /// The delay timer
+        DelayTimer,
+        /// The sound timer
+        SoundTimer,
+        /// A key press
+        Key,
+        /// The small font sprite location
+        Font,
+        /// The big font sprite location
+        BigFont,
+        /// The BCD representation
+        Bcd,
+        /// The RPL user flags
+        Flags,
+    }
+
+    /// A decoded instruction
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg(feature = "std")]
+    pub struct DisassembledInstruction {
+        pub address: u16,
+        pub bytes: [u8; 2],
+        pub kind: OpCodeKind,
+        pub operands: Vec<Operand>,
+    }
+
+    #[cfg(feature = "std")]
+    impl DisassembledInstruction {
+        /// The instruction as a big-endian word
+        pub fn code(&self) -> u16 {
+            return u16::from_be_bytes(self.bytes);
+        }
+
+        /// The Cowgod mnemonic, such as `LD`
+        pub fn mnemonic(&self) -> &'static str {
+            let op_code = get_op_code_from_hex(self.code()).expect("decoded opcodes are valid");
+            return op_code
+                .mnemonic
+                .split_once(' ')
+                .map_or(op_code.mnemonic, |(name, _)| name);
+        }
+    }
+
+    pub(crate) struct OpCode {
+        pub(crate) code: u16,
+        pub(crate) code_string: &'static str,
+        pub(crate) bit_mask: u16,
+        pub(crate) mnemonic: &'static str,
+        pub(crate) kind: OpCodeKind,
     }
 
-    const OP_CODES: [OpCode; 44] = [
+    pub(crate) const OP_CODES: [OpCode; 44] = [
         OpCode {
             code: 0x00E0,
             code_string: "00E0",
             bit_mask: 0xFFFF,
             mnemonic: "CLS",
+            kind: OpCodeKind::Cls,
         },
         OpCode {
             code: 0x00EE,
             code_string: "00EE",
-            bit_mask: 0x00FF,
+            bit_mask: 0xFFFF,
             mnemonic: "RET",
+            kind: OpCodeKind::Ret,
         },
         OpCode {
             code: 0x1000,
             code_string: "1NNN",
             bit_mask: 0xF000,
             mnemonic: "JP NNN",
+            kind: OpCodeKind::Jp,
         },
         OpCode {
             code: 0x2000,
             code_string: "2NNN",
             bit_mask: 0xF000,
             mnemonic: "CALL NNN",
+            kind: OpCodeKind::Call,
         },
         OpCode {
             code: 0x3000,
             code_string: "3XKK",
             bit_mask: 0xF000,
             mnemonic: "SE VX, KK",
+            kind: OpCodeKind::SeVxByte,
         },
         OpCode {
             code: 0x4000,
             code_string: "4XKK",
             bit_mask: 0xF000,
             mnemonic: "SNE VX, KK",
+            kind: OpCodeKind::SneVxByte,
         },
         OpCode {
             code: 0x5000,
             code_string: "5XY0",
             bit_mask: 0xF00F,
             mnemonic: "SE VX, VY",
+            kind: OpCodeKind::SeVxVy,
         },
         OpCode {
             code: 0x6000,
             code_string: "6XKK",
             bit_mask: 0xF000,
             mnemonic: "LD VX, KK",
+            kind: OpCodeKind::LdVxByte,
         },
         OpCode {
             code: 0x7000,
             code_string: "7XKK",
             bit_mask: 0xF000,
             mnemonic: "ADD VX, KK",
+            kind: OpCodeKind::AddVxByte,
         },
         OpCode {
             code: 0x8000,
             code_string: "8XY0",
             bit_mask: 0xF00F,
             mnemonic: "LD VX, VY",
+            kind: OpCodeKind::LdVxVy,
         },
         OpCode {
             code: 0x8001,
             code_string: "8XY1",
             bit_mask: 0xF00F,
             mnemonic: "OR VX, VY",
+            kind: OpCodeKind::OrVxVy,
         },
         OpCode {
             code: 0x8002,
             code_string: "8XY2",
             bit_mask: 0xF00F,
             mnemonic: "AND VX, VY",
+            kind: OpCodeKind::AndVxVy,
         },
         OpCode {
             code: 0x8003,
             code_string: "8XY3",
             bit_mask: 0xF00F,
             mnemonic: "XOR VX, VY",
+            kind: OpCodeKind::XorVxVy,
         },
         OpCode {
             code: 0x8004,
             code_string: "8XY4",
             bit_mask: 0xF00F,
             mnemonic: "ADD VX, VY",
+            kind: OpCodeKind::AddVxVy,
         },
         OpCode {
             code: 0x8005,
             code_string: "8XY5",
             bit_mask: 0xF00F,
             mnemonic: "SUB VX, VY",
+            kind: OpCodeKind::SubVxVy,
         },
         OpCode {
             code: 0x8006,
             code_string: "8XY6",
             bit_mask: 0xF00F,
             mnemonic: "SHR VX",
+            kind: OpCodeKind::ShrVx,
         },
         OpCode {
             code: 0x8007,
             code_string: "8XY7",
             bit_mask: 0xF00F,
             mnemonic: "SUBN VX, VY",
+            kind: OpCodeKind::SubnVxVy,
         },
         OpCode {
             code: 0x800E,
             code_string: "8XYE",
             bit_mask: 0xF00F,
             mnemonic: "SHL VX",
+            kind: OpCodeKind::ShlVx,
         },
         OpCode {
             code: 0x9000,
             code_string: "9XY0",
             bit_mask: 0xF00F,
             mnemonic: "SNE VX, VY",
+            kind: OpCodeKind::SneVxVy,
         },
         OpCode {
             code: 0xA000,
             code_string: "ANNN",
             bit_mask: 0xF000,
             mnemonic: "LD I, NNN",
+            kind: OpCodeKind::LdI,
         },
         OpCode {
             code: 0xB000,
             code_string: "BNNN",
             bit_mask: 0xF000,
             mnemonic: "JP V0, NNN",
+            kind: OpCodeKind::JpV0,
         },
         OpCode {
             code: 0xC000,
             code_string: "CXKK",
             bit_mask: 0xF000,
             mnemonic: "RND VX, KK",
+            kind: OpCodeKind::Rnd,
         },
         OpCode {
             code: 0xD000,
             code_string: "DXYN",
             bit_mask: 0xF000,
             mnemonic: "DRW VX, VY, N",
+            kind: OpCodeKind::Drw,
         },
         OpCode {
             code: 0xE09E,
             code_string: "EX9E",
             bit_mask: 0xF0FF,
             mnemonic: "SKP VX",
+            kind: OpCodeKind::Skp,
         },
         OpCode {
             code: 0xE0A1,
             code_string: "EXA1",
             bit_mask: 0xF0FF,
             mnemonic: "SKNP VX",
+            kind: OpCodeKind::Sknp,
         },
         OpCode {
             code: 0xF007,
             code_string: "FX07",
             bit_mask: 0xF0FF,
             mnemonic: "LD VX, DT",
+            kind: OpCodeKind::LdVxDt,
         },
         OpCode {
             code: 0xF00A,
             code_string: "FX0A",
             bit_mask: 0xF0FF,
             mnemonic: "LD VX, K",
+            kind: OpCodeKind::LdVxK,
         },
         OpCode {
             code: 0xF015,
             code_string: "FX15",
             bit_mask: 0xF0FF,
             mnemonic: "LD DT, VX",
+            kind: OpCodeKind::LdDtVx,
         },
         OpCode {
             code: 0xF018,
             code_string: "FX18",
             bit_mask: 0xF0FF,
             mnemonic: "LD ST, VX",
+            kind: OpCodeKind::LdStVx,
         },
         OpCode {
             code: 0xF01E,
             code_string: "FX1E",
             bit_mask: 0xF0FF,
             mnemonic: "ADD I, VX",
+            kind: OpCodeKind::AddIVx,
         },
         OpCode {
             code: 0xF029,
             code_string: "FX29",
             bit_mask: 0xF0FF,
             mnemonic: "LD F, VX",
+            kind: OpCodeKind::LdFVx,
         },
         OpCode {
             code: 0xF033,
             code_string: "FX33",
             bit_mask: 0xF0FF,
             mnemonic: "LD B, VX",
+            kind: OpCodeKind::LdBVx,
         },
         OpCode {
             code: 0xF055,
             code_string: "FX55",
             bit_mask: 0xF0FF,
             mnemonic: "LD [I], VX",
+            kind: OpCodeKind::LdIndirectIVx,
         },
         OpCode {
             code: 0xF065,
             code_string: "FX65",
             bit_mask: 0xF0FF,
             mnemonic: "LD VX, [I]",
+            kind: OpCodeKind::LdVxIndirectI,
         },
         OpCode {
             code: 0x00C0,
             code_string: "00CN",
             bit_mask: 0xFFF0,
             mnemonic: "SCD N",
+            kind: OpCodeKind::Scd,
         },
         OpCode {
             code: 0x00FB,
             code_string: "00FB",
             bit_mask: 0xFFFF,
             mnemonic: "SCR",
+            kind: OpCodeKind::Scr,
         },
         OpCode {
             code: 0x00FC,
             code_string: "00FC",
             bit_mask: 0xFFFF,
             mnemonic: "SCL",
+            kind: OpCodeKind::Scl,
         },
         OpCode {
             code: 0x00FD,
             code_string: "00FD",
             bit_mask: 0xFFFF,
             mnemonic: "EXIT",
+            kind: OpCodeKind::Exit,
         },
         OpCode {
             code: 0x00FE,
             code_string: "00FE",
             bit_mask: 0xFFFF,
             mnemonic: "LOW",
+            kind: OpCodeKind::Low,
         },
         OpCode {
             code: 0x00FF,
             code_string: "00FF",
             bit_mask: 0xFFFF,
             mnemonic: "HIGH",
+            kind: OpCodeKind::High,
         },
         OpCode {
             code: 0xF030,
             code_string: "FX30",
             bit_mask: 0xF0FF,
             mnemonic: "LD HF, VX",
+            kind: OpCodeKind::LdHfVx,
         },
         OpCode {
             code: 0xF075,
             code_string: "FX75",
             bit_mask: 0xF0FF,
             mnemonic: "LD R, VX",
+            kind: OpCodeKind::LdRVx,
         },
         OpCode {
             code: 0xF085,
             code_string: "FX85",
             bit_mask: 0xF0FF,
             mnemonic: "LD VX, R",
+            kind: OpCodeKind::LdVxR,
         },
         OpCode {
             code: 0x0000,
             code_string: "0NNN",
             bit_mask: 0xF000,
             mnemonic: "SYS NNN",
+            kind: OpCodeKind::Sys,
         },
     ];
 
-    fn hex_to_u16(hex: &str) -> u16 {
-        u16::from_str_radix(hex, 16).unwrap()
+    /// An error found while disassembling
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg(feature = "std")]
+    pub enum DisassemblyError {
+        /// The word at `offset` is not a Chip-8 instruction
+        InvalidOpCode { offset: usize, word: u16 },
+        /// The input ends with half an instruction at `offset`
+        TruncatedOpCode { offset: usize, byte: u8 },
+        /// The text is not a hex word of at most four digits
+        InvalidHex { text: String },
     }
 
-    fn get_op_code_from_hex(code: u16) -> Result<&'static OpCode, &'static str> {
-        for op_code in OP_CODES.iter() {
-            if op_code.code == code & op_code.bit_mask {
-                return Ok(op_code);
-            }
+    #[cfg(feature = "std")]
+    impl fmt::Display for DisassemblyError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            return match self {
+                DisassemblyError::InvalidOpCode { offset, word } => {
+                    write!(f, "Invalid OpCode {:04X} at offset {:#X}", word, offset)
+                }
+                DisassemblyError::TruncatedOpCode { offset, byte } => {
+                    write!(f, "Truncated OpCode {:02X} at offset {:#X}", byte, offset)
+                }
+                DisassemblyError::InvalidHex { text } => write!(f, "Invalid hex word {:?}", text),
+            };
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for DisassemblyError {}
+
+    #[cfg(feature = "std")]
+    fn hex_to_u16(hex: &str) -> Result<u16, DisassemblyError> {
+        let digits = hex.strip_prefix("0x").unwrap_or(hex);
+        if digits.is_empty() || digits.len() > 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(DisassemblyError::InvalidHex {
+                text: hex.to_string(),
+            });
         }
-        return Err("Invalid OpCode");
+        return Ok(u16::from_str_radix(digits, 16).expect("checked hex digits"));
+    }
+
+    fn get_op_code_from_hex(code: u16) -> Option<&'static OpCode> {
+        return OP_CODES
+            .iter()
+            .find(|op_code| op_code.code == code & op_code.bit_mask);
+    }
+
+    /// Find out which instruction an opcode is, without building any text
+    /// # Arguments
+    /// * `code` - A u16 that holds the OpCode
+    /// # Returns
+    /// * The kind of instruction, or `None` if the word is not an instruction
+    /// # Example
+    /// ```
+    /// use chip8::chip8_disassembler::chip8_disassembler::{decode_op_code, OpCodeKind};
+    /// assert_eq!(decode_op_code(0xD125), Some(OpCodeKind::Drw));
+    /// assert_eq!(decode_op_code(0x5121), None);
+    /// ```
+    pub fn decode_op_code(code: u16) -> Option<OpCodeKind> {
+        return get_op_code_from_hex(code).map(|op_code| op_code.kind);
     }
 
     /// Dissasemble a OpCode from a string
@@ -300,8 +508,9 @@ pub mod chip8_disassembler {
     /// let result = dissasemble_op_code_from_str(code).expect("fail");
     /// assert_eq!(result, "CLS");
     /// ```
-    pub fn dissasemble_op_code_from_str(code: &str) -> Result<String, &str> {
-        let code = hex_to_u16(code);
+    #[cfg(feature = "std")]
+    pub fn dissasemble_op_code_from_str(code: &str) -> Result<String, DisassemblyError> {
+        let code = hex_to_u16(code)?;
         return dissasemble_op_code_from_u16(code);
     }
 
@@ -317,23 +526,254 @@ pub mod chip8_disassembler {
     /// let result = dissasemble_op_code_from_u16(code).expect("fail");
     /// assert_eq!(result, "CLS");
     /// ```
-    pub fn dissasemble_op_code_from_u16(code: u16) -> Result<String, &'static str> {
-        let op_code = get_op_code_from_hex(code)?;
-        let mut ret = String::from(op_code.mnemonic);
-        for i in 0..4 {
-            let c = op_code.code_string.chars().nth(i).unwrap();
-            if c == 'X' || c == 'Y' || c == 'N' || c == 'K' {
-                let value = match i {
-                    0 => (code & 0xF000) >> 12,
-                    1 => (code & 0x0F00) >> 8,
-                    2 => (code & 0x00F0) >> 4,
-                    3 => code & 0x000F,
-                    _ => 0,
-                };
-                ret = ret.replacen(&c.to_string(), &value.to_string(), 1);
+    #[cfg(feature = "std")]
+    pub fn dissasemble_op_code_from_u16(code: u16) -> Result<String, DisassemblyError> {
+        let instruction = decode_instruction(0, code)?;
+        return Ok(CowgodSyntax::default().format(&instruction));
+    }
+
+    /// Decode an instruction into its kind and typed operands
+    /// # Arguments
+    /// * `address` - The address the instruction was read from
+    /// * `code` - A u16 that holds the OpCode
+    /// # Returns
+    /// * The decoded instruction, or an `InvalidOpCode` error whose offset is `address`
+    /// # Example
+    /// ```
+    /// use chip8::chip8_disassembler::chip8_disassembler::{decode_instruction, OpCodeKind, Operand};
+    /// let instruction = decode_instruction(0x200, 0xD125).expect("fail");
+    /// assert_eq!(instruction.kind, OpCodeKind::Drw);
+    /// assert_eq!(
+    ///     instruction.operands,
+    ///     [Operand::Register(1), Operand::Register(2), Operand::Nibble(5)]
+    /// );
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn decode_instruction(
+        address: u16,
+        code: u16,
+    ) -> Result<DisassembledInstruction, DisassemblyError> {
+        let op_code = get_op_code_from_hex(code).ok_or(DisassemblyError::InvalidOpCode {
+            offset: address as usize,
+            word: code,
+        })?;
+        let x = ((code & 0x0F00) >> 8) as u8;
+        let y = ((code & 0x00F0) >> 4) as u8;
+        let mut operands: Vec<Operand> = match op_code.mnemonic.split_once(' ') {
+            Some((_, template)) => template
+                .split(", ")
+                .map(|operand| match operand {
+                    "VX" => Operand::Register(x),
+                    "VY" => Operand::Register(y),
+                    "V0" => Operand::Register(0),
+                    "KK" => Operand::Byte(code as u8),
+                    "N" => Operand::Nibble((code & 0x000F) as u8),
+                    "NNN" => Operand::Address(code & 0x0FFF),
+                    "I" => Operand::I,
+                    "[I]" => Operand::IndirectI,
+                    "DT" => Operand::DelayTimer,
+                    "ST" => Operand::SoundTimer,
+                    "K" => Operand::Key,
+                    "F" => Operand::Font,
+                    "HF" => Operand::BigFont,
+                    "B" => Operand::Bcd,
+                    "R" => Operand::Flags,
+                    _ => unreachable!("unknown operand {} in the opcode table", operand),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        // SHR and SHL ignore VY, but it is part of the encoding
+        if op_code.kind == OpCodeKind::ShrVx || op_code.kind == OpCodeKind::ShlVx {
+            operands.push(Operand::Register(y));
+        }
+        return Ok(DisassembledInstruction {
+            address,
+            bytes: code.to_be_bytes(),
+            kind: op_code.kind,
+            operands,
+        });
+    }
+
+    /// A way of writing instructions as text
+    #[cfg(feature = "std")]
+    pub trait Syntax {
+        /// Format an instruction, naming addresses with `label` where it returns a name
+        fn format_with_labels(
+            &self,
+            instruction: &DisassembledInstruction,
+            label: &dyn Fn(u16) -> Option<String>,
+        ) -> String;
+
+        /// Format an instruction with numeric addresses
+        fn format(&self, instruction: &DisassembledInstruction) -> String {
+            return self.format_with_labels(instruction, &|_| None);
+        }
+    }
+
+    /// The syntax from Cowgod's Chip-8 technical reference, such as `LD V1, 12`
+    #[derive(Debug, Clone, Copy, Default)]
+    #[cfg(feature = "std")]
+    pub struct CowgodSyntax {
+        /// Write numbers with a `0x` prefix, which the assembler needs
+        pub hex_prefix: bool,
+    }
+
+    #[cfg(feature = "std")]
+    impl Syntax for CowgodSyntax {
+        fn format_with_labels(
+            &self,
+            instruction: &DisassembledInstruction,
+            label: &dyn Fn(u16) -> Option<String>,
+        ) -> String {
+            let prefix = if self.hex_prefix { "0x" } else { "" };
+            let mut operands: Vec<String> = instruction
+                .operands
+                .iter()
+                .map(|operand| match operand {
+                    Operand::Register(r) => format!("V{:X}", r),
+                    Operand::Byte(b) => format!("{}{:02X}", prefix, b),
+                    Operand::Nibble(n) => format!("{}{:X}", prefix, n),
+                    Operand::Address(a) if instruction.kind == OpCodeKind::Sys => {
+                        format!("{}{:03X}", prefix, a)
+                    }
+                    Operand::Address(a) => label(*a).unwrap_or(format!("{}{:03X}", prefix, a)),
+                    Operand::I => String::from("I"),
+                    Operand::IndirectI => String::from("[I]"),
+                    Operand::DelayTimer => String::from("DT"),
+                    Operand::SoundTimer => String::from("ST"),
+                    Operand::Key => String::from("K"),
+                    Operand::Font => String::from("F"),
+                    Operand::BigFont => String::from("HF"),
+                    Operand::Bcd => String::from("B"),
+                    Operand::Flags => String::from("R"),
+                })
+                .collect();
+            if (instruction.kind == OpCodeKind::ShrVx || instruction.kind == OpCodeKind::ShlVx)
+                && instruction.operands[1] == Operand::Register(0)
+            {
+                operands.pop();
+            }
+            if operands.is_empty() {
+                return instruction.mnemonic().to_string();
             }
+            return format!("{} {}", instruction.mnemonic(), operands.join(", "));
+        }
+    }
+
+    /// The syntax of the Octo assembler, such as `v1 := 0x12`
+    #[derive(Debug, Clone, Copy, Default)]
+    #[cfg(feature = "std")]
+    pub struct OctoSyntax;
+
+    #[cfg(feature = "std")]
+    impl Syntax for OctoSyntax {
+        fn format_with_labels(
+            &self,
+            instruction: &DisassembledInstruction,
+            label: &dyn Fn(u16) -> Option<String>,
+        ) -> String {
+            let operand = |i: usize| -> String {
+                return match instruction.operands[i] {
+                    Operand::Register(r) => format!("v{:x}", r),
+                    Operand::Byte(b) => format!("0x{:02x}", b),
+                    Operand::Nibble(n) => format!("{}", n),
+                    Operand::Address(a) => label(a).unwrap_or(format!("0x{:03x}", a)),
+                    _ => String::new(),
+                };
+            };
+            let (a, b) = (|| operand(0), || operand(1));
+            return match instruction.kind {
+                OpCodeKind::Cls => String::from("clear"),
+                OpCodeKind::Ret => String::from("return"),
+                OpCodeKind::Sys => format!("native {}", a()),
+                OpCodeKind::Jp => format!("jump {}", a()),
+                OpCodeKind::Call => format!(":call {}", a()),
+                OpCodeKind::SeVxByte | OpCodeKind::SeVxVy => format!("if {} != {} then", a(), b()),
+                OpCodeKind::SneVxByte | OpCodeKind::SneVxVy => {
+                    format!("if {} == {} then", a(), b())
+                }
+                OpCodeKind::LdVxByte | OpCodeKind::LdVxVy => format!("{} := {}", a(), b()),
+                OpCodeKind::AddVxByte | OpCodeKind::AddVxVy => format!("{} += {}", a(), b()),
+                OpCodeKind::OrVxVy => format!("{} |= {}", a(), b()),
+                OpCodeKind::AndVxVy => format!("{} &= {}", a(), b()),
+                OpCodeKind::XorVxVy => format!("{} ^= {}", a(), b()),
+                OpCodeKind::SubVxVy => format!("{} -= {}", a(), b()),
+                OpCodeKind::ShrVx => format!("{} >>= {}", a(), b()),
+                OpCodeKind::SubnVxVy => format!("{} =- {}", a(), b()),
+                OpCodeKind::ShlVx => format!("{} <<= {}", a(), b()),
+                OpCodeKind::LdI => format!("i := {}", b()),
+                OpCodeKind::JpV0 => format!("jump0 {}", b()),
+                OpCodeKind::Rnd => format!("{} := random {}", a(), b()),
+                OpCodeKind::Drw => format!("sprite {} {} {}", a(), b(), operand(2)),
+                OpCodeKind::Skp => format!("if {} -key then", a()),
+                OpCodeKind::Sknp => format!("if {} key then", a()),
+                OpCodeKind::LdVxDt => format!("{} := delay", a()),
+                OpCodeKind::LdVxK => format!("{} := key", a()),
+                OpCodeKind::LdDtVx => format!("delay := {}", b()),
+                OpCodeKind::LdStVx => format!("buzzer := {}", b()),
+                OpCodeKind::AddIVx => format!("i += {}", b()),
+                OpCodeKind::LdFVx => format!("i := hex {}", b()),
+                OpCodeKind::LdBVx => format!("bcd {}", b()),
+                OpCodeKind::LdIndirectIVx => format!("save {}", b()),
+                OpCodeKind::LdVxIndirectI => format!("load {}", a()),
+                OpCodeKind::Scd => format!("scroll-down {}", a()),
+                OpCodeKind::Scr => String::from("scroll-right"),
+                OpCodeKind::Scl => String::from("scroll-left"),
+                OpCodeKind::Exit => String::from("exit"),
+                OpCodeKind::Low => String::from("lores"),
+                OpCodeKind::High => String::from("hires"),
+                OpCodeKind::LdHfVx => format!("i := bighex {}", b()),
+                OpCodeKind::LdRVx => format!("saveflags {}", b()),
+                OpCodeKind::LdVxR => format!("loadflags {}", a()),
+            };
+        }
+    }
+
+    /// Instructions as JSON objects, for tools that post-process listings
+    #[derive(Debug, Clone, Copy, Default)]
+    #[cfg(feature = "std")]
+    pub struct JsonSyntax;
+
+    #[cfg(feature = "std")]
+    impl Syntax for JsonSyntax {
+        fn format_with_labels(
+            &self,
+            instruction: &DisassembledInstruction,
+            label: &dyn Fn(u16) -> Option<String>,
+        ) -> String {
+            let operands: Vec<serde_json::Value> = instruction
+                .operands
+                .iter()
+                .map(|operand| match operand {
+                    Operand::Register(r) => json!({"type": "register", "value": r}),
+                    Operand::Byte(b) => json!({"type": "byte", "value": b}),
+                    Operand::Nibble(n) => json!({"type": "nibble", "value": n}),
+                    Operand::Address(a) => match label(*a) {
+                        Some(name) => json!({"type": "address", "value": a, "label": name}),
+                        None => json!({"type": "address", "value": a}),
+                    },
+                    Operand::I => json!({"type": "i"}),
+                    Operand::IndirectI => json!({"type": "indirect_i"}),
+                    Operand::DelayTimer => json!({"type": "delay_timer"}),
+                    Operand::SoundTimer => json!({"type": "sound_timer"}),
+                    Operand::Key => json!({"type": "key"}),
+                    Operand::Font => json!({"type": "font"}),
+                    Operand::BigFont => json!({"type": "big_font"}),
+                    Operand::Bcd => json!({"type": "bcd"}),
+                    Operand::Flags => json!({"type": "flags"}),
+                })
+                .collect();
+            return json!({
+                "address": instruction.address,
+                "bytes": instruction.bytes,
+                "kind": format!("{:?}", instruction.kind),
+                "mnemonic": instruction.mnemonic(),
+                "operands": operands,
+                "text": CowgodSyntax::default().format_with_labels(instruction, label),
+            })
+            .to_string();
         }
-        return Ok(ret);
     }
 
     /// Dissasemble a OpCode from a slice of bytes
@@ -350,19 +790,349 @@ pub mod chip8_disassembler {
     /// assert_eq!(result[1], "RET");
     /// assert_eq!(result[2], "AND V1, V2");
     /// ```
-    pub fn dissasemble_op_code_from_bytes(bytes: &[u8]) -> Result<Vec<String>, &str> {
-        if bytes.len() % 2 != 0 {
-            return Err("Invalid OpCode");
-        }
+    #[cfg(feature = "std")]
+    pub fn dissasemble_op_code_from_bytes(bytes: &[u8]) -> Result<Vec<String>, DisassemblyError> {
         let mut ret = Vec::new();
         for i in (0..bytes.len()).step_by(2) {
+            if i + 1 == bytes.len() {
+                return Err(DisassemblyError::TruncatedOpCode {
+                    offset: i,
+                    byte: bytes[i],
+                });
+            }
             let code = (bytes[i] as u16) << 8 | bytes[i + 1] as u16;
-            let str = dissasemble_op_code_from_u16(code)?;
+            let str = dissasemble_op_code_from_u16(code).map_err(|_| {
+                DisassemblyError::InvalidOpCode {
+                    offset: i,
+                    word: code,
+                }
+            })?;
             ret.push(str);
         }
         return Ok(ret);
     }
 
+    /// Dissasemble a slice of bytes without stopping at errors
+    ///
+    /// Words that are not instructions become `DW` directives and a trailing odd byte
+    /// becomes a `DB` directive, so any input produces a listing.
+    /// # Arguments
+    /// * `bytes` - A slice of bytes that holds the OpCodes
+    /// # Returns
+    /// * A vector of strings with the dissasembled OpCodes and data directives
+    /// # Example
+    /// ```
+    /// use chip8::chip8_disassembler::chip8_disassembler::dissasemble_op_code_from_bytes_tolerant;
+    /// let bytes = [0x00, 0xE0, 0x51, 0x21, 0x80];
+    /// let result = dissasemble_op_code_from_bytes_tolerant(&bytes);
+    /// assert_eq!(result, ["CLS", "DW 0x5121", "DB 0x80"]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn dissasemble_op_code_from_bytes_tolerant(bytes: &[u8]) -> Vec<String> {
+        let mut ret = Vec::new();
+        for i in (0..bytes.len()).step_by(2) {
+            if i + 1 == bytes.len() {
+                ret.push(format!("DB 0x{:02X}", bytes[i]));
+                break;
+            }
+            let code = (bytes[i] as u16) << 8 | bytes[i + 1] as u16;
+            match dissasemble_op_code_from_u16(code) {
+                Ok(str) => ret.push(str),
+                Err(_) => ret.push(format!("DW 0x{:04X}", code)),
+            }
+        }
+        return ret;
+    }
+
+    /// The address programs are loaded at by most Chip-8 interpreters
+    pub const PROGRAM_START: u16 = 0x200;
+
+    /// The largest number of `JP` entries followed for a `JP V0, NNN` jump table.
+    /// V0 is a byte, so a table can never be longer than 128 instructions.
+    #[cfg(feature = "std")]
+    const MAX_JUMP_TABLE_ENTRIES: usize = 128;
+
+    /// One line of a control-flow aware disassembly
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg(feature = "std")]
+    pub enum DisassemblyLine {
+        /// A reachable instruction
+        Instruction(DisassembledInstruction),
+        /// A run of bytes that is never executed
+        Data { address: u16, bytes: Vec<u8> },
+    }
+
+    #[cfg(feature = "std")]
+    impl DisassemblyLine {
+        /// The address of the first byte of the line
+        pub fn address(&self) -> u16 {
+            return match self {
+                DisassemblyLine::Instruction(instruction) => instruction.address,
+                DisassemblyLine::Data { address, .. } => *address,
+            };
+        }
+
+        /// The line as assembly text, data is emitted as a `DB` directive
+        /// # Example
+        /// ```
+        /// use chip8::chip8_disassembler::chip8_disassembler::DisassemblyLine;
+        /// let line = DisassemblyLine::Data { address: 0x202, bytes: vec![0xF0, 0x90] };
+        /// assert_eq!(line.text(), "DB 0xF0, 0x90");
+        /// ```
+        pub fn text(&self) -> String {
+            return match self {
+                DisassemblyLine::Instruction(instruction) => {
+                    CowgodSyntax::default().format(instruction)
+                }
+                DisassemblyLine::Data { bytes, .. } => {
+                    let bytes: Vec<String> = bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+                    format!("DB {}", bytes.join(", "))
+                }
+            };
+        }
+    }
+
+    /// Where execution can go after an instruction
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) enum Flow {
+        /// Execution continues with the next instruction
+        Next,
+        /// Execution may continue with the next instruction or skip over it
+        Skip,
+        /// Unconditional jump to an address
+        Jump(u16),
+        /// Subroutine call, execution resumes after the call on return
+        Call(u16),
+        /// Jump to an address plus V0
+        ComputedJump(u16),
+        /// Return from a subroutine
+        Return,
+        /// The interpreter stops
+        Exit,
+    }
+
+    /// Work out the control flow of an opcode
+    pub(crate) fn flow_of(code: u16) -> Flow {
+        return match code & 0xF000 {
+            0x0000 if code == 0x00EE => Flow::Return,
+            0x0000 if code == 0x00FD => Flow::Exit,
+            0x1000 => Flow::Jump(code & 0x0FFF),
+            0x2000 => Flow::Call(code & 0x0FFF),
+            0x3000 | 0x4000 | 0x5000 | 0x9000 => Flow::Skip,
+            0xB000 => Flow::ComputedJump(code & 0x0FFF),
+            0xE000 if code & 0x00FF == 0x9E || code & 0x00FF == 0xA1 => Flow::Skip,
+            _ => Flow::Next,
+        };
+    }
+
+    /// Read the big-endian word at an address of a rom loaded at `origin`
+    #[cfg(feature = "std")]
+    fn word_at(bytes: &[u8], origin: u16, address: u16) -> Option<u16> {
+        let offset = address.checked_sub(origin)? as usize;
+        if offset + 1 >= bytes.len() {
+            return None;
+        }
+        return Some((bytes[offset] as u16) << 8 | bytes[offset + 1] as u16);
+    }
+
+    /// Find the instructions reachable from the start of a rom
+    /// # Arguments
+    /// * `bytes` - The rom
+    /// * `origin` - The address the rom is loaded at, which is also the entry point
+    /// # Returns
+    /// * One flag per rom byte, set when the byte is part of a reachable instruction
+    #[cfg(feature = "std")]
+    pub(crate) fn find_code(bytes: &[u8], origin: u16) -> Vec<bool> {
+        let mut is_code = vec![false; bytes.len()];
+        let mut pending = vec![origin];
+        while let Some(mut address) = pending.pop() {
+            while let Some(code) = word_at(bytes, origin, address) {
+                let offset = (address - origin) as usize;
+                // Jumping into the middle of an instruction found earlier keeps the earlier one
+                if is_code[offset] || is_code[offset + 1] || get_op_code_from_hex(code).is_none() {
+                    break;
+                }
+                is_code[offset] = true;
+                is_code[offset + 1] = true;
+                match flow_of(code) {
+                    Flow::Next => {}
+                    Flow::Skip => pending.push(address.wrapping_add(4)),
+                    Flow::Call(target) => pending.push(target),
+                    Flow::Jump(target) => {
+                        pending.push(target);
+                        break;
+                    }
+                    Flow::ComputedJump(table) => {
+                        pending.extend(jump_table_targets(bytes, origin, table));
+                        break;
+                    }
+                    Flow::Return | Flow::Exit => break,
+                }
+                address = address.wrapping_add(2);
+            }
+        }
+        return is_code;
+    }
+
+    /// Guess the entries of a `JP V0, NNN` jump table.
+    /// Jump tables are almost always a run of `JP` instructions starting at NNN,
+    /// so every consecutive `JP` from the base is treated as code. When the base
+    /// does not hold a `JP` only the base itself is followed.
+    #[cfg(feature = "std")]
+    pub(crate) fn jump_table_targets(bytes: &[u8], origin: u16, table: u16) -> Vec<u16> {
+        let mut targets = vec![table];
+        for entry in 1..MAX_JUMP_TABLE_ENTRIES {
+            let address = table.wrapping_add(2 * entry as u16);
+            match word_at(bytes, origin, address) {
+                Some(code) if code & 0xF000 == 0x1000 => targets.push(address),
+                _ => break,
+            }
+        }
+        return targets;
+    }
+
+    /// Dissasemble a rom by following its control flow from the entry point
+    ///
+    /// Unlike `dissasemble_op_code_from_bytes` this only decodes instructions that can be
+    /// reached through `JP`, `CALL`, skips and `RET`. Everything else, such as sprite data,
+    /// is emitted as `DB` directives, so roms with data or an odd length disassemble cleanly.
+    /// # Arguments
+    /// * `bytes` - The rom, loaded at 0x200
+    /// # Returns
+    /// * The lines of the disassembly in address order
+    /// # Example
+    /// ```
+    /// use chip8::chip8_disassembler::chip8_disassembler::dissasemble_rom;
+    /// let bytes = [0x12, 0x04, 0xFF, 0xFF, 0x00, 0xE0, 0x12, 0x04, 0x80];
+    /// let result = dissasemble_rom(&bytes);
+    /// let text: Vec<String> = result.iter().map(|l| l.text()).collect();
+    /// assert_eq!(text, ["JP 204", "DB 0xFF, 0xFF", "CLS", "JP 204", "DB 0x80"]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn dissasemble_rom(bytes: &[u8]) -> Vec<DisassemblyLine> {
+        return dissasemble_rom_at(bytes, PROGRAM_START);
+    }
+
+    /// Dissasemble a rom loaded at `origin` by following its control flow
+    /// # Arguments
+    /// * `bytes` - The rom
+    /// * `origin` - The address the rom is loaded at, which is also the entry point
+    /// # Returns
+    /// * The lines of the disassembly in address order
+    #[cfg(feature = "std")]
+    pub fn dissasemble_rom_at(bytes: &[u8], origin: u16) -> Vec<DisassemblyLine> {
+        let is_code = find_code(bytes, origin);
+        let targets = find_targets(bytes, &is_code);
+        let mut ret = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let address = origin.wrapping_add(offset as u16);
+            if is_code[offset] {
+                let code = (bytes[offset] as u16) << 8 | bytes[offset + 1] as u16;
+                let instruction =
+                    decode_instruction(address, code).expect("reachable code is valid");
+                ret.push(DisassemblyLine::Instruction(instruction));
+                offset += 2;
+                continue;
+            }
+            let start = offset;
+            offset += 1;
+            while offset < bytes.len()
+                && !is_code[offset]
+                && offset - start < 8
+                && !targets.contains(&origin.wrapping_add(offset as u16))
+            {
+                offset += 1;
+            }
+            ret.push(DisassemblyLine::Data {
+                address,
+                bytes: bytes[start..offset].to_vec(),
+            });
+        }
+        return ret;
+    }
+
+    /// Collect the addresses referenced by reachable `JP`, `CALL`, `JP V0` and `LD I` instructions
+    #[cfg(feature = "std")]
+    fn find_targets(bytes: &[u8], is_code: &[bool]) -> BTreeSet<u16> {
+        let mut targets = BTreeSet::new();
+        for offset in (0..bytes.len()).filter(|o| is_code[*o]).step_by(2) {
+            let code = (bytes[offset] as u16) << 8 | bytes[offset + 1] as u16;
+            match code & 0xF000 {
+                0x1000 | 0x2000 | 0xA000 | 0xB000 => {
+                    targets.insert(code & 0x0FFF);
+                }
+                _ => {}
+            }
+        }
+        return targets;
+    }
+
+    /// Produce a labelled listing of a rom that reassembles to the same bytes
+    ///
+    /// Jump, call, jump table and `LD I` targets inside the rom get labels, operands are
+    /// printed in hex and every line carries its address and raw bytes as a comment.
+    /// # Arguments
+    /// * `bytes` - The rom, loaded at 0x200
+    /// # Returns
+    /// * The listing as assembly source
+    /// # Example
+    /// ```
+    /// use chip8::chip8_disassembler::chip8_disassembler::dissasemble_rom_listing;
+    /// let bytes = [0xA2, 0x04, 0x12, 0x02, 0xF0];
+    /// let listing = dissasemble_rom_listing(&bytes);
+    /// assert!(listing.contains("LD I, L204"));
+    /// assert!(listing.contains("JP L202"));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn dissasemble_rom_listing(bytes: &[u8]) -> String {
+        return dissasemble_rom_listing_at(bytes, PROGRAM_START);
+    }
+
+    /// Produce a labelled listing of a rom loaded at `origin`
+    /// # Arguments
+    /// * `bytes` - The rom
+    /// * `origin` - The address the rom is loaded at, which is also the entry point
+    /// # Returns
+    /// * The listing as assembly source
+    #[cfg(feature = "std")]
+    pub fn dissasemble_rom_listing_at(bytes: &[u8], origin: u16) -> String {
+        let lines = dissasemble_rom_at(bytes, origin);
+        let starts: BTreeSet<u16> = lines.iter().map(|l| l.address()).collect();
+        let is_code = find_code(bytes, origin);
+        let labels: BTreeSet<u16> = find_targets(bytes, &is_code)
+            .intersection(&starts)
+            .copied()
+            .collect();
+        let label = |address: u16| -> Option<String> {
+            return labels
+                .contains(&address)
+                .then(|| format!("L{:03X}", address));
+        };
+        let mut ret = format!("    ORG 0x{:03X}\n", origin);
+        for line in lines.iter() {
+            if let Some(name) = label(line.address()) {
+                ret += &format!("{}:\n", name);
+            }
+            let (text, raw) = match line {
+                DisassemblyLine::Instruction(instruction) => (
+                    CowgodSyntax { hex_prefix: true }.format_with_labels(instruction, &label),
+                    instruction.bytes.to_vec(),
+                ),
+                DisassemblyLine::Data { bytes, .. } => (line.text(), bytes.clone()),
+            };
+            let raw: Vec<String> = raw.iter().map(|b| format!("{:02X}", b)).collect();
+            ret += &format!(
+                "    {:<28}; {:03X}: {}\n",
+                text,
+                line.address(),
+                raw.join(" ")
+            );
+        }
+        return ret;
+    }
+
+    #[cfg(all(test, feature = "std"))]
     mod tests {
         use super::*;
 
@@ -419,5 +1189,219 @@ pub mod chip8_disassembler {
             assert_eq!(result[1], "RET");
             assert_eq!(result[2], "AND V1, V2");
         }
+
+        #[test]
+        fn test_dissasemble_rom_follows_control_flow() {
+            // CALL 208, JP 206, data, RET
+            let bytes = [0x22, 0x08, 0x12, 0x06, 0xF0, 0x90, 0x00, 0xEE, 0x00, 0xEE];
+            let result = dissasemble_rom(&bytes);
+            let text: Vec<String> = result.iter().map(|l| l.text()).collect();
+            assert_eq!(text, ["CALL 208", "JP 206", "DB 0xF0, 0x90", "RET", "RET"]);
+            assert_eq!(result[2].address(), 0x204);
+        }
+        #[test]
+        fn test_dissasemble_rom_skips() {
+            // SE V0, 12 ; JP 200 ; CLS ; EXIT ; data
+            let bytes = [0x30, 0x12, 0x12, 0x00, 0x00, 0xE0, 0x00, 0xFD, 0x12];
+            let result = dissasemble_rom(&bytes);
+            let text: Vec<String> = result.iter().map(|l| l.text()).collect();
+            assert_eq!(text, ["SE V0, 12", "JP 200", "CLS", "EXIT", "DB 0x12"]);
+        }
+        #[test]
+        fn test_dissasemble_rom_jump_table() {
+            // JP V0, 204 ; RET ; JP 200 ; JP 202 ; data
+            let bytes = [0xB2, 0x04, 0x00, 0xEE, 0x12, 0x00, 0x12, 0x02, 0xAB];
+            let result = dissasemble_rom(&bytes);
+            let text: Vec<String> = result.iter().map(|l| l.text()).collect();
+            assert_eq!(text, ["JP V0, 204", "RET", "JP 200", "JP 202", "DB 0xAB"]);
+        }
+        #[test]
+        fn test_dissasemble_rom_overlapping_code() {
+            // CALL 204 ; JP 205 ; the RET at 205 starts inside the SYS at 204
+            let bytes = [0x22, 0x04, 0x12, 0x05, 0x00, 0x00, 0xEE, 0xFF];
+            let result = dissasemble_rom(&bytes);
+            let text: Vec<String> = result.iter().map(|l| l.text()).collect();
+            assert_eq!(text, ["CALL 204", "JP 205", "DB 0x00", "RET", "DB 0xFF"]);
+        }
+        #[test]
+        fn test_dissasemble_rom_jump_into_earlier_instruction() {
+            // JP 203 ; JP 202, which jumps back into the middle of itself
+            let bytes = [0x12, 0x03, 0x12, 0x12, 0x02];
+            let result = dissasemble_rom_at(&bytes, 0x200);
+            let text: Vec<String> = result.iter().map(|l| l.text()).collect();
+            assert_eq!(text, ["JP 203", "DB 0x12", "JP 202"]);
+        }
+        #[test]
+        fn test_dissasemble_rom_test_rom() {
+            let bytes = std::fs::read("c8_test.c8").expect("Failed to load game");
+            assert!(dissasemble_op_code_from_bytes(&bytes).is_err());
+            let result = dissasemble_rom(&bytes);
+            assert_eq!(result[0].address(), 0x200);
+            let size: usize = result
+                .iter()
+                .map(|l| match l {
+                    DisassemblyLine::Instruction { .. } => 2,
+                    DisassemblyLine::Data { bytes, .. } => bytes.len(),
+                })
+                .sum();
+            assert_eq!(size, bytes.len());
+        }
+
+        #[test]
+        fn test_dissasemble_rom_listing() {
+            // LD I, 208 ; CALL 206 ; JP 202 ; RET ; sprite
+            let bytes = [0xA2, 0x08, 0x22, 0x06, 0x12, 0x02, 0x00, 0xEE, 0xF0, 0x90];
+            let listing = dissasemble_rom_listing(&bytes);
+            let expected = [
+                "    ORG 0x200",
+                "    LD I, L208                  ; 200: A2 08",
+                "L202:",
+                "    CALL L206                   ; 202: 22 06",
+                "    JP L202                     ; 204: 12 02",
+                "L206:",
+                "    RET                         ; 206: 00 EE",
+                "L208:",
+                "    DB 0xF0, 0x90               ; 208: F0 90",
+            ];
+            assert_eq!(listing.lines().collect::<Vec<_>>(), expected);
+        }
+        #[test]
+        fn test_dissasemble_rom_listing_operands() {
+            // LD VA, 0x1B ; SHR V1, V2 ; DRW V0, V1, 5 ; JP 0x000 ; data at a label
+            let bytes = [0x6A, 0x1B, 0x81, 0x26, 0xD0, 0x15, 0x10, 0x00];
+            let listing = dissasemble_rom_listing(&bytes);
+            assert!(listing.contains("    LD VA, 0x1B "));
+            assert!(listing.contains("    SHR V1, V2 "));
+            assert!(listing.contains("    DRW V0, V1, 0x5 "));
+            assert!(listing.contains("    JP 0x000 "));
+        }
+        #[test]
+        fn test_ret_mask() {
+            let op_code = get_op_code_from_hex(0x12EE).expect("fail");
+            assert_eq!(op_code.mnemonic, "JP NNN");
+        }
+
+        #[test]
+        fn test_dissasemble_op_code_hex_operands() {
+            assert_eq!(
+                dissasemble_op_code_from_u16(0x12EE).expect("fail"),
+                "JP 2EE"
+            );
+            assert_eq!(
+                dissasemble_op_code_from_u16(0x3A1B).expect("fail"),
+                "SE VA, 1B"
+            );
+            assert_eq!(
+                dissasemble_op_code_from_u16(0xDABF).expect("fail"),
+                "DRW VA, VB, F"
+            );
+            assert_eq!(
+                dissasemble_op_code_from_u16(0x8126).expect("fail"),
+                "SHR V1, V2"
+            );
+            assert_eq!(
+                dissasemble_op_code_from_u16(0x8106).expect("fail"),
+                "SHR V1"
+            );
+        }
+        #[test]
+        fn test_decode_instruction() {
+            let instruction = decode_instruction(0x204, 0xF155).expect("fail");
+            assert_eq!(instruction.address, 0x204);
+            assert_eq!(instruction.bytes, [0xF1, 0x55]);
+            assert_eq!(instruction.kind, OpCodeKind::LdIndirectIVx);
+            assert_eq!(instruction.mnemonic(), "LD");
+            assert_eq!(
+                instruction.operands,
+                [Operand::IndirectI, Operand::Register(1)]
+            );
+            assert!(decode_instruction(0x200, 0x5121).is_err());
+        }
+        #[test]
+        fn test_json_syntax() {
+            let instruction = decode_instruction(0x200, 0x2345).expect("fail");
+            assert_eq!(
+                JsonSyntax.format_with_labels(&instruction, &|a| Some(format!("L{:03X}", a))),
+                concat!(
+                    r#"{"address":512,"bytes":[35,69],"kind":"Call","mnemonic":"CALL","#,
+                    r#""operands":[{"label":"L345","type":"address","value":837}],"text":"CALL L345"}"#
+                )
+            );
+            // Labels are escaped
+            let text =
+                JsonSyntax.format_with_labels(&instruction, &|_| Some(String::from("a\"b\\")));
+            let value: serde_json::Value = serde_json::from_str(&text).expect("fail");
+            assert_eq!(value["operands"][0]["label"], "a\"b\\");
+            assert_eq!(value["text"], "CALL a\"b\\");
+            let instruction = decode_instruction(0x200, 0xF20A).expect("fail");
+            assert!(JsonSyntax
+                .format(&instruction)
+                .contains(r#""operands":[{"type":"register","value":2},{"type":"key"}]"#));
+            let instruction = decode_instruction(0x200, 0xF155).expect("fail");
+            assert!(JsonSyntax
+                .format(&instruction)
+                .contains(r#""operands":[{"type":"indirect_i"},{"type":"register","value":1}]"#));
+            let instruction = decode_instruction(0x200, 0xF315).expect("fail");
+            assert!(JsonSyntax
+                .format(&instruction)
+                .contains(r#""operands":[{"type":"delay_timer"},{"type":"register","value":3}]"#));
+        }
+        #[test]
+        fn test_octo_syntax_round_trip() {
+            for op_code in OP_CODES.iter() {
+                let code = op_code.code | (!op_code.bit_mask & 0x0A5C);
+                let instruction = decode_instruction(0x202, code).expect("fail");
+                let text = OctoSyntax.format(&instruction);
+                let source = format!(": main {} clear", text);
+                let rom = crate::chip8_octo::compile(&source).expect(&source);
+                assert_eq!(u16::from_be_bytes([rom[2], rom[3]]), code, "{}", text);
+            }
+        }
+
+        #[test]
+        fn test_disassembly_errors() {
+            assert_eq!(
+                dissasemble_op_code_from_str("12G4"),
+                Err(DisassemblyError::InvalidHex {
+                    text: String::from("12G4")
+                })
+            );
+            assert!(dissasemble_op_code_from_str("").is_err());
+            assert!(dissasemble_op_code_from_str("12345").is_err());
+            assert_eq!(dissasemble_op_code_from_str("0x00E0").expect("fail"), "CLS");
+            assert_eq!(
+                dissasemble_op_code_from_bytes(&[0x00, 0xE0, 0x51, 0x21]),
+                Err(DisassemblyError::InvalidOpCode {
+                    offset: 2,
+                    word: 0x5121
+                })
+            );
+            let err = dissasemble_op_code_from_bytes(&[0x00, 0xE0, 0x80]).unwrap_err();
+            assert_eq!(
+                err,
+                DisassemblyError::TruncatedOpCode {
+                    offset: 2,
+                    byte: 0x80
+                }
+            );
+            assert_eq!(err.to_string(), "Truncated OpCode 80 at offset 0x2");
+            assert_eq!(
+                decode_instruction(0x206, 0x5121),
+                Err(DisassemblyError::InvalidOpCode {
+                    offset: 0x206,
+                    word: 0x5121
+                })
+            );
+        }
+        #[test]
+        fn test_dissasemble_tolerant() {
+            let bytes = std::fs::read("c8_test.c8").expect("Failed to load game");
+            let result = dissasemble_op_code_from_bytes_tolerant(&bytes);
+            assert_eq!(result.len(), bytes.len().div_ceil(2));
+            assert_eq!(
+                result[result.len() - 1],
+                format!("DB 0x{:02X}", bytes[bytes.len() - 1])
+            );
+        }
     }
 }
@@ -0,0 +1,834 @@
+use crate::chip8_frontend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::chip8_octo::compile;
+use crate::chip8_rom_database::{sha1_hex, RomDatabase, RomInfo};
+use crate::{Chip8, LoadError};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// The bundled environment definitions
+const BUNDLED_ENVIRONMENTS: &str = include_str!("../data/environments.txt");
+
+/// Games written for the environment API, as Octo source
+const BUNDLED_GAMES: [(&str, &str); 2] = [
+    ("catch", include_str!("../games/catch.8o")),
+    ("dodge", include_str!("../games/dodge.8o")),
+];
+
+/// The number of frames an action is held for when a definition does not say
+pub const DEFAULT_FRAMES_PER_ACTION: u32 = 4;
+
+/// The size of an observation, one bit per pixel
+pub const OBSERVATION_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT / 8;
+
+/// The display as a bitmap, row by row with the leftmost pixel of each byte in the highest bit
+pub type Observation = [u8; OBSERVATION_SIZE];
+
+/// A line of an environment definition that could not be read, the line is 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}: {}", self.line, self.message);
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// An operator between two expressions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    /// Bitwise and
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    /// Logical and, 1 if both sides are not 0
+    And,
+    /// Logical or, 1 if either side is not 0
+    Or,
+}
+
+/// A number computed from the interpreter's memory and registers, for rewards and done signals
+///
+/// Comparisons give 1 or 0, and anything other than 0 counts as true.
+/// ```text
+/// 12, 0x2F0           numbers
+/// mem[0x2F0]          a byte of memory
+/// bcd(0x2F0, 3)       3 decimal digits stored one to a byte, as FX33 writes them
+/// v0 .. vf, i         registers
+/// prev(expression)    the value at the start of the step
+/// + - * & == != < > <= >= && || ! and brackets, with the usual precedence
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Memory(u16),
+    Bcd { address: u16, digits: u8 },
+    Register(u8),
+    I,
+    Prev(Box<Expr>),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+}
+
+/// The parts of the machine an expression can read
+struct View<'a> {
+    memory: &'a [u8],
+    v: &'a [u8; 16],
+    i: u16,
+}
+
+impl<'a> View<'a> {
+    fn of(c: &'a Chip8) -> Self {
+        return Self {
+            memory: c.get_memory(),
+            v: c.get_registers(),
+            i: c.get_i(),
+        };
+    }
+}
+
+/// The operators of each precedence level, loosest first
+const LEVELS: [&[(&str, Operator)]; 5] = [
+    &[("||", Operator::Or)],
+    &[("&&", Operator::And)],
+    &[
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessOrEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+    ],
+    &[("+", Operator::Add), ("-", Operator::Subtract)],
+    &[("*", Operator::Multiply), ("&", Operator::BitAnd)],
+];
+
+/// Reads an expression from the front
+struct Parser<'a> {
+    text: &'a str,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        self.text = self.text.trim_start();
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.skip_space();
+        // && and || must not be read as & and |
+        if let Some(rest) = self.text.strip_prefix(symbol) {
+            if !(symbol == "&" && rest.starts_with('&')) {
+                self.text = rest;
+                return true;
+            }
+        }
+        return false;
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        return Err(format!("expected {}", symbol));
+    }
+
+    fn word(&mut self) -> &str {
+        self.skip_space();
+        let end = self
+            .text
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(self.text.len());
+        let (word, rest) = self.text.split_at(end);
+        self.text = rest;
+        return word;
+    }
+
+    fn number(&mut self) -> Result<i64, String> {
+        let word = self.word();
+        let value = match word.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => word.parse(),
+        };
+        return value.map_err(|_| format!("expected a number but found '{}'", word));
+    }
+
+    fn address(&mut self) -> Result<u16, String> {
+        return match self.number()? {
+            n @ 0..=0xFFF => Ok(n as u16),
+            n => Err(format!("address {:#x} is outside of memory", n)),
+        };
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut ret = self.binary(level + 1)?;
+        'outer: loop {
+            for (symbol, operator) in LEVELS[level] {
+                if self.eat(symbol) {
+                    let right = self.binary(level + 1)?;
+                    ret = Expr::Binary(Box::new(ret), *operator, Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(ret);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("!") && !self.text.starts_with('=') {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let ret = self.binary(0)?;
+            self.expect(")")?;
+            return Ok(ret);
+        }
+        if self.text.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok(Expr::Number(self.number()?));
+        }
+        let word = self.word().to_ascii_lowercase();
+        return match word.as_str() {
+            "mem" => {
+                self.expect("[")?;
+                let address = self.address()?;
+                self.expect("]")?;
+                Ok(Expr::Memory(address))
+            }
+            "bcd" => {
+                self.expect("(")?;
+                let address = self.address()?;
+                self.expect(",")?;
+                let digits = match self.number()? {
+                    n @ 1..=10 => n as u8,
+                    _ => return Err(String::from("expected from 1 to 10 digits")),
+                };
+                self.expect(")")?;
+                Ok(Expr::Bcd { address, digits })
+            }
+            "prev" => {
+                self.expect("(")?;
+                let ret = self.binary(0)?;
+                self.expect(")")?;
+                Ok(Expr::Prev(Box::new(ret)))
+            }
+            "i" => Ok(Expr::I),
+            _ => match word.strip_prefix('v').map(|x| u8::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x < 16 => Ok(Expr::Register(x)),
+                _ if word.is_empty() => Err(format!("unexpected '{}'", self.text)),
+                _ => Err(format!("unknown name '{}'", word)),
+            },
+        };
+    }
+}
+
+impl Expr {
+    /// Read an expression
+    /// # Example
+    /// ```
+    /// use chip8::chip8_env::Expr;
+    /// let score = Expr::parse("bcd(0x202, 3) - prev(bcd(0x202, 3))").expect("fail");
+    /// assert!(Expr::parse("mem[0x1000]").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, EnvError> {
+        let error = |message: String| EnvError { line: 1, message };
+        let mut parser = Parser { text };
+        let ret = parser.binary(0).map_err(error)?;
+        parser.skip_space();
+        if !parser.text.is_empty() {
+            return Err(error(format!("unexpected '{}'", parser.text)));
+        }
+        return Ok(ret);
+    }
+
+    /// Work out the value
+    /// # Arguments
+    /// * `now` - The machine as it is
+    /// * `before` - The machine at the start of the step, for `prev`
+    fn eval(&self, now: &View, before: &View) -> i64 {
+        return match self {
+            Expr::Number(n) => *n,
+            Expr::Memory(address) => now.memory[*address as usize] as i64,
+            Expr::Bcd { address, digits } => (0..*digits as usize)
+                .map(|d| now.memory[(*address as usize + d) & 0xFFF] as i64)
+                .fold(0, |n, digit| n * 10 + digit),
+            Expr::Register(x) => now.v[*x as usize] as i64,
+            Expr::I => now.i as i64,
+            Expr::Prev(e) => e.eval(before, before),
+            Expr::Negate(e) => e.eval(now, before).wrapping_neg(),
+            Expr::Not(e) => (e.eval(now, before) == 0) as i64,
+            Expr::Binary(a, operator, b) => {
+                let a = a.eval(now, before);
+                let b = b.eval(now, before);
+                match operator {
+                    Operator::Add => a.wrapping_add(b),
+                    Operator::Subtract => a.wrapping_sub(b),
+                    Operator::Multiply => a.wrapping_mul(b),
+                    Operator::BitAnd => a & b,
+                    Operator::Equal => (a == b) as i64,
+                    Operator::NotEqual => (a != b) as i64,
+                    Operator::Less => (a < b) as i64,
+                    Operator::Greater => (a > b) as i64,
+                    Operator::LessOrEqual => (a <= b) as i64,
+                    Operator::GreaterOrEqual => (a >= b) as i64,
+                    Operator::And => (a != 0 && b != 0) as i64,
+                    Operator::Or => (a != 0 || b != 0) as i64,
+                }
+            }
+        };
+    }
+}
+
+/// How to play a rom as a reinforcement learning environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvSpec {
+    pub name: String,
+    /// The keypad combinations an agent can choose from, bit N set to hold key N
+    pub actions: Vec<u16>,
+    /// How many frames each action is held for
+    pub frames_per_action: u32,
+    /// Instructions per frame, if the rom needs a speed other than its database entry's
+    pub tickrate: Option<u32>,
+    /// The reward for a step, worked out at its end
+    pub reward: Expr,
+    /// Whether the episode is over, worked out at the end of each step
+    pub done: Expr,
+}
+
+/// Environment definitions for any number of roms, keyed by the SHA-1 hash of the rom
+///
+/// As text, each rom's definition follows its hash in square brackets as `key = value` lines.
+/// Actions are keys in hexadecimal, joined with `+` to hold several and `-` for none. `frames`
+/// and `tickrate` are optional. Lines starting with `#` are comments.
+/// ```text
+/// # games/catch.8o
+/// [2a7940f975d6a855ad4c0480b6b583ce4e5ee0fd]
+/// name = catch
+/// actions = - 4 6
+/// frames = 4
+/// reward = bcd(0x202, 3) - prev(bcd(0x202, 3))
+/// done = mem[0x205] == 0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvFile {
+    roms: BTreeMap<String, EnvSpec>,
+}
+
+/// The fields of a definition as they are read
+#[derive(Default)]
+struct Fields {
+    line: usize,
+    name: Option<String>,
+    actions: Option<Vec<u16>>,
+    frames_per_action: Option<u32>,
+    tickrate: Option<u32>,
+    reward: Option<Expr>,
+    done: Option<Expr>,
+}
+
+impl Fields {
+    fn finish(self) -> Result<EnvSpec, EnvError> {
+        let line = self.line;
+        let missing = |field: &str| EnvError {
+            line,
+            message: format!("the definition has no {}", field),
+        };
+        return Ok(EnvSpec {
+            name: self.name.ok_or_else(|| missing("name"))?,
+            actions: self.actions.ok_or_else(|| missing("actions"))?,
+            frames_per_action: self.frames_per_action.unwrap_or(DEFAULT_FRAMES_PER_ACTION),
+            tickrate: self.tickrate,
+            reward: self.reward.ok_or_else(|| missing("reward"))?,
+            done: self.done.ok_or_else(|| missing("done"))?,
+        });
+    }
+}
+
+/// Read an action such as `4+5`, or `-` for no keys
+fn parse_action(text: &str) -> Option<u16> {
+    if text == "-" {
+        return Some(0);
+    }
+    let mut ret = 0;
+    for key in text.split('+') {
+        let key = u8::from_str_radix(key, 16).ok().filter(|key| *key < 16)?;
+        ret |= 1 << key;
+    }
+    return Some(ret);
+}
+
+impl EnvFile {
+    /// The definitions that ship with the crate, for the games in `games/` and some public domain roms
+    pub fn bundled() -> Self {
+        return Self::parse(BUNDLED_ENVIRONMENTS).expect("the bundled definitions are valid");
+    }
+
+    /// Read definitions from text
+    pub fn parse(text: &str) -> Result<Self, EnvError> {
+        let mut ret = Self::default();
+        let mut rom: Option<(String, Fields)> = None;
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| EnvError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(hash) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let hash = hash.trim().to_ascii_lowercase();
+                if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(error(String::from("expected a rom hash in hexadecimal")));
+                }
+                if let Some((hash, fields)) = rom.take() {
+                    ret.roms.insert(hash, fields.finish()?);
+                }
+                let fields = Fields {
+                    line: index + 1,
+                    ..Fields::default()
+                };
+                rom = Some((hash, fields));
+                continue;
+            }
+            let Some((_, fields)) = rom.as_mut() else {
+                return Err(error(String::from(
+                    "a definition must follow a [rom hash] line",
+                )));
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(String::from("expected key = value")));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let number = || {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| error(format!("expected a positive {}", key)))
+            };
+            match key {
+                "name" => fields.name = Some(value.to_string()),
+                "actions" => {
+                    let actions = value
+                        .split_whitespace()
+                        .map(|a| parse_action(a).ok_or_else(|| error(format!("bad action {}", a))))
+                        .collect::<Result<Vec<u16>, EnvError>>()?;
+                    if actions.is_empty() {
+                        return Err(error(String::from("expected at least one action")));
+                    }
+                    fields.actions = Some(actions);
+                }
+                "frames" => fields.frames_per_action = Some(number()?),
+                "tickrate" => fields.tickrate = Some(number()?),
+                "reward" | "done" => {
+                    let expr = Expr::parse(value).map_err(|e| error(e.message))?;
+                    if key == "reward" {
+                        fields.reward = Some(expr);
+                    } else {
+                        fields.done = Some(expr);
+                    }
+                }
+                _ => return Err(error(format!("unknown key {}", key))),
+            }
+        }
+        if let Some((hash, fields)) = rom {
+            ret.roms.insert(hash, fields.finish()?);
+        }
+        return Ok(ret);
+    }
+
+    /// Read a definition file
+    /// # Returns
+    /// * An `InvalidData` error wrapping an `EnvError` if the file cannot be parsed
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        return Self::parse(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+    }
+
+    /// The definition for a rom
+    pub fn get(&self, rom_hash: &str) -> Option<&EnvSpec> {
+        return self.roms.get(&rom_hash.to_ascii_lowercase());
+    }
+
+    /// The definition for a rom, by its bytes
+    pub fn lookup(&self, rom: &[u8]) -> Option<&EnvSpec> {
+        return self.get(&sha1_hex(rom));
+    }
+}
+
+/// The names of the bundled games
+pub fn bundled_games() -> impl Iterator<Item = &'static str> {
+    return BUNDLED_GAMES.iter().map(|(name, _)| *name);
+}
+
+/// Compile one of the bundled games
+/// # Returns
+/// * The rom, or `None` if there is no game by that name
+pub fn bundled_game(name: &str) -> Option<Vec<u8>> {
+    let (_, source) = BUNDLED_GAMES.iter().find(|(n, _)| *n == name)?;
+    return Some(compile(source).expect("the bundled games compile"));
+}
+
+/// A rom wrapped up for reinforcement learning, with `reset` and `step`
+/// # Example
+/// ```
+/// use chip8::chip8_env::Env;
+/// let mut env = Env::bundled("catch").expect("fail");
+/// env.set_seed(1);
+/// env.reset();
+/// let mut score = 0;
+/// loop {
+///     // Hold key 6, the second action, and see how far that gets
+///     let (_observation, reward, done) = env.step(2);
+///     score += reward;
+///     if done {
+///         break;
+///     }
+/// }
+/// assert!(score < 0);
+/// ```
+pub struct Env {
+    c: Chip8,
+    rom: Vec<u8>,
+    info: Option<RomInfo>,
+    spec: EnvSpec,
+    seed: Option<u32>,
+    before: (Vec<u8>, [u8; 16], u16),
+    done: bool,
+}
+
+impl Env {
+    /// Wrap a rom, configured from the bundled rom database if it is there
+    /// # Arguments
+    /// * `rom` - The rom
+    /// * `spec` - How to play it
+    /// # Returns
+    /// * The environment, already reset, or an error if the rom cannot be loaded
+    pub fn new(rom: Vec<u8>, spec: EnvSpec) -> Result<Self, LoadError> {
+        let mut ret = Self {
+            c: Chip8::new(),
+            info: RomDatabase::bundled().lookup(&rom).cloned(),
+            rom,
+            spec,
+            seed: None,
+            before: (Vec::new(), [0; 16], 0),
+            done: false,
+        };
+        ret.c.load_game_from_slice(&ret.rom)?;
+        ret.reset();
+        return Ok(ret);
+    }
+
+    /// One of the bundled games with its bundled definition
+    pub fn bundled(name: &str) -> Option<Self> {
+        let rom = bundled_game(name)?;
+        let spec = EnvFile::bundled().lookup(&rom)?.clone();
+        return Some(Self::new(rom, spec).expect("the bundled games fit in memory"));
+    }
+
+    /// Make every following episode repeatable, each one still differs from the one before
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = Some(seed);
+    }
+
+    /// Start a new episode from power on
+    /// # Returns
+    /// * The first observation
+    pub fn reset(&mut self) -> Observation {
+        self.c = Chip8::new();
+        self.c
+            .load_game_from_slice(&self.rom)
+            .expect("the rom loaded before");
+        if let Some(info) = self.info.as_ref() {
+            self.c.configure(info);
+        }
+        if let Some(tickrate) = self.spec.tickrate {
+            self.c.set_instructions_per_frame(tickrate);
+        }
+        if let Some(seed) = self.seed {
+            self.c.set_rng_seed(seed);
+            self.seed = Some(seed.wrapping_mul(0x9E37_79B9).wrapping_add(1));
+        }
+        self.done = false;
+        return self.observation();
+    }
+
+    /// Hold an action's keys for `frames_per_action` frames
+    /// # Arguments
+    /// * `action` - An index into the definition's actions
+    /// # Returns
+    /// * The observation, the reward and whether the episode is over. Once it is over, steps
+    ///   do nothing until the next `reset`.
+    /// # Panics
+    /// This function will panic if there is no such action
+    pub fn step(&mut self, action: usize) -> (Observation, i64, bool) {
+        let keys = self.spec.actions[action];
+        if self.done {
+            return (self.observation(), 0, true);
+        }
+        self.before.0.clear();
+        self.before.0.extend_from_slice(self.c.get_memory());
+        self.before.1 = *self.c.get_registers();
+        self.before.2 = self.c.get_i();
+        for key in 0..16 {
+            self.c.set_key(key, keys & 1 << key != 0);
+        }
+        for _ in 0..self.spec.frames_per_action {
+            self.c.run_frame();
+        }
+        let now = View::of(&self.c);
+        let before = View {
+            memory: &self.before.0,
+            v: &self.before.1,
+            i: self.before.2,
+        };
+        let reward = self.spec.reward.eval(&now, &before);
+        self.done = self.spec.done.eval(&now, &before) != 0;
+        return (self.observation(), reward, self.done);
+    }
+
+    /// The display as it is now
+    pub fn observation(&self) -> Observation {
+        let mut ret = [0; OBSERVATION_SIZE];
+        for (index, pixel) in self.c.get_gfx().iter().enumerate() {
+            if *pixel != 0 {
+                ret[index / 8] |= 0x80 >> (index % 8);
+            }
+        }
+        return ret;
+    }
+
+    /// The number of actions to choose from
+    pub fn action_count(&self) -> usize {
+        return self.spec.actions.len();
+    }
+
+    /// How the rom is played
+    pub fn spec(&self) -> &EnvSpec {
+        return &self.spec;
+    }
+
+    /// The interpreter, to look at anything the observation leaves out
+    pub fn chip8(&self) -> &Chip8 {
+        return &self.c;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expressions() {
+        let mut c = Chip8::new();
+        let before = (c.get_memory().to_vec(), *c.get_registers(), c.get_i());
+        c.set_memory(0x300, 1);
+        c.set_memory(0x301, 2);
+        c.set_memory(0x302, 3);
+        c.set_registers([7; 16]);
+        c.set_i(0x300);
+        let now = View::of(&c);
+        let before = View {
+            memory: &before.0,
+            v: &before.1,
+            i: before.2,
+        };
+        let eval = |text: &str| Expr::parse(text).expect(text).eval(&now, &before);
+        assert_eq!(eval("bcd(0x300, 3)"), 123);
+        assert_eq!(eval("bcd(0x300, 3) - prev(bcd(0x300, 3))"), 123);
+        assert_eq!(eval("mem[0x301] * 2 + 1"), 5);
+        assert_eq!(eval("1 + 2 * 3 == 7 && !(va < 7)"), 1);
+        assert_eq!(eval("0 || mem[768] & 1"), 1);
+        assert_eq!(eval("-i + prev(i)"), -0x300);
+        assert_eq!(eval("vF != 7"), 0);
+    }
+    #[test]
+    fn test_expression_errors() {
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("mem[0x300").is_err());
+        assert!(Expr::parse("bcd(0x300, 0)").is_err());
+        assert!(Expr::parse("vg").is_err());
+        assert!(Expr::parse("1 2").is_err());
+        assert!(Expr::parse("lives").is_err());
+    }
+    #[test]
+    fn test_definitions() {
+        let text = "# comment\n[ABC]\nname = test\nactions = - 4 4+6\n\
+                    reward = v0\ndone = v1\n[def]\nname = other\nactions = 0\n\
+                    frames = 2\ntickrate = 30\nreward = 1\ndone = 0\n";
+        let envs = EnvFile::parse(text).expect("fail");
+        let spec = envs.get("abc").expect("fail");
+        assert_eq!(spec.actions, [0, 0x10, 0x50]);
+        assert_eq!(spec.frames_per_action, DEFAULT_FRAMES_PER_ACTION);
+        assert_eq!(spec.tickrate, None);
+        assert_eq!(spec.reward, Expr::Register(0));
+        let spec = envs.get("DEF").expect("fail");
+        assert_eq!((spec.frames_per_action, spec.tickrate), (2, Some(30)));
+
+        let line = |text: &str| EnvFile::parse(text).expect_err("fail").line;
+        assert_eq!(line("name = x\n"), 1);
+        assert_eq!(line("[abc]\nname = x\nactions = 10\n"), 3);
+        assert_eq!(line("[abc]\nframes = 0\n"), 2);
+        assert_eq!(line("[abc]\nreward = (1\n"), 2);
+        assert_eq!(line("[abc]\nspeed = 1\n"), 2);
+        assert_eq!(line("[abc]\nname = x\n\n[def]\n"), 1);
+    }
+    #[test]
+    fn test_bundled() {
+        let envs = EnvFile::bundled();
+        for name in bundled_games() {
+            let rom = bundled_game(name).expect("fail");
+            assert_eq!(envs.lookup(&rom).expect(name).name, name);
+        }
+        assert!(Env::bundled("pong").is_none());
+        // Pong is only defined, the rom is not bundled
+        let pong = envs
+            .get("b232ef880bd6060fb45fa6effed7edf0ae95670e")
+            .expect("fail");
+        assert_eq!(pong.name, "pong");
+        assert_eq!(pong.actions, [0, 1 << 1, 1 << 4]);
+    }
+    #[test]
+    fn test_pong() {
+        // Pong is not bundled, point CHIP8_ROMS at a directory holding the rom to run this test
+        let Some(dir) = std::env::var_os("CHIP8_ROMS") else {
+            return;
+        };
+        let envs = EnvFile::bundled();
+        let entries = std::fs::read_dir(&dir).expect("CHIP8_ROMS is a directory");
+        let (rom, spec) = entries
+            .flatten()
+            .find_map(|entry| {
+                let rom = std::fs::read(entry.path()).ok()?;
+                let spec = envs
+                    .lookup(&rom)
+                    .filter(|spec| spec.name == "pong")?
+                    .clone();
+                return Some((rom, spec));
+            })
+            .expect("CHIP8_ROMS holds pong");
+        let mut env = Env::new(rom, spec).expect("fail");
+        env.set_seed(1);
+        env.reset();
+        // The serve gets past the right paddle, which never moves
+        let reward = (0..300).map(|_| env.step(0).1).find(|reward| *reward != 0);
+        assert_eq!(reward, Some(1));
+        assert_eq!(env.chip8().get_memory()[0x2F3..0x2F5], [1, 0]);
+        // Holding the left paddle at the top, the rewards add up to the difference in scores
+        let mut total = 1;
+        for _ in 0..5000 {
+            let (_, reward, done) = env.step(1);
+            total += reward;
+            if done {
+                break;
+            }
+        }
+        let score = &env.chip8().get_memory()[0x2F3..0x2F5];
+        assert!(score.contains(&9));
+        assert_eq!(total, score[0] as i64 - score[1] as i64);
+    }
+    #[test]
+    fn test_catch() {
+        let mut env = Env::bundled("catch").expect("fail");
+        assert_eq!(env.action_count(), 3);
+        env.set_seed(7);
+        // Episodes start from power on, before anything is drawn
+        assert_eq!(env.reset(), [0; OBSERVATION_SIZE]);
+        // Follow the ball, which should catch every one
+        let mut total = 0;
+        for _ in 0..200 {
+            let c = env.chip8();
+            let (paddle, ball) = (c.get_registers()[0xA] as i32, c.get_registers()[0xB] as i32);
+            let action = if ball < paddle {
+                1
+            } else if ball > paddle + 7 {
+                2
+            } else {
+                0
+            };
+            let (_, reward, done) = env.step(action);
+            assert!(reward >= 0);
+            assert!(!done);
+            total += reward;
+        }
+        assert!(total >= 10);
+        assert_eq!(total, env.chip8().get_registers()[0xD] as i64);
+        // Standing still loses the three lives
+        let (mut total, mut steps) = (0, 0);
+        env.reset();
+        loop {
+            let (_, reward, done) = env.step(0);
+            total += reward;
+            steps += 1;
+            if done {
+                break;
+            }
+        }
+        assert!(total <= -2);
+        // Steps after the end change nothing
+        let (_, reward, done) = env.step(1);
+        assert_eq!((reward, done), (0, true));
+        assert!(steps < 200);
+    }
+    #[test]
+    fn test_dodge() {
+        let mut env = Env::bundled("dodge").expect("fail");
+        let mut episodes = Vec::new();
+        for _ in 0..2 {
+            env.set_seed(3);
+            env.reset();
+            let (mut observations, mut total) = (Vec::new(), 0);
+            loop {
+                let (observation, reward, done) = env.step(0);
+                observations.push(observation);
+                total += reward;
+                if done {
+                    break;
+                }
+            }
+            let score = &env.chip8().get_memory()[0x202..0x205];
+            assert_eq!(total, (score[0] * 100 + score[1] * 10 + score[2]) as i64);
+            episodes.push(observations);
+        }
+        // The same seed plays the same game
+        assert_eq!(episodes[0], episodes[1]);
+        assert!(episodes[0].len() < 1000);
+    }
+    #[test]
+    fn test_observation() {
+        // Draw the top row of the 0 glyph, 0xF0, at the top left
+        let spec = EnvSpec {
+            name: String::from("test"),
+            actions: vec![0],
+            frames_per_action: 1,
+            tickrate: None,
+            reward: Expr::Number(1),
+            done: Expr::Number(0),
+        };
+        let mut env = Env::new(vec![0xD0, 0x01, 0x12, 0x02], spec).expect("fail");
+        assert_eq!(env.reset(), [0; OBSERVATION_SIZE]);
+        let (observation, reward, done) = env.step(0);
+        assert_eq!(observation[..2], [0xF0, 0]);
+        assert!(observation[2..].iter().all(|byte| *byte == 0));
+        assert_eq!((reward, done), (1, false));
+    }
+}
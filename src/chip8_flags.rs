@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub use crate::FLAG_COUNT;
+
+/// Somewhere to keep the RPL user flags of each rom between runs
+///
+/// Roms are identified by the SHA-1 hash of their bytes.
+pub trait FlagStore: Send {
+    /// Get the flags saved for a rom, if there are any
+    fn load(&mut self, rom_hash: &str) -> Option<[u8; FLAG_COUNT]>;
+    /// Save the flags of a rom
+    fn save(&mut self, rom_hash: &str, flags: &[u8; FLAG_COUNT]) -> std::io::Result<()>;
+}
+
+/// Keeps flags for as long as the store lives
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFlagStore {
+    flags: HashMap<String, [u8; FLAG_COUNT]>,
+}
+
+impl FlagStore for MemoryFlagStore {
+    fn load(&mut self, rom_hash: &str) -> Option<[u8; FLAG_COUNT]> {
+        return self.flags.get(rom_hash).copied();
+    }
+
+    fn save(&mut self, rom_hash: &str, flags: &[u8; FLAG_COUNT]) -> std::io::Result<()> {
+        self.flags.insert(rom_hash.to_string(), *flags);
+        return Ok(());
+    }
+}
+
+/// Keeps flags in a directory, one `<rom hash>.flags` file of 16 bytes per rom
+#[derive(Debug, Clone)]
+pub struct FileFlagStore {
+    directory: PathBuf,
+}
+
+impl FileFlagStore {
+    /// Create a store that keeps its files in `directory`, which is created on the first save
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// use chip8::chip8_flags::FileFlagStore;
+    /// let mut c = Chip8::new();
+    /// c.set_flag_store(Box::new(FileFlagStore::new(std::env::temp_dir().join("chip8-flags"))));
+    /// ```
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        return Self {
+            directory: directory.into(),
+        };
+    }
+
+    fn path(&self, rom_hash: &str) -> PathBuf {
+        return self.directory.join(format!("{}.flags", rom_hash));
+    }
+}
+
+impl FlagStore for FileFlagStore {
+    fn load(&mut self, rom_hash: &str) -> Option<[u8; FLAG_COUNT]> {
+        let bytes = std::fs::read(self.path(rom_hash)).ok()?;
+        let mut flags = [0; FLAG_COUNT];
+        let count = bytes.len().min(FLAG_COUNT);
+        flags[..count].copy_from_slice(&bytes[..count]);
+        return Some(flags);
+    }
+
+    fn save(&mut self, rom_hash: &str, flags: &[u8; FLAG_COUNT]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        return std::fs::write(self.path(rom_hash), flags);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    fn run(c: &mut Chip8, opcode: u16) {
+        let pc = c.pc as usize;
+        c.memory[pc] = (opcode >> 8) as u8;
+        c.memory[pc + 1] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let mut store = MemoryFlagStore::default();
+        assert_eq!(store.load("abc"), None);
+        store.save("abc", &[7; FLAG_COUNT]).expect("fail");
+        assert_eq!(store.load("abc"), Some([7; FLAG_COUNT]));
+    }
+    #[test]
+    fn test_flags_survive_restart() {
+        let directory = std::env::temp_dir().join(format!("chip8-flags-{}", std::process::id()));
+        let game = vec![0x12, 0x00, 0x00, 0x00];
+
+        let mut c = Chip8::new();
+        c.set_flag_store(Box::new(FileFlagStore::new(&directory)));
+        c.load_game(game.clone()).expect("fail");
+        c.v[..16].copy_from_slice(&[9; 16]);
+        c.pc = 0x202;
+        run(&mut c, 0xFF75);
+
+        let mut c = Chip8::new();
+        c.load_game(game.clone()).expect("fail");
+        c.set_flag_store(Box::new(FileFlagStore::new(&directory)));
+        c.pc = 0x202;
+        run(&mut c, 0xFF85);
+        assert_eq!(c.v, [9; 16]);
+
+        // Another rom has its own flags
+        let mut c = Chip8::new();
+        c.set_flag_store(Box::new(FileFlagStore::new(&directory)));
+        c.load_game(vec![0x12, 0x02, 0x00, 0x00]).expect("fail");
+        assert_eq!(c.get_flags(), &[0; FLAG_COUNT]);
+        std::fs::remove_dir_all(&directory).expect("fail");
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{Chip8, CHIP8_FONTSET};
+use core::fmt;
+
+/// The number of bytes in a small font, 16 glyphs of 5 rows
+pub const SMALL_FONT_SIZE: usize = 16 * 5;
+
+/// The number of bytes in a big font, 16 glyphs of 10 rows
+pub const BIG_FONT_SIZE: usize = 16 * 10;
+
+/// The 4x5 hex fonts used by `FX29`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmallFont {
+    CosmacVip,
+    Dream6800,
+    Eti660,
+    Schip,
+    Octo,
+}
+
+/// The 8x10 fonts used by `FX30`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BigFont {
+    /// The small font with every row drawn twice, as this interpreter has always used
+    Doubled,
+    /// The SCHIP 1.1 digits. SCHIP has no big A-F, so those are taken from the Octo font.
+    Schip,
+    /// The big hex font Octo uses for XO-CHIP
+    Octo,
+}
+
+const COSMAC_VIP: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const DREAM_6800: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const ETI_660: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xE0, 0x80, 0x80, // F
+];
+
+const SCHIP: [u8; SMALL_FONT_SIZE] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const SCHIP_BIG: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+const OCTO_BIG: [u8; BIG_FONT_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+impl SmallFont {
+    /// The glyph rows of the font
+    pub fn bytes(&self) -> [u8; SMALL_FONT_SIZE] {
+        return match self {
+            SmallFont::CosmacVip => COSMAC_VIP,
+            SmallFont::Dream6800 => DREAM_6800,
+            SmallFont::Eti660 => ETI_660,
+            SmallFont::Schip => SCHIP,
+            SmallFont::Octo => CHIP8_FONTSET[..SMALL_FONT_SIZE]
+                .try_into()
+                .expect("the fontset starts with a small font"),
+        };
+    }
+}
+
+/// Errors from loading fonts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontError {
+    /// The font is neither a small font nor a small font followed by a big font
+    InvalidSize(usize),
+    /// The font does not fit in memory at the address
+    InvalidAddress(u16),
+    /// The font at the address would overwrite the program, which starts at the load address
+    OverlapsProgram(u16),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            FontError::InvalidSize(size) => write!(
+                f,
+                "a font is {} or {} bytes, not {}",
+                SMALL_FONT_SIZE,
+                SMALL_FONT_SIZE + BIG_FONT_SIZE,
+                size
+            ),
+            FontError::InvalidAddress(address) => {
+                write!(f, "a font does not fit in memory at {:03X}", address)
+            }
+            FontError::OverlapsProgram(address) => {
+                write!(f, "a font at {:03X} would overwrite the program", address)
+            }
+        };
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FontError {}
+
+/// A small font for `FX29` and a big font for `FX30`
+///
+/// In memory the big font follows the small font, so `FX29` points I at
+/// `base + digit * 5` and `FX30` at `base + 80 + digit * 10`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Font {
+    pub small: [u8; SMALL_FONT_SIZE],
+    pub big: [u8; BIG_FONT_SIZE],
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        return Self::new(SmallFont::Octo, BigFont::Doubled);
+    }
+}
+
+impl Font {
+    /// Combine built-in small and big fonts
+    /// # Example
+    /// ```
+    /// use chip8::chip8_font::{BigFont, Font, SmallFont};
+    /// let font = Font::new(SmallFont::CosmacVip, BigFont::Schip);
+    /// assert_eq!(font.small[5..10], [0x60, 0x20, 0x20, 0x20, 0x70]);
+    /// ```
+    pub fn new(small: SmallFont, big: BigFont) -> Self {
+        let small = small.bytes();
+        let big = match big {
+            BigFont::Doubled => Self::doubled(&small),
+            BigFont::Schip => SCHIP_BIG,
+            BigFont::Octo => OCTO_BIG,
+        };
+        return Self { small, big };
+    }
+
+    /// Read a custom font
+    /// # Arguments
+    /// * `bytes` - 80 bytes of small glyphs, optionally followed by 160 bytes of big glyphs.
+    ///   Without big glyphs the small ones are drawn twice as tall.
+    /// # Returns
+    /// * The font, or an error if there are the wrong number of bytes
+    /// # Example
+    /// ```
+    /// use chip8::chip8_font::Font;
+    /// let font = Font::from_bytes(&[0xF0; 80]).expect("fail");
+    /// assert_eq!(font.big, [0xF0; 160]);
+    /// assert!(Font::from_bytes(&[0; 100]).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FontError> {
+        if bytes.len() != SMALL_FONT_SIZE && bytes.len() != SMALL_FONT_SIZE + BIG_FONT_SIZE {
+            return Err(FontError::InvalidSize(bytes.len()));
+        }
+        let small: [u8; SMALL_FONT_SIZE] =
+            bytes[..SMALL_FONT_SIZE].try_into().expect("length checked");
+        let big = match bytes.get(SMALL_FONT_SIZE..) {
+            Some(big) if !big.is_empty() => big.try_into().expect("length checked"),
+            _ => Self::doubled(&small),
+        };
+        return Ok(Self { small, big });
+    }
+
+    fn doubled(small: &[u8; SMALL_FONT_SIZE]) -> [u8; BIG_FONT_SIZE] {
+        let mut big = [0; BIG_FONT_SIZE];
+        for (i, row) in small.iter().enumerate() {
+            big[i * 2] = *row;
+            big[i * 2 + 1] = *row;
+        }
+        return big;
+    }
+}
+
+impl Chip8 {
+    /// Replace the font at the font address
+    /// # Arguments
+    /// * `font` - The font to write into memory
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// use chip8::chip8_font::{BigFont, Font, SmallFont};
+    /// let mut c = Chip8::new();
+    /// c.set_font(&Font::new(SmallFont::CosmacVip, BigFont::Octo));
+    /// ```
+    pub fn set_font(&mut self, font: &Font) {
+        let start = self.font_address as usize;
+        self.memory[start..start + SMALL_FONT_SIZE].copy_from_slice(&font.small);
+        self.memory[start + SMALL_FONT_SIZE..start + SMALL_FONT_SIZE + BIG_FONT_SIZE]
+            .copy_from_slice(&font.big);
+    }
+
+    /// Move the font to another address
+    /// # Arguments
+    /// * `address` - Where the small font starts, the big font follows it
+    /// # Returns
+    /// * An error if the font would run past the end of memory or past the load address, where
+    ///   it would overwrite the program
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// c.set_font_address(0x50).expect("fail");
+    /// assert_eq!(c.get_font_address(), 0x50);
+    /// assert!(c.set_font_address(0x180).is_err());
+    /// assert!(c.set_font_address(0xF80).is_err());
+    /// ```
+    pub fn set_font_address(&mut self, address: u16) -> Result<(), FontError> {
+        let size = SMALL_FONT_SIZE + BIG_FONT_SIZE;
+        if address as usize + size > self.memory.len() {
+            return Err(FontError::InvalidAddress(address));
+        }
+        if address as usize + size > self.load_address as usize {
+            return Err(FontError::OverlapsProgram(address));
+        }
+        let old = self.font_address as usize;
+        let mut font = [0; SMALL_FONT_SIZE + BIG_FONT_SIZE];
+        font.copy_from_slice(&self.memory[old..old + size]);
+        self.memory[old..old + size].fill(0);
+        self.font_address = address;
+        let new = address as usize;
+        self.memory[new..new + size].copy_from_slice(&font);
+        return Ok(());
+    }
+
+    /// Get the address of the small font
+    pub fn get_font_address(&self) -> u16 {
+        return self.font_address;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(c: &mut Chip8, opcode: u16) {
+        c.pc = 0x200;
+        c.memory[0x200] = (opcode >> 8) as u8;
+        c.memory[0x201] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+    }
+
+    #[test]
+    fn test_default_font_unchanged() {
+        let font = Font::default();
+        assert_eq!(font.small[..], CHIP8_FONTSET[..SMALL_FONT_SIZE]);
+        assert_eq!(font.big[..], CHIP8_FONTSET[SMALL_FONT_SIZE..]);
+    }
+    #[test]
+    fn test_font_sets() {
+        let fonts = [
+            SmallFont::CosmacVip,
+            SmallFont::Dream6800,
+            SmallFont::Eti660,
+            SmallFont::Schip,
+            SmallFont::Octo,
+        ];
+        for (i, a) in fonts.iter().enumerate() {
+            for b in fonts[i + 1..].iter() {
+                assert_ne!(a.bytes(), b.bytes());
+            }
+            // Small glyphs are at most 4 pixels wide
+            assert!(a.bytes().iter().all(|row| row & 0x0F == 0));
+        }
+        assert_ne!(
+            Font::new(SmallFont::Octo, BigFont::Schip).big,
+            Font::new(SmallFont::Octo, BigFont::Octo).big
+        );
+    }
+    #[test]
+    fn test_font_address() {
+        let mut c = Chip8::new();
+        c.set_font(&Font::new(SmallFont::Eti660, BigFont::Schip));
+        c.set_font_address(0x100).expect("fail");
+        assert_eq!(c.memory[..0xF0], [0; 0xF0]);
+        c.v[0] = 0x1;
+        run(&mut c, 0xF029);
+        assert_eq!(c.i, 0x105);
+        assert_eq!(c.memory[0x105], 0x20);
+        run(&mut c, 0xF030);
+        assert_eq!(c.i, 0x100 + 80 + 10);
+        assert_eq!(c.memory[c.i as usize..c.i as usize + 3], [0x18, 0x38, 0x58]);
+        assert_eq!(
+            c.set_font_address(0xFFF),
+            Err(FontError::InvalidAddress(0xFFF))
+        );
+    }
+    #[test]
+    fn test_font_address_keeps_program() {
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[0x12, 0x00]).expect("fail");
+        assert_eq!(
+            c.set_font_address(0x200),
+            Err(FontError::OverlapsProgram(0x200))
+        );
+        assert_eq!(
+            c.set_font_address(0x111),
+            Err(FontError::OverlapsProgram(0x111))
+        );
+        assert_eq!(c.memory[0x200..0x202], [0x12, 0x00]);
+        assert_eq!(c.get_font_address(), 0);
+        c.set_font_address(0x110).expect("fail");
+        // Below an ETI 660 program there is room further up
+        c.set_load_address(crate::ETI_660_LOAD_ADDRESS);
+        c.set_font_address(0x500).expect("fail");
+    }
+    #[test]
+    fn test_custom_font() {
+        let mut bytes = vec![0x80; SMALL_FONT_SIZE];
+        bytes.extend_from_slice(&[0xFF; BIG_FONT_SIZE]);
+        let mut c = Chip8::new();
+        c.set_font(&Font::from_bytes(&bytes).expect("fail"));
+        assert_eq!(c.memory[..SMALL_FONT_SIZE + BIG_FONT_SIZE], bytes[..]);
+        assert_eq!(
+            Font::from_bytes(&bytes[1..]),
+            Err(FontError::InvalidSize(239))
+        );
+    }
+}
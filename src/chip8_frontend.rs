@@ -0,0 +1,359 @@
+use crate::chip8_cheats::{apply_cheats, Cheat};
+use crate::Chip8;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The width of the display in pixels
+pub const SCREEN_WIDTH: usize = 64;
+
+/// The height of the display in pixels
+pub const SCREEN_HEIGHT: usize = 32;
+
+/// Somewhere to show the display
+pub trait DisplaySink {
+    /// Show a new frame
+    /// # Arguments
+    /// * `gfx` - `SCREEN_WIDTH * SCREEN_HEIGHT` pixels, row by row, 0 for off and 1 for on
+    fn draw(&mut self, gfx: &[u8]);
+}
+
+/// Something that can play the buzzer
+pub trait AudioSink {
+    /// Start or stop the buzzer
+    fn set_playing(&mut self, playing: bool);
+}
+
+/// Where key presses come from
+pub trait InputSource {
+    /// Read the keypad once per frame
+    /// # Returns
+    /// * Whether each of the 16 keys is held, or `None` when the user wants to quit
+    fn poll(&mut self) -> Option<[bool; 16]>;
+}
+
+/// Paces the runner at the 60Hz frame rate
+pub trait Clock {
+    /// Wait until the next frame should start
+    fn wait_for_frame(&mut self);
+}
+
+/// A clock that sleeps so frames run in real time
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    frame: Duration,
+    next: Option<Instant>,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        return Self::new(60);
+    }
+}
+
+impl SystemClock {
+    /// Create a clock that ticks `frames_per_second` times a second
+    pub fn new(frames_per_second: u32) -> Self {
+        return Self {
+            frame: Duration::from_secs(1) / frames_per_second,
+            next: None,
+        };
+    }
+}
+
+impl Clock for SystemClock {
+    fn wait_for_frame(&mut self) {
+        let now = Instant::now();
+        let next = self.next.unwrap_or(now);
+        if next > now {
+            std::thread::sleep(next - now);
+            self.next = Some(next + self.frame);
+        } else {
+            // Running behind, so start counting again instead of rushing to catch up
+            self.next = Some(now + self.frame);
+        }
+    }
+}
+
+/// A display that keeps every frame it is sent
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDisplay {
+    pub frames: Vec<Vec<u8>>,
+}
+
+impl DisplaySink for MemoryDisplay {
+    fn draw(&mut self, gfx: &[u8]) {
+        self.frames.push(gfx.to_vec());
+    }
+}
+
+/// A buzzer that remembers when it was started and stopped
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAudio {
+    pub playing: bool,
+    /// Every state the buzzer was set to, in order
+    pub changes: Vec<bool>,
+}
+
+impl AudioSink for MemoryAudio {
+    fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+        self.changes.push(playing);
+    }
+}
+
+/// Input that plays back a fixed list of keypad states, one per frame, then quits
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedInput {
+    frames: VecDeque<[bool; 16]>,
+}
+
+impl ScriptedInput {
+    /// Create input from the keypad state of each frame
+    pub fn new(frames: impl IntoIterator<Item = [bool; 16]>) -> Self {
+        return Self {
+            frames: frames.into_iter().collect(),
+        };
+    }
+
+    /// Create input that runs `count` frames with no keys held
+    pub fn idle(count: usize) -> Self {
+        return Self::new(std::iter::repeat_n([false; 16], count));
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        return self.frames.pop_front();
+    }
+}
+
+/// A clock that never waits, it only counts frames
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    pub frames: u64,
+}
+
+impl Clock for ManualClock {
+    fn wait_for_frame(&mut self) {
+        self.frames += 1;
+    }
+}
+
+/// Drives a `Chip8` against a frontend
+///
+/// Every frame the runner waits for the clock, reads the keypad, runs the instructions per
+/// frame set on the `Chip8`, then sends the display if it changed and the buzzer state if it
+/// changed. With the `vblank` quirk a frame ends after its first draw.
+pub struct Runner<D: DisplaySink, A: AudioSink, I: InputSource, C: Clock> {
+    chip8: Chip8,
+    display: D,
+    audio: A,
+    input: I,
+    clock: C,
+    last_gfx: Option<Vec<u8>>,
+    playing: bool,
+    cheats: Vec<Cheat>,
+}
+
+impl<D: DisplaySink, A: AudioSink, I: InputSource, C: Clock> Runner<D, A, I, C> {
+    /// Create a runner
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// use chip8::chip8_frontend::{ManualClock, MemoryAudio, MemoryDisplay, Runner, ScriptedInput};
+    /// let mut c = Chip8::new();
+    /// c.load_game(vec![0x00, 0xE0, 0x12, 0x00]).expect("fail");
+    /// let mut runner = Runner::new(
+    ///     c,
+    ///     MemoryDisplay::default(),
+    ///     MemoryAudio::default(),
+    ///     ScriptedInput::idle(3),
+    ///     ManualClock::default(),
+    /// );
+    /// runner.run();
+    /// assert_eq!(runner.display().frames.len(), 1);
+    /// ```
+    pub fn new(chip8: Chip8, display: D, audio: A, input: I, clock: C) -> Self {
+        return Self {
+            chip8,
+            display,
+            audio,
+            input,
+            clock,
+            last_gfx: None,
+            playing: false,
+            cheats: Vec::new(),
+        };
+    }
+
+    /// Run one frame
+    /// # Returns
+    /// * `false` when the input source asked to quit, in which case nothing was run
+    pub fn run_frame(&mut self) -> bool {
+        self.clock.wait_for_frame();
+        let keys = match self.input.poll() {
+            Some(keys) => keys,
+            None => return false,
+        };
+        for (key, held) in keys.iter().enumerate() {
+            self.chip8.set_key(key as u8, *held);
+        }
+        apply_cheats(&mut self.chip8, &self.cheats);
+        self.chip8.run_frame();
+        let gfx = self.chip8.get_gfx();
+        if self.last_gfx.as_deref() != Some(gfx) {
+            self.display.draw(gfx);
+            self.last_gfx = Some(gfx.to_vec());
+        }
+        let playing = self.chip8.get_sound_timer() > 0;
+        if playing != self.playing {
+            self.audio.set_playing(playing);
+            self.playing = playing;
+        }
+        return true;
+    }
+
+    /// Run frames until the input source asks to quit
+    pub fn run(&mut self) {
+        while self.run_frame() {}
+        if self.playing {
+            self.audio.set_playing(false);
+            self.playing = false;
+        }
+    }
+
+    /// Freeze bytes of memory at the start of every frame
+    /// # Arguments
+    /// * `cheats` - Such as `CheatFile::for_chip8`, replacing any set before
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) {
+        self.cheats = cheats;
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        return &self.chip8;
+    }
+
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        return &mut self.chip8;
+    }
+
+    pub fn display(&self) -> &D {
+        return &self.display;
+    }
+
+    pub fn audio(&self) -> &A {
+        return &self.audio;
+    }
+
+    pub fn input(&self) -> &I {
+        return &self.input;
+    }
+
+    pub fn clock(&self) -> &C {
+        return &self.clock;
+    }
+
+    /// Take the runner apart again
+    pub fn into_parts(self) -> (Chip8, D, A, I, C) {
+        return (self.chip8, self.display, self.audio, self.input, self.clock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quirks;
+
+    fn runner(
+        game: &[u8],
+        input: ScriptedInput,
+    ) -> Runner<MemoryDisplay, MemoryAudio, ScriptedInput, ManualClock> {
+        let mut c = Chip8::new();
+        c.load_game_from_slice(game).expect("fail");
+        return Runner::new(
+            c,
+            MemoryDisplay::default(),
+            MemoryAudio::default(),
+            input,
+            ManualClock::default(),
+        );
+    }
+
+    #[test]
+    fn test_draws_only_changes() {
+        // LD I, 20A ; DRW V0, V0, 1 ; JP 204 ; JP 206 ; JP 206 ; sprite
+        let game = [
+            0xA2, 0x0A, 0xD0, 0x01, 0x12, 0x06, 0x12, 0x06, 0x12, 0x06, 0x80,
+        ];
+        let mut runner = runner(&game, ScriptedInput::idle(4));
+        runner.chip8_mut().set_instructions_per_frame(1);
+        runner.run();
+        let frames = &runner.display().frames;
+        // The blank screen, then the pixel
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], [0; SCREEN_WIDTH * SCREEN_HEIGHT]);
+        assert_eq!(frames[1][0], 1);
+        assert_eq!(runner.clock().frames, 5);
+    }
+    #[test]
+    fn test_keys_and_audio() {
+        // SKP V0 ; JP 200 ; LD V1, 2 ; LD ST, V1 ; JP 208
+        let game = [0xE0, 0x9E, 0x12, 0x00, 0x61, 0x02, 0xF1, 0x18, 0x12, 0x08];
+        let mut pressed = [false; 16];
+        pressed[0] = true;
+        let input = ScriptedInput::new([[false; 16], pressed, [false; 16], [false; 16]]);
+        let mut runner = runner(&game, input);
+        runner.chip8_mut().set_instructions_per_frame(2);
+        assert!(runner.run_frame());
+        assert_eq!(runner.chip8().pc, 0x200);
+        assert!(!runner.audio().playing);
+        assert!(runner.run_frame());
+        assert!(runner.chip8().get_key(0));
+        assert!(runner.run_frame());
+        assert!(runner.audio().playing);
+        runner.run();
+        let (c, _, audio, _, _) = runner.into_parts();
+        assert_eq!(c.get_sound_timer(), 0);
+        assert_eq!(audio.changes, [true, false]);
+    }
+    #[test]
+    fn test_timers_tick_once_per_frame() {
+        // LD V0, C8 ; LD DT, V0 ; JP 204
+        let game = [0x60, 0xC8, 0xF0, 0x15, 0x12, 0x04];
+        let mut runner = runner(&game, ScriptedInput::idle(61));
+        assert!(runner.run_frame());
+        let before = runner.chip8().get_delay_timer();
+        for _ in 0..60 {
+            assert!(runner.run_frame());
+        }
+        assert_eq!(before - runner.chip8().get_delay_timer(), 60);
+    }
+    #[test]
+    fn test_cheats_apply_each_frame() {
+        // LD I, 300 ; LD V0, [I] ; JP 202
+        let game = [0xA3, 0x00, 0xF0, 0x65, 0x12, 0x02];
+        let mut runner = runner(&game, ScriptedInput::idle(2));
+        runner.set_cheats(vec![Cheat {
+            address: 0x300,
+            value: 7,
+            description: String::new(),
+        }]);
+        runner.run_frame();
+        assert_eq!(runner.chip8().get_registers()[0], 7);
+        runner.chip8_mut().set_memory(0x300, 1);
+        runner.run_frame();
+        assert_eq!(runner.chip8().get_registers()[0], 7);
+    }
+    #[test]
+    fn test_vblank_ends_frame() {
+        // DRW V0, V0, 1 ; DRW V0, V0, 1 ; JP 204
+        let game = [0xD0, 0x01, 0xD0, 0x01, 0x12, 0x04];
+        let mut runner = runner(&game, ScriptedInput::idle(1));
+        runner.chip8_mut().set_quirks(Quirks {
+            vblank: true,
+            ..Quirks::default()
+        });
+        runner.run_frame();
+        assert_eq!(runner.chip8().pc, 0x202);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::chip8_octo::compile;
+use crate::chip8_patch::{apply_patch, PatchError};
+use crate::chip8_rom_database::Colors;
+use crate::{check_game_fits, Chip8, LoadError, Quirks, DEFAULT_LOAD_ADDRESS};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The file formats roms are distributed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    /// Raw program bytes, as in `.ch8` files
+    Binary,
+    /// A GIF exported by Octo with the program source and options hidden in the pixels
+    Octocart,
+    /// Intel HEX records
+    IntelHex,
+    /// Hex bytes written out as text, such as `6E05 6500 6B06`
+    HexText,
+}
+
+/// A decoded rom with whatever settings its container carried
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomImage {
+    pub format: RomFormat,
+    pub bytes: Vec<u8>,
+    /// The address the rom asks to be loaded at
+    pub load_address: Option<u16>,
+    pub quirks: Option<Quirks>,
+    /// Instructions per 60Hz frame
+    pub tickrate: Option<u32>,
+    pub colors: Option<Colors>,
+}
+
+impl RomImage {
+    fn new(format: RomFormat, bytes: Vec<u8>) -> Self {
+        return Self {
+            format,
+            bytes,
+            load_address: None,
+            quirks: None,
+            tickrate: None,
+            colors: None,
+        };
+    }
+
+    /// Apply an IPS or BPS patch to the program bytes, before the rom is loaded
+    /// # Example
+    /// ```
+    /// use chip8::chip8_loader::parse_rom;
+    /// use chip8::chip8_patch::create_ips;
+    /// let mut rom = parse_rom(&[0x60, 0x01]).expect("fail");
+    /// rom.apply_patch(&create_ips(&[0x60, 0x01], &[0x60, 0x02])).expect("fail");
+    /// assert_eq!(rom.bytes, [0x60, 0x02]);
+    /// ```
+    pub fn apply_patch(&mut self, patch: &[u8]) -> Result<(), PatchError> {
+        self.bytes = apply_patch(&self.bytes, patch)?;
+        return Ok(());
+    }
+}
+
+/// The payload of an octocart, as written by Octo
+#[derive(Deserialize)]
+struct Octocart {
+    program: String,
+    #[serde(default)]
+    options: OctoOptions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct OctoOptions {
+    tickrate: Option<u32>,
+    background_color: Option<String>,
+    fill_color: Option<String>,
+    fill_color2: Option<String>,
+    blend_color: Option<String>,
+    buzz_color: Option<String>,
+    quiet_color: Option<String>,
+    shift_quirks: bool,
+    load_store_quirks: bool,
+    clip_quirks: bool,
+    jump_quirks: bool,
+    v_blank_quirks: bool,
+    logic_quirks: bool,
+}
+
+impl OctoOptions {
+    fn quirks(&self) -> Quirks {
+        return Quirks {
+            shift: self.shift_quirks,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            wrap: !self.clip_quirks,
+            jump: self.jump_quirks,
+            vblank: self.v_blank_quirks,
+            logic: Some(self.logic_quirks),
+        };
+    }
+
+    fn colors(&self) -> Colors {
+        let pixels = [
+            &self.background_color,
+            &self.fill_color,
+            &self.fill_color2,
+            &self.blend_color,
+        ];
+        return Colors {
+            pixels: pixels.iter().map_while(|c| c.as_ref().cloned()).collect(),
+            buzzer: self.buzz_color.clone(),
+            silence: self.quiet_color.clone(),
+        };
+    }
+}
+
+fn invalid(message: String) -> LoadError {
+    return LoadError::InvalidFormat(message);
+}
+
+/// Guess the format of a rom file from its contents
+/// # Arguments
+/// * `bytes` - The contents of the file
+/// # Example
+/// ```
+/// use chip8::chip8_loader::{detect_format, RomFormat};
+/// assert_eq!(detect_format(&[0x12, 0x00]), RomFormat::Binary);
+/// assert_eq!(detect_format(b"12 00\n"), RomFormat::HexText);
+/// assert_eq!(detect_format(b":02000000120CE0\n"), RomFormat::IntelHex);
+/// ```
+pub fn detect_format(bytes: &[u8]) -> RomFormat {
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return RomFormat::Octocart;
+    }
+    let is_text = bytes
+        .iter()
+        .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+    if !is_text {
+        return RomFormat::Binary;
+    }
+    let text = std::str::from_utf8(bytes).expect("ascii is valid utf-8");
+    // Text such as an Octo listing can start with ':', so every line has to be a record
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .peekable();
+    if lines.peek().is_some()
+        && lines
+            .enumerate()
+            .all(|(i, l)| parse_record(l, i + 1).is_ok())
+    {
+        return RomFormat::IntelHex;
+    }
+    if parse_hex_text(text).is_ok() {
+        return RomFormat::HexText;
+    }
+    return RomFormat::Binary;
+}
+
+/// Decode a rom file in any supported format
+/// # Arguments
+/// * `bytes` - The contents of the file
+/// # Returns
+/// * The rom, or an error if the file could not be decoded
+pub fn parse_rom(bytes: &[u8]) -> Result<RomImage, LoadError> {
+    return match detect_format(bytes) {
+        RomFormat::Binary => Ok(RomImage::new(RomFormat::Binary, bytes.to_vec())),
+        RomFormat::Octocart => decode_octocart(bytes),
+        RomFormat::IntelHex => {
+            parse_intel_hex(std::str::from_utf8(bytes).expect("detected as text"))
+        }
+        RomFormat::HexText => Ok(RomImage::new(
+            RomFormat::HexText,
+            parse_hex_text(std::str::from_utf8(bytes).expect("detected as text"))?,
+        )),
+    };
+}
+
+/// Read and decode a rom file in any supported format
+/// # Arguments
+/// * `path` - The path to the file
+/// # Returns
+/// * The rom, or an error if the file could not be read or decoded
+/// # Example
+/// ```
+/// use chip8::chip8_loader::{load_rom, RomFormat};
+/// let rom = load_rom("c8_test.c8").expect("fail");
+/// assert_eq!(rom.format, RomFormat::Binary);
+/// ```
+pub fn load_rom(path: &str) -> Result<RomImage, LoadError> {
+    return parse_rom(&std::fs::read(path)?);
+}
+
+/// Decode an Octo cartridge GIF
+///
+/// The low two bits of every pixel's palette index hold the payload, four pixels to a
+/// byte. The payload is a 32 bit big endian length followed by a JSON object with the
+/// Octo source in `program` and the emulator settings in `options`. The source is compiled
+/// with `chip8_octo::compile`.
+/// # Arguments
+/// * `bytes` - The GIF file
+/// # Returns
+/// * The compiled rom with the cartridge's quirks, tick rate and colours
+pub fn decode_octocart(bytes: &[u8]) -> Result<RomImage, LoadError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options
+        .read_info(bytes)
+        .map_err(|e| invalid(format!("octocart: {}", e)))?;
+    let mut pixels = Vec::new();
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|e| invalid(format!("octocart: {}", e)))?
+    {
+        pixels.extend_from_slice(&frame.buffer);
+    }
+    let payload: Vec<u8> = pixels
+        .chunks_exact(4)
+        .map(|p| (p[0] & 3) << 6 | (p[1] & 3) << 4 | (p[2] & 3) << 2 | (p[3] & 3))
+        .collect();
+    if payload.len() < 4 {
+        return Err(invalid(String::from(
+            "octocart: the image holds no program",
+        )));
+    }
+    let size = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    let json = payload
+        .get(4..4 + size)
+        .ok_or_else(|| invalid(String::from("octocart: the program is cut short")))?;
+    // Octo writes one character per byte
+    let json: String = json.iter().map(|b| *b as char).collect();
+    let cart: Octocart =
+        serde_json::from_str(&json).map_err(|e| invalid(format!("octocart: {}", e)))?;
+    let program = compile(&cart.program).map_err(|e| invalid(format!("octocart: {}", e)))?;
+    let mut rom = RomImage::new(RomFormat::Octocart, program);
+    rom.quirks = Some(cart.options.quirks());
+    rom.tickrate = cart.options.tickrate;
+    rom.colors = Some(cart.options.colors());
+    return Ok(rom);
+}
+
+fn parse_hex_byte(text: &str, line: usize) -> Result<u8, LoadError> {
+    return u8::from_str_radix(text, 16)
+        .map_err(|_| invalid(format!("line {}: invalid hex {}", line, text)));
+}
+
+/// Decode one Intel HEX record and check its byte count and checksum
+fn parse_record(line: &str, line_number: usize) -> Result<Vec<u8>, LoadError> {
+    let hex = line
+        .strip_prefix(':')
+        .ok_or_else(|| invalid(format!("line {}: records start with ':'", line_number)))?;
+    if hex.len() % 2 != 0 || !hex.is_ascii() || hex.len() < 10 {
+        return Err(invalid(format!("line {}: malformed record", line_number)));
+    }
+    let record = (0..hex.len())
+        .step_by(2)
+        .map(|i| parse_hex_byte(&hex[i..i + 2], line_number))
+        .collect::<Result<Vec<u8>, LoadError>>()?;
+    if record.len() != record[0] as usize + 5 {
+        return Err(invalid(format!("line {}: wrong byte count", line_number)));
+    }
+    if record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+        return Err(invalid(format!("line {}: bad checksum", line_number)));
+    }
+    return Ok(record);
+}
+
+/// Decode Intel HEX records
+///
+/// Addresses at or above 0x200 are memory addresses and set the load address to the lowest
+/// one. Lower addresses are offsets into the rom. Gaps are filled with zeros.
+/// # Arguments
+/// * `text` - The records
+/// # Example
+/// ```
+/// use chip8::chip8_loader::parse_intel_hex;
+/// let rom = parse_intel_hex(":0402000000E0120008\n:00000001FF\n").expect("fail");
+/// assert_eq!(rom.bytes, [0x00, 0xE0, 0x12, 0x00]);
+/// assert_eq!(rom.load_address, Some(0x200));
+/// ```
+pub fn parse_intel_hex(text: &str) -> Result<RomImage, LoadError> {
+    let mut data = BTreeMap::new();
+    let mut base: u32 = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let record = parse_record(line, line_number)?;
+        let count = record[0] as usize;
+        let address = u16::from_be_bytes([record[1], record[2]]) as u32;
+        let bytes = &record[4..4 + count];
+        match record[3] {
+            0x00 => {
+                for (i, byte) in bytes.iter().enumerate() {
+                    let target = (base + address).checked_add(i as u32).ok_or_else(|| {
+                        invalid(format!("line {}: address past FFFFFFFF", line_number))
+                    })?;
+                    data.insert(target, *byte);
+                }
+            }
+            0x01 => break,
+            0x02 if count == 2 => base = (u16::from_be_bytes([bytes[0], bytes[1]]) as u32) << 4,
+            0x04 if count == 2 => base = (u16::from_be_bytes([bytes[0], bytes[1]]) as u32) << 16,
+            0x03 | 0x05 => {}
+            kind => {
+                return Err(invalid(format!(
+                    "line {}: unsupported record type {:02X}",
+                    line_number, kind
+                )))
+            }
+        }
+    }
+    let (first, last) = match (data.keys().next(), data.keys().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Err(LoadError::Empty),
+    };
+    if last > 0xFFF {
+        return Err(invalid(format!(
+            "address {:X} is past the end of memory",
+            last
+        )));
+    }
+    let mut bytes = vec![0; (last - first + 1) as usize];
+    for (address, byte) in data.iter() {
+        bytes[(address - first) as usize] = *byte;
+    }
+    let mut rom = RomImage::new(RomFormat::IntelHex, bytes);
+    if first >= 0x200 {
+        rom.load_address = Some(first as u16);
+    } else {
+        rom.bytes
+            .splice(0..0, std::iter::repeat_n(0, first as usize));
+    }
+    return Ok(rom);
+}
+
+/// Decode hex bytes written out as text
+///
+/// Bytes are hex digit pairs, alone or grouped like `6E05`, separated by spaces or commas
+/// and optionally prefixed with `0x`. Words ending in `:` are address columns and are
+/// skipped, as is anything after `;`, `#` or `//`.
+/// # Arguments
+/// * `text` - The listing
+/// # Example
+/// ```
+/// use chip8::chip8_loader::parse_hex_text;
+/// let bytes = parse_hex_text("200: 00E0 0x12,0x00 ; loop").expect("fail");
+/// assert_eq!(bytes, [0x00, 0xE0, 0x12, 0x00]);
+/// ```
+pub fn parse_hex_text(text: &str) -> Result<Vec<u8>, LoadError> {
+    let mut bytes = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = [";", "#", "//"]
+            .iter()
+            .filter_map(|c| line.find(c))
+            .min()
+            .map_or(line, |end| &line[..end]);
+        for word in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if word.is_empty() || word.ends_with(':') {
+                continue;
+            }
+            let digits = word
+                .strip_prefix("0x")
+                .or_else(|| word.strip_prefix("0X"))
+                .unwrap_or(word);
+            if digits.is_empty()
+                || digits.len() % 2 != 0
+                || !digits.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(invalid(format!("line {}: invalid hex {}", index + 1, word)));
+            }
+            for i in (0..digits.len()).step_by(2) {
+                bytes.push(parse_hex_byte(&digits[i..i + 2], index + 1)?);
+            }
+        }
+    }
+    if bytes.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    return Ok(bytes);
+}
+
+impl Chip8 {
+    /// Load a rom file in any supported format and apply the settings it carries
+    /// # Arguments
+    /// * `path` - The path to the file
+    /// # Returns
+    /// * The decoded rom, so frontends can use its colours, or an error if it could not be loaded
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// let rom = c.load_rom("c8_test.c8").expect("fail");
+    /// assert!(rom.quirks.is_none());
+    /// ```
+    pub fn load_rom(&mut self, path: &str) -> Result<RomImage, LoadError> {
+        let rom = load_rom(path)?;
+        self.load_rom_image(&rom)?;
+        return Ok(rom);
+    }
+
+    /// Load a decoded rom and apply the settings it carries
+    ///
+    /// Roms that do not say where they load go to `DEFAULT_LOAD_ADDRESS`. Nothing changes when
+    /// the rom does not fit.
+    /// # Arguments
+    /// * `rom` - The rom to load
+    pub fn load_rom_image(&mut self, rom: &RomImage) -> Result<(), LoadError> {
+        let address = rom.load_address.unwrap_or(DEFAULT_LOAD_ADDRESS);
+        check_game_fits(&rom.bytes, address)?;
+        self.set_load_address(address);
+        self.load_game_from_slice(&rom.bytes)?;
+        if let Some(quirks) = rom.quirks {
+            self.set_quirks(quirks);
+        }
+        if let Some(tickrate) = rom.tickrate {
+            self.set_instructions_per_frame(tickrate);
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn octocart(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(json.as_bytes());
+        let mut pixels: Vec<u8> = payload
+            .iter()
+            .flat_map(|b| [b >> 6, (b >> 4) & 3, (b >> 2) & 3, b & 3])
+            // The label drawn over the data uses the upper palette bits
+            .map(|p| p | 4)
+            .collect();
+        let width = 32;
+        pixels.resize(pixels.len().div_ceil(width) * width, 0);
+        let height = (pixels.len() / width) as u16;
+        let palette: Vec<u8> = (0..8).flat_map(|i| [i * 30, i * 30, i * 30]).collect();
+        let mut gif = Vec::new();
+        {
+            let mut encoder =
+                gif::Encoder::new(&mut gif, width as u16, height, &palette).expect("fail");
+            let frame = gif::Frame::from_indexed_pixels(width as u16, height, pixels, None);
+            encoder.write_frame(&frame).expect("fail");
+        }
+        return gif;
+    }
+
+    #[test]
+    fn test_octocart() {
+        let json = r##"{
+            "program": ": main\n  v0 := 5\n  loop again",
+            "options": {
+                "tickrate": 20,
+                "backgroundColor": "#996600",
+                "fillColor": "#FFCC00",
+                "fillColor2": "#FF6600",
+                "blendColor": "#662200",
+                "buzzColor": "#FFAA00",
+                "quietColor": "#000000",
+                "shiftQuirks": true,
+                "loadStoreQuirks": false,
+                "clipQuirks": true,
+                "vBlankQuirks": true
+            }
+        }"##;
+        let cart = octocart(json);
+        assert_eq!(detect_format(&cart), RomFormat::Octocart);
+        let rom = parse_rom(&cart).expect("fail");
+        assert_eq!(rom.format, RomFormat::Octocart);
+        assert_eq!(
+            rom.bytes,
+            compile(": main\n  v0 := 5\n  loop again").expect("fail")
+        );
+        assert_eq!(rom.tickrate, Some(20));
+        assert_eq!(
+            rom.quirks,
+            Some(Quirks {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: Some(false),
+            })
+        );
+        let colors = rom.colors.expect("fail");
+        assert_eq!(colors.pixels, ["#996600", "#FFCC00", "#FF6600", "#662200"]);
+        assert_eq!(colors.buzzer.as_deref(), Some("#FFAA00"));
+    }
+    #[test]
+    fn test_octocart_errors() {
+        assert!(matches!(
+            decode_octocart(b"GIF89a"),
+            Err(LoadError::InvalidFormat(_))
+        ));
+        let cart = octocart(r#"{"program": ": main\n  frobnicate"}"#);
+        assert!(matches!(parse_rom(&cart), Err(LoadError::InvalidFormat(_))));
+    }
+    #[test]
+    fn test_intel_hex() {
+        let text = ":020000040000FA\n:04020000A20A6005E9\n:02020600120CD8\n:00000001FF\n";
+        let rom = parse_rom(text.as_bytes()).expect("fail");
+        assert_eq!(rom.format, RomFormat::IntelHex);
+        assert_eq!(rom.load_address, Some(0x200));
+        assert_eq!(rom.bytes, [0xA2, 0x0A, 0x60, 0x05, 0x00, 0x00, 0x12, 0x0C]);
+
+        let rom = parse_intel_hex(":0200020012FEEC\n").expect("fail");
+        assert_eq!(rom.load_address, None);
+        assert_eq!(rom.bytes, [0x00, 0x00, 0x12, 0xFE]);
+
+        assert!(parse_intel_hex(":04020000A20A6005EA\n").is_err());
+        // Text that only starts like a record is not Intel HEX
+        assert_eq!(
+            detect_format(b":02000000120CE0\nhello\n"),
+            RomFormat::Binary
+        );
+        assert_eq!(detect_format(b": main\n  clear\n"), RomFormat::Binary);
+        assert_eq!(detect_format(b":04020000A20A6005EA\n"), RomFormat::Binary);
+        assert_eq!(
+            detect_format(b"\n:020000040000FA\n\n:00000001FF\n"),
+            RomFormat::IntelHex
+        );
+        assert!(parse_intel_hex(":0302000012FE\n").is_err());
+        assert!(matches!(
+            parse_intel_hex(":00000001FF\n"),
+            Err(LoadError::Empty)
+        ));
+        // The last byte of this record would be past the 32 bit address space
+        assert!(matches!(
+            parse_intel_hex(":02000004FFFFFC\n:02FFFF00AABB9B\n:00000001FF"),
+            Err(LoadError::InvalidFormat(_))
+        ));
+    }
+    #[test]
+    fn test_hex_text() {
+        let game = std::fs::read("c8_test.c8").expect("Failed to load game");
+        let mut text = String::from("# c8_test\n");
+        for (i, word) in game.chunks(2).enumerate() {
+            text += &format!("{:X}: ", 0x200 + i * 2);
+            text += &word
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<String>();
+            text += "\n";
+        }
+        assert_eq!(detect_format(text.as_bytes()), RomFormat::HexText);
+        assert_eq!(parse_rom(text.as_bytes()).expect("fail").bytes, game);
+        assert!(parse_hex_text("12 0").is_err());
+        assert!(parse_hex_text("CLS").is_err());
+        assert_eq!(detect_format(b"CLS\n"), RomFormat::Binary);
+    }
+    #[test]
+    fn test_load_rom() {
+        let path = std::env::temp_dir().join("chip8_loader_test.hex");
+        std::fs::write(&path, ":02060000600494\n:00000001FF\n").expect("fail");
+        let mut c = Chip8::new();
+        c.load_rom(path.to_str().expect("fail")).expect("fail");
+        std::fs::remove_file(&path).expect("fail");
+        assert_eq!(c.get_load_address(), 0x600);
+        c.emulate_cycle();
+        assert_eq!(c.v[0], 4);
+    }
+    #[test]
+    fn test_load_rom_image_address() {
+        let mut c = Chip8::new();
+        let mut rom = RomImage::new(RomFormat::IntelHex, vec![0x60, 0x04]);
+        rom.load_address = Some(0x600);
+        c.load_rom_image(&rom).expect("fail");
+        // A rom that does not fit leaves the machine as it was
+        let mut large = RomImage::new(RomFormat::IntelHex, vec![0; 0x100]);
+        large.load_address = Some(0xF80);
+        assert!(matches!(
+            c.load_rom_image(&large),
+            Err(LoadError::TooLarge { .. })
+        ));
+        assert_eq!(c.get_load_address(), 0x600);
+        assert_eq!(c.get_pc(), 0x600);
+        // The address of a previous rom does not carry over to a binary one
+        c.load_rom_image(&RomImage::new(RomFormat::Binary, vec![0x61, 0x05]))
+            .expect("fail");
+        assert_eq!(c.get_load_address(), 0x200);
+        c.emulate_cycle();
+        assert_eq!(c.v[1], 5);
+    }
+}
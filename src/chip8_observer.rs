@@ -0,0 +1,192 @@
+/// A register an instruction can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    /// V0 to VF
+    V(u8),
+    /// The index register I
+    I,
+}
+
+/// One of the two 60Hz timers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Timer {
+    Delay,
+    Sound,
+}
+
+/// Sees what the interpreter does, for debuggers, profilers, coverage, cheats and achievements
+///
+/// Pass an observer to `Chip8::emulate_cycle_with` or `Chip8::run_frame_with`. Every callback
+/// does nothing by default, and `emulate_cycle` runs with `NoObserver`, whose empty callbacks
+/// compile away. The `pc` given to a callback is the address of the instruction responsible.
+/// # Example
+/// ```
+/// use chip8::chip8_observer::Observer;
+/// use chip8::Chip8;
+///
+/// #[derive(Default)]
+/// struct Coverage {
+///     executed: Vec<u16>,
+/// }
+///
+/// impl Observer for Coverage {
+///     fn execute(&mut self, pc: u16, _opcode: u16) {
+///         self.executed.push(pc);
+///     }
+/// }
+///
+/// let mut c = Chip8::new();
+/// c.load_game_from_slice(&[0x60, 0x01, 0x12, 0x00]).expect("fail");
+/// let mut coverage = Coverage::default();
+/// c.emulate_cycle_with(&mut coverage);
+/// c.emulate_cycle_with(&mut coverage);
+/// assert_eq!(coverage.executed, [0x200, 0x202]);
+/// ```
+#[allow(unused_variables)]
+pub trait Observer {
+    /// An instruction was fetched and is about to run
+    fn fetch(&mut self, pc: u16, opcode: u16) {}
+    /// An instruction finished running, after all of its other callbacks
+    fn execute(&mut self, pc: u16, opcode: u16) {}
+    /// A byte of memory was read as data, by DXYN or FX65
+    fn memory_read(&mut self, pc: u16, address: u16, value: u8) {}
+    /// A byte of memory was written, by FX33 or FX55
+    fn memory_write(&mut self, pc: u16, address: u16, value: u8) {}
+    /// A register was written, including VF when an instruction sets the flag
+    fn register_write(&mut self, pc: u16, register: Register, value: u16) {}
+    /// DXYN drew a sprite
+    /// # Arguments
+    /// * `x`, `y` - Where the sprite was drawn, before clipping or wrapping
+    /// * `address` - Where the sprite data was read from
+    /// * `height` - The number of rows
+    /// * `collision` - Whether a lit pixel was turned off
+    fn sprite_draw(&mut self, pc: u16, x: u8, y: u8, address: u16, height: u8, collision: bool) {}
+    /// FX15 or FX18 set a timer
+    fn timer_set(&mut self, pc: u16, timer: Timer, value: u8) {}
+    /// EX9E, EXA1 or FX0A looked at a key
+    fn key_query(&mut self, pc: u16, key: u8, pressed: bool) {}
+}
+
+/// The observer that ignores everything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Fetch(u16, u16),
+        Execute(u16),
+        Read(u16, u16, u8),
+        Write(u16, u16, u8),
+        Register(u16, Register, u16),
+        Draw(u16, u8, u8, u16, u8, bool),
+        Timer(u16, Timer, u8),
+        Key(u16, u8, bool),
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<Event>,
+    }
+
+    impl Observer for Recorder {
+        fn fetch(&mut self, pc: u16, opcode: u16) {
+            self.events.push(Event::Fetch(pc, opcode));
+        }
+        fn execute(&mut self, pc: u16, _opcode: u16) {
+            self.events.push(Event::Execute(pc));
+        }
+        fn memory_read(&mut self, pc: u16, address: u16, value: u8) {
+            self.events.push(Event::Read(pc, address, value));
+        }
+        fn memory_write(&mut self, pc: u16, address: u16, value: u8) {
+            self.events.push(Event::Write(pc, address, value));
+        }
+        fn register_write(&mut self, pc: u16, register: Register, value: u16) {
+            self.events.push(Event::Register(pc, register, value));
+        }
+        fn sprite_draw(
+            &mut self,
+            pc: u16,
+            x: u8,
+            y: u8,
+            address: u16,
+            height: u8,
+            collision: bool,
+        ) {
+            self.events
+                .push(Event::Draw(pc, x, y, address, height, collision));
+        }
+        fn timer_set(&mut self, pc: u16, timer: Timer, value: u8) {
+            self.events.push(Event::Timer(pc, timer, value));
+        }
+        fn key_query(&mut self, pc: u16, key: u8, pressed: bool) {
+            self.events.push(Event::Key(pc, key, pressed));
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        // LD V0, 3 ; LD I, 300 ; LD [I], V0 ; DRW V0, V0, 1 ; SKP V0 ; LD ST, V0
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[
+            0x60, 0x03, 0xA3, 0x00, 0xF0, 0x55, 0xD0, 0x01, 0xE0, 0x9E, 0xF0, 0x18,
+        ])
+        .expect("fail");
+        c.set_key(3, true);
+        let mut recorder = Recorder::default();
+        for _ in 0..5 {
+            c.emulate_cycle_with(&mut recorder);
+        }
+        let events: Vec<Event> = recorder
+            .events
+            .into_iter()
+            .filter(|e| !matches!(e, Event::Fetch(..) | Event::Execute(..)))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                Event::Register(0x200, Register::V(0), 3),
+                Event::Register(0x202, Register::I, 0x300),
+                Event::Write(0x204, 0x300, 3),
+                Event::Read(0x206, 0x300, 3),
+                Event::Register(0x206, Register::V(0xF), 0),
+                Event::Draw(0x206, 3, 3, 0x300, 1, false),
+                Event::Key(0x208, 3, true),
+            ]
+        );
+        assert_eq!(c.get_pc(), 0x20C);
+    }
+    #[test]
+    fn test_fetch_and_execute() {
+        // LD V0, 3 ; LD ST, V0 ; JP 202
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x02])
+            .expect("fail");
+        let mut recorder = Recorder::default();
+        c.set_instructions_per_frame(3);
+        c.run_frame_with(&mut recorder);
+        assert_eq!(
+            recorder.events,
+            [
+                Event::Fetch(0x200, 0x6003),
+                Event::Register(0x200, Register::V(0), 3),
+                Event::Execute(0x200),
+                Event::Fetch(0x202, 0xF018),
+                Event::Timer(0x202, Timer::Sound, 3),
+                Event::Execute(0x202),
+                Event::Fetch(0x204, 0x1202),
+                Event::Execute(0x204),
+            ]
+        );
+        // A trait object works as well
+        let observer: &mut dyn Observer = &mut Recorder::default();
+        c.emulate_cycle_with(observer);
+    }
+}
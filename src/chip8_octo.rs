@@ -0,0 +1,948 @@
+use crate::chip8_assembler::AssemblerError;
+use crate::chip8_disassembler::chip8_disassembler::PROGRAM_START;
+use std::collections::HashMap;
+
+/// The most macro expansions a program may perform, to stop runaway recursive macros
+const MAX_MACRO_EXPANSIONS: usize = 10000;
+
+/// A whitespace separated word of Octo source and where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+/// A comparison in an `if`, `while` or `begin` condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    Key,
+    NotKey,
+}
+
+impl Comparison {
+    fn negate(self) -> Comparison {
+        return match self {
+            Comparison::Equal => Comparison::NotEqual,
+            Comparison::NotEqual => Comparison::Equal,
+            Comparison::Less => Comparison::GreaterOrEqual,
+            Comparison::GreaterOrEqual => Comparison::Less,
+            Comparison::Greater => Comparison::LessOrEqual,
+            Comparison::LessOrEqual => Comparison::Greater,
+            Comparison::Key => Comparison::NotKey,
+            Comparison::NotKey => Comparison::Key,
+        };
+    }
+}
+
+/// The right hand side of a condition
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Number(u8),
+    None,
+}
+
+/// An open control flow block, waiting for its `else`, `end` or `again`
+enum Block {
+    If { jump: usize },
+    Else { jump: usize },
+    Loop { start: u16, whiles: Vec<usize> },
+}
+
+/// An address operand that refers to a label that was not defined yet
+struct Fixup {
+    address: usize,
+    name: Token,
+}
+
+struct Macro {
+    arguments: Vec<String>,
+    body: Vec<Token>,
+}
+
+struct Compiler {
+    tokens: Vec<Token>,
+    position: usize,
+    memory: Vec<u8>,
+    here: usize,
+    end: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<(Block, Token)>,
+    expansions: usize,
+}
+
+fn error(token: &Token, message: String) -> AssemblerError {
+    return AssemblerError {
+        line: token.line,
+        column: token.column,
+        message,
+    };
+}
+
+/// Split source into tokens, dropping `#` comments
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut start = None;
+        for (column, c) in line.char_indices().chain([(line.len(), ' ')]) {
+            match (start, c.is_whitespace()) {
+                (None, false) => start = Some(column),
+                (Some(s), true) => {
+                    tokens.push(Token {
+                        text: line[s..column].to_string(),
+                        line: number + 1,
+                        column: s + 1,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    return tokens;
+}
+
+/// Parse a numeric literal, `0x` for hex, `0b` for binary, decimal otherwise, with an optional `-`
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let lower = digits.to_ascii_lowercase();
+    let value = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse().ok()?
+    } else {
+        return None;
+    };
+    return Some(if negative { -value } else { value });
+}
+
+impl Compiler {
+    fn new(source: &str) -> Self {
+        return Self {
+            tokens: tokenize(source),
+            position: 0,
+            memory: vec![0; 0x1000],
+            here: PROGRAM_START as usize,
+            end: PROGRAM_START as usize,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            expansions: 0,
+        };
+    }
+
+    fn next(&mut self) -> Result<Token, AssemblerError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                return Ok(token.clone());
+            }
+            None => {
+                let last = self.tokens.last().cloned().unwrap_or(Token {
+                    text: String::new(),
+                    line: 1,
+                    column: 1,
+                });
+                return Err(error(&last, String::from("Unexpected end of file")));
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        return self.tokens.get(self.position).map(|t| t.text.as_str());
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AssemblerError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(error(
+                &token,
+                format!("Expected {} but found {}", text, token.text),
+            ));
+        }
+        return Ok(token);
+    }
+
+    fn emit(&mut self, token: &Token, byte: u8) -> Result<(), AssemblerError> {
+        if self.here >= self.memory.len() {
+            return Err(error(token, String::from("Program does not fit in memory")));
+        }
+        self.memory[self.here] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        return Ok(());
+    }
+
+    fn emit_op(&mut self, token: &Token, code: u16) -> Result<(), AssemblerError> {
+        self.emit(token, (code >> 8) as u8)?;
+        return self.emit(token, code as u8);
+    }
+
+    fn register_of(&self, text: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(text) {
+            return Some(*register);
+        }
+        let lower = text.to_ascii_lowercase();
+        if lower.len() == 2 && lower.starts_with('v') {
+            return u8::from_str_radix(&lower[1..], 16).ok();
+        }
+        return None;
+    }
+
+    fn register(&mut self) -> Result<u8, AssemblerError> {
+        let token = self.next()?;
+        return match self.register_of(&token.text) {
+            Some(register) => Ok(register),
+            None => Err(error(
+                &token,
+                format!("Expected a register but found {}", token.text),
+            )),
+        };
+    }
+
+    /// Read a number or constant that has to be known now
+    fn value(&mut self, min: i64, max: i64) -> Result<i64, AssemblerError> {
+        let token = self.next()?;
+        let value = match parse_number(&token.text) {
+            Some(value) => value,
+            None => match self.constants.get(&token.text) {
+                Some(value) => *value,
+                None => match self.labels.get(&token.text) {
+                    Some(address) => *address as i64,
+                    None => return Err(error(&token, format!("Undefined name {}", token.text))),
+                },
+            },
+        };
+        if value < min || value > max {
+            return Err(error(
+                &token,
+                format!("Value {} is out of range {}..={}", value, min, max),
+            ));
+        }
+        return Ok(value);
+    }
+
+    fn byte(&mut self) -> Result<u8, AssemblerError> {
+        return Ok(self.value(-128, 255)? as u8);
+    }
+
+    /// Emit an instruction with a 12 bit address, which may refer to a later label
+    fn emit_address(&mut self, token: &Token, code: u16) -> Result<(), AssemblerError> {
+        let target = self.next()?;
+        let address = match parse_number(&target.text) {
+            Some(address) => Some(address),
+            None => match self.constants.get(&target.text) {
+                Some(address) => Some(*address),
+                None => self.labels.get(&target.text).map(|a| *a as i64),
+            },
+        };
+        match address {
+            Some(address) if (0..=0xFFF).contains(&address) => {
+                return self.emit_op(token, code | address as u16);
+            }
+            Some(address) => {
+                return Err(error(
+                    &target,
+                    format!("Address {} is out of range", address),
+                ))
+            }
+            None => {
+                self.fixups.push(Fixup {
+                    address: self.here,
+                    name: target,
+                });
+                return self.emit_op(token, code);
+            }
+        }
+    }
+
+    fn define_label(&mut self, name: Token) -> Result<(), AssemblerError> {
+        if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) {
+            return Err(error(&name, format!("Duplicate name {}", name.text)));
+        }
+        self.labels.insert(name.text, self.here as u16);
+        return Ok(());
+    }
+
+    /// Read a `{ ... }` group, keeping nested braces
+    fn braces(&mut self) -> Result<Vec<Token>, AssemblerError> {
+        self.expect("{")?;
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    /// Evaluate a `:calc` expression. As in Octo, operators have no precedence and are
+    /// evaluated right to left unless grouped with parentheses.
+    fn calc(&self, tokens: &[Token], position: &mut usize) -> Result<f64, AssemblerError> {
+        let token = match tokens.get(*position) {
+            Some(token) => token.clone(),
+            None => {
+                let last = tokens.last().or(self.tokens.get(self.position - 1));
+                return Err(error(
+                    last.expect("calc follows a token"),
+                    String::from("Missing term"),
+                ));
+            }
+        };
+        *position += 1;
+        let term = match token.text.as_str() {
+            "(" => {
+                let value = self.calc(tokens, position)?;
+                match tokens.get(*position) {
+                    Some(close) if close.text == ")" => *position += 1,
+                    _ => return Err(error(&token, String::from("Missing )"))),
+                }
+                value
+            }
+            "-" => -self.calc(tokens, position)?,
+            "~" => !(self.calc(tokens, position)? as i64) as f64,
+            "HERE" => self.here as f64,
+            text => match parse_number(text) {
+                Some(n) => n as f64,
+                None => match (self.constants.get(text), self.labels.get(text)) {
+                    (Some(value), _) => *value as f64,
+                    (None, Some(address)) => *address as f64,
+                    _ => return Err(error(&token, format!("Undefined name {}", text))),
+                },
+            },
+        };
+        let operator = match tokens.get(*position) {
+            Some(operator) if operator.text != ")" => operator.clone(),
+            _ => return Ok(term),
+        };
+        *position += 1;
+        let rest = self.calc(tokens, position)?;
+        let (a, b) = (term as i64, rest as i64);
+        return Ok(match operator.text.as_str() {
+            "+" => term + rest,
+            "-" => term - rest,
+            "*" => term * rest,
+            "/" => term / rest,
+            "%" => (a % b) as f64,
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => (a << b) as f64,
+            ">>" => (a >> b) as f64,
+            _ => {
+                return Err(error(
+                    &operator,
+                    format!("Unknown operator {}", operator.text),
+                ))
+            }
+        });
+    }
+
+    /// Read a condition such as `v1 == 3`, `v2 != v3` or `v0 key`
+    fn condition(&mut self) -> Result<(u8, Comparison, Operand), AssemblerError> {
+        let x = self.register()?;
+        let token = self.next()?;
+        let comparison = match token.text.as_str() {
+            "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            ">" => Comparison::Greater,
+            "<=" => Comparison::LessOrEqual,
+            ">=" => Comparison::GreaterOrEqual,
+            "key" => return Ok((x, Comparison::Key, Operand::None)),
+            "-key" => return Ok((x, Comparison::NotKey, Operand::None)),
+            _ => return Err(error(&token, format!("Unknown comparison {}", token.text))),
+        };
+        let operand = match self.peek().and_then(|text| self.register_of(text)) {
+            Some(y) => {
+                self.position += 1;
+                Operand::Register(y)
+            }
+            None => Operand::Number(self.byte()?),
+        };
+        return Ok((x, comparison, operand));
+    }
+
+    /// Emit the instructions that skip the next instruction when the condition is false
+    fn skip_unless(
+        &mut self,
+        token: &Token,
+        (x, comparison, operand): (u8, Comparison, Operand),
+    ) -> Result<(), AssemblerError> {
+        let x = x as u16;
+        let code = match (comparison, operand) {
+            (Comparison::Key, _) => 0xE0A1 | x << 8,
+            (Comparison::NotKey, _) => 0xE09E | x << 8,
+            (Comparison::Equal, Operand::Number(n)) => 0x4000 | x << 8 | n as u16,
+            (Comparison::NotEqual, Operand::Number(n)) => 0x3000 | x << 8 | n as u16,
+            (Comparison::Equal, Operand::Register(y)) => 0x9000 | x << 8 | (y as u16) << 4,
+            (Comparison::NotEqual, Operand::Register(y)) => 0x5000 | x << 8 | (y as u16) << 4,
+            (_, Operand::None) => unreachable!("only key conditions have no operand"),
+            (comparison, operand) => {
+                // Ordered comparisons go through VF, which ends up 1 when there was no borrow
+                let no_borrow_means_true = match operand {
+                    Operand::Register(y) => {
+                        let (a, b) = match comparison {
+                            Comparison::Less | Comparison::GreaterOrEqual => (x, y as u16),
+                            _ => (y as u16, x),
+                        };
+                        self.emit_op(token, 0x8F00 | a << 4)?;
+                        self.emit_op(token, 0x8F05 | b << 4)?;
+                        matches!(
+                            comparison,
+                            Comparison::GreaterOrEqual | Comparison::LessOrEqual
+                        )
+                    }
+                    _ => {
+                        let n = match operand {
+                            Operand::Number(n) => n as u16,
+                            _ => 0,
+                        };
+                        self.emit_op(token, 0x6F00 | n)?;
+                        match comparison {
+                            Comparison::Less | Comparison::GreaterOrEqual => {
+                                self.emit_op(token, 0x8F07 | x << 4)?
+                            }
+                            _ => self.emit_op(token, 0x8F05 | x << 4)?,
+                        }
+                        matches!(
+                            comparison,
+                            Comparison::GreaterOrEqual | Comparison::LessOrEqual
+                        )
+                    }
+                };
+                if no_borrow_means_true {
+                    0x3F00
+                } else {
+                    0x3F01
+                }
+            }
+        };
+        return self.emit_op(token, code);
+    }
+
+    /// Emit a placeholder `jump` and return its address so it can be patched later
+    fn forward_jump(&mut self, token: &Token) -> Result<usize, AssemblerError> {
+        let address = self.here;
+        self.emit_op(token, 0x1000)?;
+        return Ok(address);
+    }
+
+    fn patch_jump(&mut self, address: usize, target: usize) {
+        self.memory[address] = 0x10 | (target >> 8) as u8;
+        self.memory[address + 1] = target as u8;
+    }
+
+    fn statement(&mut self) -> Result<(), AssemblerError> {
+        let token = self.next()?;
+        if let Some(x) = self.register_of(&token.text) {
+            return self.register_statement(&token, x as u16);
+        }
+        if let Some(value) = parse_number(&token.text) {
+            if !(-128..=255).contains(&value) {
+                return Err(error(&token, format!("Byte {} is out of range", value)));
+            }
+            return self.emit(&token, value as u8);
+        }
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                return self.define_label(name);
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value(i64::MIN, i64::MAX)?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                let body = self.braces()?;
+                let value = self.calc(&body, &mut 0)?;
+                self.constants.insert(name.text, value.floor() as i64);
+            }
+            ":org" => {
+                let address = self.value(0, 0xFFF)?;
+                self.here = address as usize;
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    let body = self.braces()?;
+                    self.calc(&body, &mut 0)? as i64 as u8
+                } else {
+                    self.byte()?
+                };
+                self.emit(&token, value)?;
+            }
+            ":call" => self.emit_address(&token, 0x2000)?,
+            ":macro" => {
+                let name = self.next()?;
+                let mut arguments = Vec::new();
+                while self.peek().is_some_and(|text| text != "{") {
+                    arguments.push(self.next()?.text);
+                }
+                let body = self.braces()?;
+                self.macros.insert(name.text, Macro { arguments, body });
+            }
+            "clear" => self.emit_op(&token, 0x00E0)?,
+            "return" | ";" => self.emit_op(&token, 0x00EE)?,
+            "exit" => self.emit_op(&token, 0x00FD)?,
+            "lores" => self.emit_op(&token, 0x00FE)?,
+            "hires" => self.emit_op(&token, 0x00FF)?,
+            "scroll-right" => self.emit_op(&token, 0x00FB)?,
+            "scroll-left" => self.emit_op(&token, 0x00FC)?,
+            "scroll-down" => {
+                let n = self.value(0, 15)? as u16;
+                self.emit_op(&token, 0x00C0 | n)?;
+            }
+            "jump" => self.emit_address(&token, 0x1000)?,
+            "jump0" => self.emit_address(&token, 0xB000)?,
+            "native" => self.emit_address(&token, 0x0000)?,
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.value(0, 15)? as u16;
+                self.emit_op(&token, 0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()? as u16;
+                let code = if token.text == "delay" {
+                    0xF015
+                } else {
+                    0xF018
+                };
+                self.emit_op(&token, code | x << 8)?;
+            }
+            "bcd" | "save" | "load" | "saveflags" | "loadflags" => {
+                let x = self.register()? as u16;
+                let code = match token.text.as_str() {
+                    "bcd" => 0xF033,
+                    "save" => 0xF055,
+                    "load" => 0xF065,
+                    "saveflags" => 0xF075,
+                    _ => 0xF085,
+                };
+                self.emit_op(&token, code | x << 8)?;
+            }
+            "i" => {
+                let operator = self.next()?;
+                match operator.text.as_str() {
+                    ":=" => match self.peek() {
+                        Some("hex") | Some("bighex") => {
+                            let code = if self.next()?.text == "hex" {
+                                0xF029
+                            } else {
+                                0xF030
+                            };
+                            let x = self.register()? as u16;
+                            self.emit_op(&token, code | x << 8)?;
+                        }
+                        _ => self.emit_address(&token, 0xA000)?,
+                    },
+                    "+=" => {
+                        let x = self.register()? as u16;
+                        self.emit_op(&token, 0xF01E | x << 8)?;
+                    }
+                    _ => {
+                        return Err(error(
+                            &operator,
+                            format!("Unknown operator {}", operator.text),
+                        ))
+                    }
+                }
+            }
+            "if" => {
+                let condition = self.condition()?;
+                let keyword = self.next()?;
+                match keyword.text.as_str() {
+                    "then" => self.skip_unless(&token, condition)?,
+                    "begin" => {
+                        let (x, comparison, operand) = condition;
+                        self.skip_unless(&token, (x, comparison.negate(), operand))?;
+                        let jump = self.forward_jump(&token)?;
+                        self.blocks.push((Block::If { jump }, token));
+                    }
+                    _ => {
+                        return Err(error(
+                            &keyword,
+                            format!("Expected then or begin but found {}", keyword.text),
+                        ))
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some((Block::If { jump }, _)) => {
+                    let end = self.forward_jump(&token)?;
+                    self.patch_jump(jump, self.here);
+                    self.blocks.push((Block::Else { jump: end }, token));
+                }
+                _ => return Err(error(&token, String::from("else without if ... begin"))),
+            },
+            "end" => match self.blocks.pop() {
+                Some((Block::If { jump }, _)) | Some((Block::Else { jump }, _)) => {
+                    self.patch_jump(jump, self.here);
+                }
+                _ => return Err(error(&token, String::from("end without begin"))),
+            },
+            "loop" => {
+                let start = self.here as u16;
+                self.blocks.push((
+                    Block::Loop {
+                        start,
+                        whiles: Vec::new(),
+                    },
+                    token,
+                ));
+            }
+            "while" => {
+                let (x, comparison, operand) = self.condition()?;
+                self.skip_unless(&token, (x, comparison.negate(), operand))?;
+                let jump = self.forward_jump(&token)?;
+                match self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find_map(|(block, _)| match block {
+                        Block::Loop { whiles, .. } => Some(whiles),
+                        _ => None,
+                    }) {
+                    Some(whiles) => whiles.push(jump),
+                    None => return Err(error(&token, String::from("while outside of loop"))),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some((Block::Loop { start, whiles }, _)) => {
+                    self.emit_op(&token, 0x1000 | start)?;
+                    for jump in whiles {
+                        self.patch_jump(jump, self.here);
+                    }
+                }
+                _ => return Err(error(&token, String::from("again without loop"))),
+            },
+            name if self.macros.contains_key(name) => self.expand(&token)?,
+            name if self.constants.contains_key(name) => {
+                let value = self.constants[name];
+                self.emit(&token, value as u8)?;
+            }
+            _ => {
+                // Any other name is a call to a subroutine
+                self.position -= 1;
+                self.emit_address(&token, 0x2000)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Replace a macro invocation with the macro body, substituting its arguments
+    fn expand(&mut self, token: &Token) -> Result<(), AssemblerError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(error(token, String::from("Too many macro expansions")));
+        }
+        let count = self.macros[&token.text].arguments.len();
+        let mut values = HashMap::new();
+        for i in 0..count {
+            let value = self.next()?;
+            values.insert(self.macros[&token.text].arguments[i].clone(), value.text);
+        }
+        let body: Vec<Token> = self.macros[&token.text]
+            .body
+            .iter()
+            .map(|t| Token {
+                text: values.get(&t.text).cloned().unwrap_or(t.text.clone()),
+                ..t.clone()
+            })
+            .collect();
+        self.tokens.splice(self.position..self.position, body);
+        return Ok(());
+    }
+
+    fn register_statement(&mut self, token: &Token, x: u16) -> Result<(), AssemblerError> {
+        let operator = self.next()?;
+        let y = self.peek().and_then(|text| self.register_of(text));
+        if let Some(y) = y {
+            self.position += 1;
+            let code = match operator.text.as_str() {
+                ":=" => 0x8000,
+                "|=" => 0x8001,
+                "&=" => 0x8002,
+                "^=" => 0x8003,
+                "+=" => 0x8004,
+                "-=" => 0x8005,
+                ">>=" => 0x8006,
+                "=-" => 0x8007,
+                "<<=" => 0x800E,
+                _ => {
+                    return Err(error(
+                        &operator,
+                        format!("Unknown operator {}", operator.text),
+                    ))
+                }
+            };
+            return self.emit_op(token, code | x << 8 | (y as u16) << 4);
+        }
+        match operator.text.as_str() {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.position += 1;
+                    let mask = self.byte()? as u16;
+                    self.emit_op(token, 0xC000 | x << 8 | mask)?;
+                }
+                Some("delay") => {
+                    self.position += 1;
+                    self.emit_op(token, 0xF007 | x << 8)?;
+                }
+                Some("key") => {
+                    self.position += 1;
+                    self.emit_op(token, 0xF00A | x << 8)?;
+                }
+                _ => {
+                    let n = self.byte()? as u16;
+                    self.emit_op(token, 0x6000 | x << 8 | n)?;
+                }
+            },
+            "+=" => {
+                let n = self.byte()? as u16;
+                self.emit_op(token, 0x7000 | x << 8 | n)?;
+            }
+            "-=" => {
+                let n = self.byte()?.wrapping_neg() as u16;
+                self.emit_op(token, 0x7000 | x << 8 | n)?;
+            }
+            _ => {
+                return Err(error(
+                    &operator,
+                    format!("Unknown operator {}", operator.text),
+                ))
+            }
+        }
+        return Ok(());
+    }
+
+    fn compile(mut self) -> Result<Vec<u8>, AssemblerError> {
+        let start = Token {
+            text: String::from("main"),
+            line: 1,
+            column: 1,
+        };
+        // Octo programs always start with a jump to main
+        self.fixups.push(Fixup {
+            address: self.here,
+            name: start.clone(),
+        });
+        self.emit_op(&start, 0x1000)?;
+        while self.position < self.tokens.len() {
+            self.statement()?;
+        }
+        if let Some((_, token)) = self.blocks.last() {
+            return Err(error(token, format!("{} is never closed", token.text)));
+        }
+        for fixup in self.fixups.iter() {
+            let address = match self.labels.get(&fixup.name.text) {
+                Some(address) => *address,
+                None => {
+                    return Err(error(
+                        &fixup.name,
+                        format!("Undefined label {}", fixup.name.text),
+                    ))
+                }
+            };
+            self.memory[fixup.address] |= (address >> 8) as u8;
+            self.memory[fixup.address + 1] = address as u8;
+        }
+        return Ok(self.memory[PROGRAM_START as usize..self.end].to_vec());
+    }
+}
+
+/// Compile Octo source into a rom that can be loaded with `Chip8::load_game`
+///
+/// Supports labels, registers and `:alias`, `:const`, `:calc`, `:macro`, `:org`, `:byte`,
+/// `loop`/`while`/`again`, `if ... then`, `if ... begin ... else ... end` and sprite data
+/// written as plain numbers. Like Octo, the rom starts with a jump to `: main`.
+/// # Arguments
+/// * `source` - The Octo source
+/// # Returns
+/// * The rom, or the first error found
+/// # Example
+/// ```
+/// use chip8::chip8_octo::compile;
+/// let rom = compile(": main v0 := 5 loop again").expect("fail");
+/// assert_eq!(rom, [0x12, 0x02, 0x60, 0x05, 0x12, 0x04]);
+/// ```
+pub fn compile(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    return Compiler::new(source).compile();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_compile_instructions() {
+        let source = "
+            : main
+            clear
+            v1 := 0x12  v1 += 3  v1 -= 1  v2 := v1  v2 =- v1
+            i := hex v1  i += v2  bcd v3  save v4  load v5
+            delay := v1  buzzer := v2  v3 := delay  v4 := key  v5 := random 0xF0
+            sprite v1 v2 5
+            return
+        ";
+        let rom = compile(source).expect("fail");
+        assert_eq!(
+            rom,
+            [
+                0x12, 0x02, 0x00, 0xE0, 0x61, 0x12, 0x71, 0x03, 0x71, 0xFF, 0x82, 0x10, 0x82, 0x17,
+                0xF1, 0x29, 0xF2, 0x1E, 0xF3, 0x33, 0xF4, 0x55, 0xF5, 0x65, 0xF1, 0x15, 0xF2, 0x18,
+                0xF3, 0x07, 0xF4, 0x0A, 0xC5, 0xF0, 0xD1, 0x25, 0x00, 0xEE
+            ]
+        );
+    }
+    #[test]
+    fn test_compile_names() {
+        let source = "
+            :alias x v3
+            :const SPEED 4
+            :calc DOUBLE { SPEED * 2 + 1 }
+            :macro bump reg amount { reg += amount }
+            : main
+            x := SPEED
+            x := DOUBLE
+            bump x 7
+            i := sprite
+            draw
+            : draw ;
+            :org 0x300
+            : sprite 0xF0 0b10010000 SPEED
+        ";
+        let rom = compile(source).expect("fail");
+        assert_eq!(
+            &rom[..14],
+            [0x12, 0x02, 0x63, 0x04, 0x63, 0x0C, 0x73, 0x07, 0xA3, 0x00, 0x22, 0x0C, 0x00, 0xEE]
+        );
+        assert_eq!(rom.len(), 0x103);
+        assert_eq!(&rom[0x100..], [0xF0, 0x90, 0x04]);
+    }
+    #[test]
+    fn test_compile_control_flow() {
+        let source = "
+            : main
+            loop
+                if v0 == 3 then v1 := 1
+                while v0 != 9
+                if v2 key begin v3 := 1 else v3 := 2 end
+                v0 += 1
+            again
+        ";
+        let rom = compile(source).expect("fail");
+        assert_eq!(
+            rom,
+            [
+                0x12, 0x02, // jump main
+                0x40, 0x03, 0x61, 0x01, // if v0 == 3 then v1 := 1
+                0x40, 0x09, 0x12, 0x18, // while v0 != 9
+                0xE2, 0x9E, 0x12, 0x12, 0x63, 0x01, 0x12, 0x14, 0x63, 0x02, // if ... end
+                0x70, 0x01, 0x12, 0x02 // v0 += 1 again
+            ]
+        );
+    }
+    #[test]
+    fn test_compile_ordered_comparisons() {
+        for (x, comparison, y) in [
+            (3, "<", 5),
+            (5, ">", 3),
+            (3, "<=", 3),
+            (5, ">=", 5),
+            (2, ">", 7),
+        ] {
+            for right in [format!("{}", y), String::from("v1")] {
+                let source = format!(
+                    ": main v0 := {} v1 := {} v2 := 0 if v0 {} {} then v2 := 1 loop again",
+                    x, y, comparison, right
+                );
+                let mut c = Chip8::new();
+                c.load_game(compile(&source).expect("fail")).expect("fail");
+                for _ in 0..8 {
+                    c.emulate_cycle();
+                }
+                let expected = match comparison {
+                    "<" => x < y,
+                    ">" => x > y,
+                    "<=" => x <= y,
+                    _ => x >= y,
+                };
+                assert_eq!(c.v[2] == 1, expected, "{}", source);
+            }
+        }
+    }
+    #[test]
+    fn test_compile_runs() {
+        let source = "
+            : main
+            i := box
+            v0 := 0
+            v1 := 0
+            sprite v0 v1 2
+            loop again
+            : box 0xC0 0xC0
+        ";
+        let mut c = Chip8::new();
+        c.load_game(compile(source).expect("fail")).expect("fail");
+        for _ in 0..6 {
+            c.emulate_cycle();
+        }
+        assert_eq!(&c.gfx[..3], [1, 1, 0]);
+        assert_eq!(&c.gfx[64..67], [1, 1, 0]);
+    }
+    #[test]
+    fn test_compile_errors() {
+        let err = compile(": main\n  v0 := 300").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 9));
+        let err = compile(": main jump nowhere").unwrap_err();
+        assert_eq!(err.to_string(), "1:13: Undefined label nowhere");
+        let err = compile(": main loop v0 += 1").unwrap_err();
+        assert_eq!(err.message, "loop is never closed");
+        let err = compile("v0 := 1").unwrap_err();
+        assert_eq!(err.message, "Undefined label main");
+        let err = compile(":macro m { m } : main m").unwrap_err();
+        assert_eq!(err.message, "Too many macro expansions");
+    }
+}
@@ -0,0 +1,543 @@
+use std::fmt;
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+// An IPS record at this offset would read as the footer
+const IPS_EOF_OFFSET: usize = 0x454F46;
+const IPS_MAX_RECORD: usize = 0xFFFF;
+const BPS_HEADER: &[u8] = b"BPS1";
+
+/// The largest rom a patch may produce, the whole XO-CHIP address space
+pub const MAX_TARGET_SIZE: usize = 0x10000;
+
+/// The patch formats fixes are distributed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    /// International Patching System, byte replacements at fixed offsets
+    Ips,
+    /// beat patches, which carry CRC32 checksums of the original, the result and the patch
+    Bps,
+}
+
+/// Why a patch could not be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch does not start with a known header
+    UnknownFormat,
+    /// The patch ends in the middle of a record
+    Truncated,
+    /// The patch reads or writes outside of the rom it describes
+    OutOfRange,
+    /// The patch would produce a rom larger than `max` bytes
+    TooLarge { size: usize, max: usize },
+    /// The rom is not the one the patch was made for
+    SourceMismatch { expected: u32, found: u32 },
+    /// The patched rom does not have the checksum the patch promised
+    TargetMismatch { expected: u32, found: u32 },
+    /// The patch itself is damaged
+    PatchMismatch { expected: u32, found: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            PatchError::UnknownFormat => write!(f, "not an IPS or BPS patch"),
+            PatchError::Truncated => write!(f, "the patch is truncated"),
+            PatchError::OutOfRange => write!(f, "the patch refers to bytes outside of the rom"),
+            PatchError::TooLarge { size, max } => write!(
+                f,
+                "the patch makes a rom of {} bytes but at most {} bytes are allowed",
+                size, max
+            ),
+            PatchError::SourceMismatch { expected, found } => write!(
+                f,
+                "the patch is for a rom with CRC32 {:08x}, not {:08x}",
+                expected, found
+            ),
+            PatchError::TargetMismatch { expected, found } => write!(
+                f,
+                "the patched rom has CRC32 {:08x} instead of {:08x}",
+                found, expected
+            ),
+            PatchError::PatchMismatch { expected, found } => write!(
+                f,
+                "the patch has CRC32 {:08x} instead of {:08x}",
+                found, expected
+            ),
+        };
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// The CRC32 checksum BPS patches use, as in zlib
+/// # Example
+/// ```
+/// use chip8::chip8_patch::crc32;
+/// assert_eq!(crc32(b"123456789"), 0xCBF43926);
+/// ```
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    return !crc;
+}
+
+/// Guess the format of a patch from its header
+pub fn detect_patch_format(patch: &[u8]) -> Option<PatchFormat> {
+    if patch.starts_with(IPS_HEADER) {
+        return Some(PatchFormat::Ips);
+    }
+    if patch.starts_with(BPS_HEADER) {
+        return Some(PatchFormat::Bps);
+    }
+    return None;
+}
+
+/// Apply an IPS or BPS patch to a rom
+/// # Arguments
+/// * `rom` - The original rom
+/// * `patch` - The patch file
+/// # Returns
+/// * The patched rom, or an error if the patch is damaged or, for BPS, made for another rom
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    return match detect_patch_format(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err(PatchError::UnknownFormat),
+    };
+}
+
+/// Reads a patch from the front
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], PatchError> {
+        let end = self.at.checked_add(count).ok_or(PatchError::Truncated)?;
+        let ret = self.bytes.get(self.at..end).ok_or(PatchError::Truncated)?;
+        self.at = end;
+        return Ok(ret);
+    }
+
+    fn big_endian(&mut self, count: usize) -> Result<usize, PatchError> {
+        return Ok(self
+            .take(count)?
+            .iter()
+            .fold(0, |n, byte| n << 8 | *byte as usize));
+    }
+
+    /// A BPS number, seven bits to a byte with the last byte flagged
+    fn number(&mut self) -> Result<usize, PatchError> {
+        let mut ret: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.take(1)?[0];
+            let part = ((byte & 0x7F) as usize)
+                .checked_mul(shift)
+                .ok_or(PatchError::OutOfRange)?;
+            ret = ret.checked_add(part).ok_or(PatchError::OutOfRange)?;
+            if byte & 0x80 != 0 {
+                return Ok(ret);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::OutOfRange)?;
+            ret = ret.checked_add(shift).ok_or(PatchError::OutOfRange)?;
+        }
+    }
+}
+
+/// Apply an IPS patch, including the truncation extension
+/// # Example
+/// ```
+/// use chip8::chip8_patch::apply_ips;
+/// let patch = b"PATCH\x00\x00\x01\x00\x01\xAAEOF";
+/// assert_eq!(apply_ips(&[1, 2, 3], patch).expect("fail"), [1, 0xAA, 3]);
+/// ```
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(IPS_HEADER) {
+        return Err(PatchError::UnknownFormat);
+    }
+    let mut r = Reader {
+        bytes: patch,
+        at: IPS_HEADER.len(),
+    };
+    let mut ret = rom.to_vec();
+    loop {
+        if r.take(IPS_FOOTER.len())? == IPS_FOOTER {
+            break;
+        }
+        r.at -= IPS_FOOTER.len();
+        let offset = r.big_endian(3)?;
+        let (data, size) = match r.big_endian(2)? {
+            // A run of one byte
+            0 => {
+                let size = r.big_endian(2)?;
+                (None, size)
+            }
+            size => (Some(r.take(size)?), size),
+        };
+        if offset + size > MAX_TARGET_SIZE {
+            return Err(PatchError::TooLarge {
+                size: offset + size,
+                max: MAX_TARGET_SIZE,
+            });
+        }
+        if ret.len() < offset + size {
+            ret.resize(offset + size, 0);
+        }
+        match data {
+            Some(data) => ret[offset..offset + size].copy_from_slice(data),
+            None => {
+                let value = r.take(1)?[0];
+                ret[offset..offset + size].fill(value);
+            }
+        }
+    }
+    if r.at + 3 <= patch.len() {
+        let size = r.big_endian(3)?;
+        ret.truncate(size);
+    }
+    return Ok(ret);
+}
+
+/// Apply a BPS patch, checking the rom, the result and the patch against their checksums
+/// # Example
+/// ```
+/// use chip8::chip8_patch::{apply_bps, create_bps};
+/// let patch = create_bps(&[1, 2, 3], &[1, 5, 3, 4]);
+/// assert_eq!(apply_bps(&[1, 2, 3], &patch).expect("fail"), [1, 5, 3, 4]);
+/// assert!(apply_bps(&[1, 2, 4], &patch).is_err());
+/// ```
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(BPS_HEADER) {
+        return Err(PatchError::UnknownFormat);
+    }
+    if patch.len() < BPS_HEADER.len() + 12 {
+        return Err(PatchError::Truncated);
+    }
+    let footer = patch.len() - 12;
+    let checksum = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().expect("4 bytes"));
+    let (source_crc, target_crc, patch_crc) =
+        (checksum(footer), checksum(footer + 4), checksum(footer + 8));
+    let found = crc32(&patch[..footer + 8]);
+    if found != patch_crc {
+        return Err(PatchError::PatchMismatch {
+            expected: patch_crc,
+            found,
+        });
+    }
+    let found = crc32(rom);
+    if found != source_crc {
+        return Err(PatchError::SourceMismatch {
+            expected: source_crc,
+            found,
+        });
+    }
+
+    let mut r = Reader {
+        bytes: &patch[..footer],
+        at: BPS_HEADER.len(),
+    };
+    let source_size = r.number()?;
+    let target_size = r.number()?;
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::TooLarge {
+            size: target_size,
+            max: MAX_TARGET_SIZE,
+        });
+    }
+    let metadata_size = r.number()?;
+    r.take(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(PatchError::OutOfRange);
+    }
+    let mut target = Vec::with_capacity(target_size);
+    let (mut source_at, mut target_at) = (0usize, 0usize);
+    while r.at < footer {
+        let action = r.number()?;
+        let length = (action >> 2) + 1;
+        if target.len() + length > target_size {
+            return Err(PatchError::OutOfRange);
+        }
+        match action & 3 {
+            // SourceRead, the bytes at the same offset in the rom
+            0 => {
+                let at = target.len();
+                target.extend_from_slice(rom.get(at..at + length).ok_or(PatchError::OutOfRange)?);
+            }
+            // TargetRead, bytes from the patch
+            1 => target.extend_from_slice(r.take(length)?),
+            // SourceCopy, bytes from anywhere in the rom
+            2 => {
+                source_at = relative(source_at, r.number()?)?;
+                let bytes = rom
+                    .get(source_at..source_at + length)
+                    .ok_or(PatchError::OutOfRange)?;
+                target.extend_from_slice(bytes);
+                source_at += length;
+            }
+            // TargetCopy, earlier bytes of the result, which may overlap the bytes being written
+            _ => {
+                target_at = relative(target_at, r.number()?)?;
+                if target_at >= target.len() {
+                    return Err(PatchError::OutOfRange);
+                }
+                for _ in 0..length {
+                    target.push(target[target_at]);
+                    target_at += 1;
+                }
+            }
+        }
+    }
+    if target.len() != target_size {
+        return Err(PatchError::OutOfRange);
+    }
+    let found = crc32(&target);
+    if found != target_crc {
+        return Err(PatchError::TargetMismatch {
+            expected: target_crc,
+            found,
+        });
+    }
+    return Ok(target);
+}
+
+/// Move a BPS copy offset, whose lowest bit is the sign
+fn relative(offset: usize, data: usize) -> Result<usize, PatchError> {
+    let distance = data >> 1;
+    return if data & 1 != 0 {
+        offset.checked_sub(distance)
+    } else {
+        offset.checked_add(distance)
+    }
+    .ok_or(PatchError::OutOfRange);
+}
+
+/// Make an IPS patch that turns one rom into another
+///
+/// Runs of one byte are stored compressed, and a shorter result uses the truncation extension.
+/// # Panics
+/// This function will panic if the modified rom is 16MB or larger, which IPS cannot address
+/// # Example
+/// ```
+/// use chip8::chip8_patch::{apply_ips, create_ips};
+/// let patch = create_ips(&[1, 2, 3], &[1, 9, 3, 4]);
+/// assert_eq!(apply_ips(&[1, 2, 3], &patch).expect("fail"), [1, 9, 3, 4]);
+/// ```
+pub fn create_ips(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    assert!(modified.len() < 1 << 24, "IPS offsets are 24 bits");
+    let mut ret = IPS_HEADER.to_vec();
+    let differs = |at: usize| original.get(at) != Some(&modified[at]);
+    let mut at = 0;
+    while at < modified.len() {
+        if !differs(at) {
+            at += 1;
+            continue;
+        }
+        // A record cannot start where it would read as the footer
+        let start = if at == IPS_EOF_OFFSET { at - 1 } else { at };
+        let mut end = at;
+        while end < modified.len() && end - start < IPS_MAX_RECORD && differs(end) {
+            end += 1;
+        }
+        let data = &modified[start..end];
+        ret.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        if data.len() > 3 && data.iter().all(|b| *b == data[0]) {
+            ret.extend_from_slice(&[0, 0]);
+            ret.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            ret.push(data[0]);
+        } else {
+            ret.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            ret.extend_from_slice(data);
+        }
+        at = end;
+    }
+    ret.extend_from_slice(IPS_FOOTER);
+    if modified.len() < original.len() {
+        ret.extend_from_slice(&(modified.len() as u32).to_be_bytes()[1..]);
+    }
+    return ret;
+}
+
+fn push_number(out: &mut Vec<u8>, mut n: usize) {
+    loop {
+        let part = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(0x80 | part);
+            return;
+        }
+        out.push(part);
+        n -= 1;
+    }
+}
+
+/// Make a BPS patch that turns one rom into another
+///
+/// Bytes that match the original at the same offset are read from it, the rest are stored
+/// in the patch, which suits fixes that change a few instructions in place.
+pub fn create_bps(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut ret = BPS_HEADER.to_vec();
+    push_number(&mut ret, original.len());
+    push_number(&mut ret, modified.len());
+    push_number(&mut ret, 0);
+    let same = |at: usize| original.get(at) == Some(&modified[at]);
+    let mut at = 0;
+    while at < modified.len() {
+        let from_source = same(at);
+        let mut end = at;
+        while end < modified.len() && same(end) == from_source {
+            end += 1;
+        }
+        let length = end - at;
+        if from_source {
+            push_number(&mut ret, (length - 1) << 2);
+        } else {
+            push_number(&mut ret, (length - 1) << 2 | 1);
+            ret.extend_from_slice(&modified[at..end]);
+        }
+        at = end;
+    }
+    ret.extend_from_slice(&crc32(original).to_le_bytes());
+    ret.extend_from_slice(&crc32(modified).to_le_bytes());
+    let checksum = crc32(&ret);
+    ret.extend_from_slice(&checksum.to_le_bytes());
+    return ret;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roms() -> (Vec<u8>, Vec<u8>) {
+        let original = std::fs::read("c8_test.c8").expect("fail");
+        let mut modified = original.clone();
+        // A quirk fix changing one instruction, a filled block and an appended routine
+        modified[0x10] = 0x8E;
+        modified[0x11] = 0x06;
+        modified[0x20..0x30].fill(0xEE);
+        modified.extend_from_slice(&[0x00, 0xE0, 0x00, 0xEE]);
+        return (original, modified);
+    }
+    #[test]
+    fn test_ips_round_trip() {
+        let (original, modified) = roms();
+        let patch = create_ips(&original, &modified);
+        assert_eq!(detect_patch_format(&patch), Some(PatchFormat::Ips));
+        assert_eq!(apply_patch(&original, &patch), Ok(modified.clone()));
+        // The filled block is run length encoded
+        assert!(patch.len() < 5 + 3 * 5 + 2 + 2 + 1 + 4 + 3 + 16);
+        let patch = create_ips(&modified, &original);
+        assert_eq!(apply_ips(&modified, &patch), Ok(original.clone()));
+        assert_eq!(create_ips(&original, &original), b"PATCHEOF");
+    }
+    #[test]
+    fn test_ips_errors() {
+        assert_eq!(
+            apply_ips(&[1], b"PATCH\x00\x00"),
+            Err(PatchError::Truncated)
+        );
+        assert_eq!(
+            apply_ips(&[1], b"PATCH\x00\x00\x00\x00\x02\x01EOF"),
+            Err(PatchError::Truncated)
+        );
+        assert_eq!(apply_patch(&[1], b"PAT"), Err(PatchError::UnknownFormat));
+        // Records may grow the rom
+        assert_eq!(
+            apply_ips(&[1], b"PATCH\x00\x00\x02\x00\x00\x00\x02\x07EOF"),
+            Ok(vec![1, 0, 7, 7])
+        );
+        // A run at offset 454F45 would grow the rom to over 4 MB
+        assert_eq!(
+            apply_ips(&[1], b"PATCH\x45\x4F\x45\x00\x00\xFF\xFF\x07EOF"),
+            Err(PatchError::TooLarge {
+                size: 0x454F45 + 0xFFFF,
+                max: MAX_TARGET_SIZE
+            })
+        );
+        assert_eq!(
+            apply_ips(&[1], b"PATCH\x00\xFF\xFF\x00\x01\x07EOF").map(|rom| rom.len()),
+            Ok(MAX_TARGET_SIZE)
+        );
+    }
+    #[test]
+    fn test_bps_round_trip() {
+        let (original, modified) = roms();
+        let patch = create_bps(&original, &modified);
+        assert_eq!(detect_patch_format(&patch), Some(PatchFormat::Bps));
+        assert_eq!(apply_patch(&original, &patch), Ok(modified.clone()));
+        let patch = create_bps(&modified, &original);
+        assert_eq!(apply_bps(&modified, &patch), Ok(original.clone()));
+        let patch = create_bps(&[], &[1, 2]);
+        assert_eq!(apply_bps(&[], &patch), Ok(vec![1, 2]));
+    }
+    #[test]
+    fn test_bps_checksums() {
+        let (original, modified) = roms();
+        let patch = create_bps(&original, &modified);
+        assert!(matches!(
+            apply_bps(&modified, &patch),
+            Err(PatchError::SourceMismatch { .. })
+        ));
+        let mut damaged = patch.clone();
+        damaged[10] ^= 1;
+        assert!(matches!(
+            apply_bps(&original, &damaged),
+            Err(PatchError::PatchMismatch { .. })
+        ));
+        assert_eq!(apply_bps(&original, b"BPS1"), Err(PatchError::Truncated));
+    }
+    #[test]
+    fn test_bps_too_large() {
+        // A valid patch asking for a 4 GB rom, made of one TargetCopy that repeats a byte
+        let rom = [1];
+        let mut patch = BPS_HEADER.to_vec();
+        push_number(&mut patch, 1);
+        push_number(&mut patch, 1 << 32);
+        push_number(&mut patch, 0);
+        push_number(&mut patch, 0);
+        push_number(&mut patch, ((1 << 32) - 2) << 2 | 3);
+        push_number(&mut patch, 0);
+        patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+        patch.extend_from_slice(&0u32.to_le_bytes());
+        let checksum = crc32(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(
+            apply_bps(&rom, &patch),
+            Err(PatchError::TooLarge {
+                size: 1 << 32,
+                max: MAX_TARGET_SIZE
+            })
+        );
+        let patch = create_bps(&rom, &vec![7; MAX_TARGET_SIZE]);
+        assert_eq!(apply_bps(&rom, &patch), Ok(vec![7; MAX_TARGET_SIZE]));
+    }
+    #[test]
+    fn test_bps_copies() {
+        // SourceCopy of 2 bytes from offset 1, then TargetCopy of 3 bytes from offset 0
+        let rom = [1, 2, 3];
+        let mut patch = BPS_HEADER.to_vec();
+        push_number(&mut patch, 3);
+        push_number(&mut patch, 5);
+        push_number(&mut patch, 0);
+        push_number(&mut patch, (2 - 1) << 2 | 2);
+        push_number(&mut patch, 1 << 1);
+        push_number(&mut patch, (3 - 1) << 2 | 3);
+        push_number(&mut patch, 0);
+        patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+        patch.extend_from_slice(&crc32(&[2, 3, 2, 3, 2]).to_le_bytes());
+        let checksum = crc32(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(apply_bps(&rom, &patch), Ok(vec![2, 3, 2, 3, 2]));
+    }
+}
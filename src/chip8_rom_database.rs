@@ -0,0 +1,348 @@
+use crate::{Chip8, LoadError, Quirks};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// The bundled metadata, in the format of the community CHIP-8 database `programs.json`
+const BUNDLED_PROGRAMS: &str = include_str!("../data/programs.json");
+
+/// The machines a rom can be written for, as named by the community CHIP-8 database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Platform {
+    OriginalChip8,
+    HybridVip,
+    ModernChip8,
+    Chip48,
+    SuperChip1,
+    SuperChip,
+    MegaChip8,
+    XoChip,
+    Other(String),
+}
+
+impl Platform {
+    /// Look up a platform by its database id, such as `superchip`
+    pub fn from_id(id: &str) -> Self {
+        return match id {
+            "originalChip8" => Platform::OriginalChip8,
+            "hybridVIP" => Platform::HybridVip,
+            "modernChip8" => Platform::ModernChip8,
+            "chip48" => Platform::Chip48,
+            "superchip1" => Platform::SuperChip1,
+            "superchip" => Platform::SuperChip,
+            "megachip8" => Platform::MegaChip8,
+            "xochip" => Platform::XoChip,
+            other => Platform::Other(other.to_string()),
+        };
+    }
+
+    /// The quirks the platform's interpreter has
+    pub fn quirks(&self) -> Quirks {
+        let none = Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: Some(false),
+        };
+        return match self {
+            Platform::OriginalChip8 | Platform::HybridVip => Quirks {
+                vblank: true,
+                logic: Some(true),
+                ..none
+            },
+            Platform::Chip48 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                ..none
+            },
+            Platform::SuperChip1 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                vblank: true,
+                ..none
+            },
+            Platform::SuperChip | Platform::MegaChip8 => Quirks {
+                shift: true,
+                memory_leave_i_unchanged: true,
+                jump: true,
+                ..none
+            },
+            Platform::XoChip => Quirks { wrap: true, ..none },
+            Platform::ModernChip8 | Platform::Other(_) => none,
+        };
+    }
+}
+
+/// Display colours suggested for a rom, as `#rrggbb` strings
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+pub struct Colors {
+    #[serde(default)]
+    pub pixels: Vec<String>,
+    pub buzzer: Option<String>,
+    pub silence: Option<String>,
+}
+
+/// Everything the database knows about one rom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The first platform the rom runs on
+    pub platform: Platform,
+    /// The quirks of the platform, with the rom's own overrides applied
+    pub quirks: Quirks,
+    /// Recommended instructions per 60Hz frame
+    pub tickrate: Option<u32>,
+    /// Game actions such as `left` or `a`, mapped to keypad keys
+    pub keymap: BTreeMap<String, u8>,
+    pub colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgramEntry {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+    colors: Option<Colors>,
+}
+
+/// Apply database quirk overrides such as `{"shift": true}`
+fn apply_quirk_overrides(quirks: &mut Quirks, overrides: &HashMap<String, bool>) {
+    for (name, value) in overrides.iter() {
+        match name.as_str() {
+            "shift" => quirks.shift = *value,
+            "memoryIncrementByX" => quirks.memory_increment_by_x = *value,
+            "memoryLeaveIUnchanged" => quirks.memory_leave_i_unchanged = *value,
+            "wrap" => quirks.wrap = *value,
+            "jump" => quirks.jump = *value,
+            "vblank" => quirks.vblank = *value,
+            "logic" => quirks.logic = Some(*value),
+            _ => {}
+        }
+    }
+}
+
+/// Hash rom bytes the way the database keys them
+/// # Example
+/// ```
+/// use chip8::chip8_rom_database::sha1_hex;
+/// assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+/// ```
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    return sha1_smol::Sha1::from(bytes).digest().to_string();
+}
+
+/// Rom metadata keyed by SHA-1 hash
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// The small database bundled with the crate
+    pub fn bundled() -> Self {
+        return Self::from_json(BUNDLED_PROGRAMS).expect("the bundled database is valid");
+    }
+
+    /// Read a database in the format of the community CHIP-8 database `programs.json`
+    /// # Arguments
+    /// * `json` - The contents of `programs.json`
+    /// # Returns
+    /// * The database, or an error if the json does not match the format
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let programs: Vec<ProgramEntry> = serde_json::from_str(json)?;
+        let mut roms = HashMap::new();
+        for program in programs.into_iter() {
+            for (hash, rom) in program.roms.into_iter() {
+                let platform_id = rom
+                    .platforms
+                    .first()
+                    .cloned()
+                    .unwrap_or(String::from("originalChip8"));
+                let platform = Platform::from_id(&platform_id);
+                let mut quirks = platform.quirks();
+                if let Some(overrides) = rom.quirky_platforms.get(&platform_id) {
+                    apply_quirk_overrides(&mut quirks, overrides);
+                }
+                roms.insert(
+                    hash.to_ascii_lowercase(),
+                    RomInfo {
+                        title: program.title.clone(),
+                        authors: program.authors.clone(),
+                        platform,
+                        quirks,
+                        tickrate: rom.tickrate,
+                        keymap: rom.keys,
+                        colors: rom.colors,
+                    },
+                );
+            }
+        }
+        return Ok(Self { roms });
+    }
+
+    /// Find a rom by its bytes
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        return self.lookup_hash(&sha1_hex(rom));
+    }
+
+    /// Find a rom by its SHA-1 hash in hex
+    pub fn lookup_hash(&self, hash: &str) -> Option<&RomInfo> {
+        return self.roms.get(&hash.to_ascii_lowercase());
+    }
+
+    /// The number of roms in the database
+    pub fn len(&self) -> usize {
+        return self.roms.len();
+    }
+
+    /// Whether the database has no roms
+    pub fn is_empty(&self) -> bool {
+        return self.roms.is_empty();
+    }
+}
+
+impl Chip8 {
+    /// Apply the quirks and speed a rom database entry asks for
+    /// # Arguments
+    /// * `info` - The database entry
+    pub fn configure(&mut self, info: &RomInfo) {
+        self.set_quirks(info.quirks);
+        if let Some(tickrate) = info.tickrate {
+            self.set_instructions_per_frame(tickrate);
+        }
+    }
+
+    /// Load a game and configure the interpreter from its database entry
+    /// # Arguments
+    /// * `game` - The game to load
+    /// * `database` - The database to look the game up in
+    /// # Returns
+    /// * The database entry, if the game was found, so frontends can use its keymap and colours.
+    ///   An error if the game could not be loaded.
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// use chip8::chip8_rom_database::RomDatabase;
+    /// let mut c = Chip8::new();
+    /// let game = std::fs::read("c8_test.c8").expect("fail");
+    /// let info = c.load_game_from_database(game, &RomDatabase::bundled()).expect("fail");
+    /// assert_eq!(info.expect("fail").title, "Chip-8 test rom");
+    /// ```
+    pub fn load_game_from_database(
+        &mut self,
+        game: Vec<u8>,
+        database: &RomDatabase,
+    ) -> Result<Option<RomInfo>, LoadError> {
+        self.load_game_from_slice(&game)?;
+        let info = database.lookup(&game).cloned();
+        if let Some(info) = info.as_ref() {
+            self.configure(info);
+        }
+        return Ok(info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAMS: &str = r##"[
+        {
+            "title": "Quirky",
+            "authors": ["Someone"],
+            "roms": {
+                "ABCDEF0123456789ABCDEF0123456789ABCDEF01": {
+                    "file": "quirky.ch8",
+                    "platforms": ["superchip", "xochip"],
+                    "quirkyPlatforms": { "superchip": { "jump": false, "wrap": true } },
+                    "tickrate": 30,
+                    "keys": { "left": 7, "right": 9, "a": 6 },
+                    "colors": { "pixels": ["#000000", "#ff0000"] }
+                }
+            }
+        }
+    ]"##;
+
+    #[test]
+    fn test_from_json() {
+        let database = RomDatabase::from_json(PROGRAMS).expect("fail");
+        assert_eq!(database.len(), 1);
+        let info = database
+            .lookup_hash("abcdef0123456789abcdef0123456789abcdef01")
+            .expect("fail");
+        assert_eq!(info.title, "Quirky");
+        assert_eq!(info.authors, ["Someone"]);
+        assert_eq!(info.platform, Platform::SuperChip);
+        assert_eq!(
+            info.quirks,
+            Quirks {
+                jump: false,
+                wrap: true,
+                ..Platform::SuperChip.quirks()
+            }
+        );
+        assert_eq!(info.tickrate, Some(30));
+        assert_eq!(info.keymap["left"], 7);
+        let colors = info.colors.as_ref().expect("fail");
+        assert_eq!(colors.pixels[1], "#ff0000");
+        assert_eq!(colors.buzzer, None);
+    }
+    #[test]
+    fn test_bundled_lookup() {
+        let game = std::fs::read("c8_test.c8").expect("Failed to load game");
+        let database = RomDatabase::bundled();
+        let info = database.lookup(&game).expect("fail");
+        assert_eq!(info.platform, Platform::ModernChip8);
+        assert!(database.lookup(&game[1..]).is_none());
+        // The IBM logo of the conformance suite is the one every interpreter runs first
+        let logo = std::fs::read("tests/roms/2-ibm-logo.ch8").expect("fail");
+        let info = database.lookup(&logo).expect("fail");
+        assert_eq!(info.title, "IBM Logo");
+        assert_eq!(info.platform, Platform::OriginalChip8);
+        let info = database
+            .lookup_hash("b232ef880bd6060fb45fa6effed7edf0ae95670e")
+            .expect("fail");
+        assert_eq!(info.authors, ["Paul Vervalin"]);
+        assert_eq!(info.keymap["up"], 1);
+    }
+    #[test]
+    fn test_load_game_from_database() {
+        let database = RomDatabase::from_json(PROGRAMS).expect("fail");
+        let mut c = Chip8::new();
+        assert!(c
+            .load_game_from_database(vec![0x12, 0x00], &database)
+            .expect("fail")
+            .is_none());
+        assert_eq!(c.get_quirks(), Quirks::default());
+        assert_eq!(c.memory[0x200], 0x12);
+
+        let game = std::fs::read("c8_test.c8").expect("Failed to load game");
+        let info = c
+            .load_game_from_database(game, &RomDatabase::bundled())
+            .expect("fail")
+            .expect("fail");
+        assert_eq!(c.get_quirks(), info.quirks);
+        assert_eq!(c.get_instructions_per_frame(), 15);
+        assert!(c.load_game_from_database(vec![], &database).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use crate::chip8_font::{BIG_FONT_SIZE, SMALL_FONT_SIZE};
+use crate::{Chip8, Quirks, FLAG_COUNT};
+use core::fmt;
+
+const MAGIC: [u8; 4] = *b"C8ST";
+const VERSION: u8 = 2;
+
+/// The number of bytes in a saved state
+pub const STATE_SIZE: usize = MAGIC.len()
+    + 1 // version
+    + 4096 // memory
+    + 16 // v
+    + 2 // i
+    + 2 // pc
+    + 64 * 32 // gfx
+    + 1 // extended_gfx_mode
+    + 1 // delay_timer
+    + 1 // sound_timer
+    + 16 * 2 // stack
+    + 2 // sp
+    + 16 // key
+    + 1 // awaited_key
+    + 7 // quirks
+    + 4 // instructions_per_frame
+    + 2 // load_address
+    + 2 // font_address
+    + FLAG_COUNT
+    + 4; // rng_state
+
+/// Why a saved state could not be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The state is not `STATE_SIZE` bytes long
+    InvalidSize(usize),
+    /// The state does not start with the state header
+    InvalidHeader,
+    /// The state was saved by another version of the format
+    UnsupportedVersion(u8),
+    /// The stack pointer, the key awaited by FX0A, the load or font address or the random
+    /// number generator state is out of range
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            StateError::InvalidSize(size) => {
+                write!(f, "a saved state is {} bytes, not {}", STATE_SIZE, size)
+            }
+            StateError::InvalidHeader => write!(f, "not a saved state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported saved state version {}", version)
+            }
+            StateError::Corrupt => write!(f, "the saved state is corrupt"),
+        };
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+struct Writer<'a> {
+    out: &'a mut [u8],
+    at: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.out[self.at..self.at + bytes.len()].copy_from_slice(bytes);
+        self.at += bytes.len();
+    }
+}
+
+struct Reader<'a> {
+    state: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut ret = [0; N];
+        ret.copy_from_slice(&self.state[self.at..self.at + N]);
+        self.at += N;
+        return ret;
+    }
+
+    fn u8(&mut self) -> u8 {
+        return self.bytes::<1>()[0];
+    }
+
+    fn u16(&mut self) -> u16 {
+        return u16::from_be_bytes(self.bytes());
+    }
+
+    fn u32(&mut self) -> u32 {
+        return u32::from_be_bytes(self.bytes());
+    }
+}
+
+impl Chip8 {
+    /// Save the whole machine, so it can be restored with `load_state`
+    ///
+    /// The state holds memory, registers, timers, the display, keys, quirks, flags and
+    /// the random number generator. The flag store and the loaded rom's hash are not saved.
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// c.load_game_from_slice(&[0x60, 0x01, 0x12, 0x00]).expect("fail");
+    /// let state = c.save_state();
+    /// c.emulate_cycle();
+    /// c.load_state(&state).expect("fail");
+    /// assert_eq!(c.save_state(), state);
+    /// ```
+    pub fn save_state(&self) -> [u8; STATE_SIZE] {
+        let mut state = [0; STATE_SIZE];
+        let mut w = Writer {
+            out: &mut state,
+            at: 0,
+        };
+        w.bytes(&MAGIC);
+        w.bytes(&[VERSION]);
+        w.bytes(&self.memory);
+        w.bytes(&self.v);
+        w.bytes(&self.i.to_be_bytes());
+        w.bytes(&self.pc.to_be_bytes());
+        w.bytes(&self.gfx);
+        w.bytes(&[
+            self.extended_gfx_mode as u8,
+            self.delay_timer,
+            self.sound_timer,
+        ]);
+        for address in self.stack.iter() {
+            w.bytes(&address.to_be_bytes());
+        }
+        w.bytes(&self.sp.to_be_bytes());
+        w.bytes(&self.key);
+        // 0 while FX0A is not waiting on a released key
+        w.bytes(&[self.awaited_key.map_or(0, |k| k + 1)]);
+        let q = self.quirks;
+        w.bytes(&[
+            q.shift as u8,
+            q.memory_increment_by_x as u8,
+            q.memory_leave_i_unchanged as u8,
+            q.wrap as u8,
+            q.jump as u8,
+            q.vblank as u8,
+            // 0 for None, 1 for false and 2 for true
+            q.logic.map_or(0, |l| l as u8 + 1),
+        ]);
+        w.bytes(&self.instructions_per_frame.to_be_bytes());
+        w.bytes(&self.load_address.to_be_bytes());
+        w.bytes(&self.font_address.to_be_bytes());
+        w.bytes(&self.flags);
+        w.bytes(&self.rng_state.to_be_bytes());
+        debug_assert_eq!(w.at, STATE_SIZE);
+        return state;
+    }
+
+    /// Restore a state saved by `save_state`
+    /// # Arguments
+    /// * `state` - The saved state
+    /// # Returns
+    /// * An error if the state is not valid, in which case the machine is unchanged
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        // The version comes before the size, as other versions have other sizes
+        let header = MAGIC.len() + 1;
+        if state.len() < header {
+            return Err(StateError::InvalidSize(state.len()));
+        }
+        if state[..MAGIC.len()] != MAGIC {
+            return Err(StateError::InvalidHeader);
+        }
+        let version = state[MAGIC.len()];
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if state.len() != STATE_SIZE {
+            return Err(StateError::InvalidSize(state.len()));
+        }
+        let mut r = Reader { state, at: header };
+        let memory = r.bytes::<4096>();
+        let v = r.bytes::<16>();
+        let i = r.u16();
+        let pc = r.u16();
+        let gfx = r.bytes::<{ 64 * 32 }>();
+        let extended_gfx_mode = r.u8() != 0;
+        let delay_timer = r.u8();
+        let sound_timer = r.u8();
+        let mut stack = [0; 16];
+        for address in stack.iter_mut() {
+            *address = r.u16();
+        }
+        let sp = r.u16();
+        // Any program counter is fine, addresses wrap around memory
+        if sp as usize > stack.len() {
+            return Err(StateError::Corrupt);
+        }
+        let key = r.bytes::<16>();
+        let awaited_key = match r.u8() {
+            0 => None,
+            k @ 1..=16 => Some(k - 1),
+            _ => return Err(StateError::Corrupt),
+        };
+        let q = r.bytes::<7>();
+        let quirks = Quirks {
+            shift: q[0] != 0,
+            memory_increment_by_x: q[1] != 0,
+            memory_leave_i_unchanged: q[2] != 0,
+            wrap: q[3] != 0,
+            jump: q[4] != 0,
+            vblank: q[5] != 0,
+            logic: match q[6] {
+                0 => None,
+                l @ 1..=2 => Some(l == 2),
+                _ => return Err(StateError::Corrupt),
+            },
+        };
+
+        let instructions_per_frame = r.u32();
+        let load_address = r.u16();
+        let font_address = r.u16();
+        let flags = r.bytes::<FLAG_COUNT>();
+        let rng_state = r.u32();
+        // FX29 and FX30 index memory from the font address, and xorshift never leaves 0
+        if load_address > 0xFFF
+            || font_address as usize + SMALL_FONT_SIZE + BIG_FONT_SIZE > memory.len()
+            || rng_state == 0
+        {
+            return Err(StateError::Corrupt);
+        }
+
+        self.memory = memory;
+        self.v = v;
+        self.i = i;
+        self.pc = pc;
+        self.gfx = gfx;
+        self.extended_gfx_mode = extended_gfx_mode;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.stack = stack;
+        self.sp = sp;
+        self.key = key;
+        self.awaited_key = awaited_key;
+        self.quirks = quirks;
+        self.instructions_per_frame = instructions_per_frame;
+        self.load_address = load_address;
+        self.font_address = font_address;
+        self.flags = flags;
+        self.rng_state = rng_state;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[0x60, 0x05, 0xF0, 0x15, 0xA2, 0x00, 0xD0, 0x05, 0x22, 0x00])
+            .expect("fail");
+        c.set_quirks(Quirks {
+            jump: true,
+            ..Quirks::default()
+        });
+        c.set_flags([3; FLAG_COUNT]);
+        c.set_key(7, true);
+        for _ in 0..5 {
+            c.emulate_cycle();
+        }
+        let state = c.save_state();
+
+        let mut d = Chip8::new();
+        d.load_state(&state).expect("fail");
+        assert_eq!(d.save_state(), state);
+        assert_eq!(d.pc, c.pc);
+        assert_eq!(d.sp, 1);
+        assert_eq!(d.stack[0], 0x20A);
+        assert_eq!(d.gfx, c.gfx);
+        assert_eq!(d.get_quirks(), c.get_quirks());
+        assert!(d.get_key(7));
+        c.emulate_cycle();
+        d.emulate_cycle();
+        assert_eq!(d.save_state(), c.save_state());
+    }
+    #[test]
+    fn test_invalid_states() {
+        let mut c = Chip8::new();
+        let state = c.save_state();
+        assert_eq!(
+            c.load_state(&state[..STATE_SIZE - 1]),
+            Err(StateError::InvalidSize(STATE_SIZE - 1))
+        );
+        assert_eq!(c.load_state(&state[..3]), Err(StateError::InvalidSize(3)));
+        // Version 1 states are shorter, they had no key awaited by FX0A
+        let mut old = state[..STATE_SIZE - 1].to_vec();
+        old[4] = 1;
+        assert_eq!(c.load_state(&old), Err(StateError::UnsupportedVersion(1)));
+        let mut bad = state;
+        bad[0] = b'X';
+        assert_eq!(c.load_state(&bad), Err(StateError::InvalidHeader));
+        let mut bad = state;
+        bad[4] = 9;
+        assert_eq!(c.load_state(&bad), Err(StateError::UnsupportedVersion(9)));
+        let mut bad = state;
+        // The high byte of sp
+        bad[5 + 4096 + 16 + 2 + 2 + 64 * 32 + 3 + 16 * 2] = 0xFF;
+        assert_eq!(c.load_state(&bad), Err(StateError::Corrupt));
+        let mut bad = state;
+        // The key awaited by FX0A
+        bad[5 + 4096 + 16 + 2 + 2 + 64 * 32 + 3 + 16 * 2 + 2 + 16] = 17;
+        assert_eq!(c.load_state(&bad), Err(StateError::Corrupt));
+        assert_eq!(c.save_state(), state);
+    }
+    #[test]
+    fn test_out_of_range_addresses() {
+        let mut c = Chip8::new();
+        let state = c.save_state();
+        // The last fields are the load and font addresses, the flags and the rng state
+        let load_address = STATE_SIZE - 4 - FLAG_COUNT - 2 - 2;
+        let font_address = load_address + 2;
+        let rng_state = STATE_SIZE - 4;
+        let mut bad = state;
+        bad[load_address..load_address + 2].copy_from_slice(&0x1000u16.to_be_bytes());
+        assert_eq!(c.load_state(&bad), Err(StateError::Corrupt));
+        let mut bad = state;
+        bad[font_address..font_address + 2].copy_from_slice(&0xFFF0u16.to_be_bytes());
+        assert_eq!(c.load_state(&bad), Err(StateError::Corrupt));
+        let mut bad = state;
+        bad[font_address..font_address + 2].copy_from_slice(&0xF11u16.to_be_bytes());
+        assert_eq!(c.load_state(&bad), Err(StateError::Corrupt));
+        // The last address the whole font fits at
+        bad[font_address..font_address + 2].copy_from_slice(&0xF10u16.to_be_bytes());
+        assert_eq!(c.load_state(&bad), Ok(()));
+        let mut bad = state;
+        bad[rng_state..].fill(0);
+        assert_eq!(c.load_state(&bad), Err(StateError::Corrupt));
+    }
+}
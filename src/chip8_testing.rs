@@ -0,0 +1,368 @@
+use crate::chip8_frontend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::chip8_rom_database::sha1_hex;
+use crate::Chip8;
+use std::fmt;
+use std::path::Path;
+
+/// Set this environment variable to rewrite snapshot files instead of comparing with them
+pub const UPDATE_SNAPSHOTS_VAR: &str = "CHIP8_BLESS";
+
+/// A picture of the display, for asserting on in rom level tests
+///
+/// As text a snapshot is its dimensions followed by one line per row, `#` for a lit pixel
+/// and `.` for an unlit one:
+/// ```text
+/// 4x2
+/// #..#
+/// .##.
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+/// Why snapshot text could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The first line is not `<width>x<height>`
+    InvalidHeader,
+    /// The number of rows differs from the height
+    WrongHeight { expected: usize, found: usize },
+    /// A row is not as long as the width, rows are numbered from 0
+    WrongWidth { row: usize, found: usize },
+    /// A pixel is neither `#` nor `.`
+    InvalidPixel {
+        row: usize,
+        column: usize,
+        found: char,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            SnapshotError::InvalidHeader => {
+                write!(f, "a snapshot starts with its size, such as 64x32")
+            }
+            SnapshotError::WrongHeight { expected, found } => {
+                write!(f, "expected {} rows, found {}", expected, found)
+            }
+            SnapshotError::WrongWidth { row, found } => {
+                write!(f, "row {} has the wrong width {}", row, found)
+            }
+            SnapshotError::InvalidPixel { row, column, found } => {
+                write!(
+                    f,
+                    "invalid pixel {:?} at row {} column {}",
+                    found, row, column
+                )
+            }
+        };
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Snapshot {
+    /// Make a snapshot from pixels stored row by row, anything but 0 is lit
+    /// # Panics
+    /// This function will panic if there are not `width * height` pixels
+    pub fn new(width: usize, height: usize, pixels: &[u8]) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "a {}x{} screen",
+            width,
+            height
+        );
+        return Self {
+            width,
+            height,
+            pixels: pixels.iter().map(|p| *p != 0).collect(),
+        };
+    }
+
+    /// Take a snapshot of an interpreter's display
+    pub fn from_chip8(c: &Chip8) -> Self {
+        return Self::new(SCREEN_WIDTH, SCREEN_HEIGHT, c.get_gfx());
+    }
+
+    /// Read a snapshot from its text form, a missing newline at the end is fine
+    /// # Example
+    /// ```
+    /// use chip8::chip8_testing::Snapshot;
+    /// let s = Snapshot::parse("2x1\n#.\n").expect("fail");
+    /// assert_eq!(s.to_string(), "2x1\n#.\n");
+    /// assert!(Snapshot::parse("2x1\n#\n").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, SnapshotError> {
+        let mut lines = text.lines();
+        let (width, height) = lines
+            .next()
+            .and_then(|header| header.trim().split_once('x'))
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .ok_or(SnapshotError::InvalidHeader)?;
+        let rows: Vec<&str> = lines.collect();
+        if rows.len() != height {
+            return Err(SnapshotError::WrongHeight {
+                expected: height,
+                found: rows.len(),
+            });
+        }
+        let mut pixels = Vec::with_capacity(width * height);
+        for (row, line) in rows.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(SnapshotError::WrongWidth {
+                    row,
+                    found: line.chars().count(),
+                });
+            }
+            for (column, pixel) in line.chars().enumerate() {
+                pixels.push(match pixel {
+                    '#' => true,
+                    '.' => false,
+                    found => {
+                        return Err(SnapshotError::InvalidPixel { row, column, found });
+                    }
+                });
+            }
+        }
+        return Ok(Self {
+            width,
+            height,
+            pixels,
+        });
+    }
+
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    pub fn height(&self) -> usize {
+        return self.height;
+    }
+
+    /// Whether the pixel at column `x` of row `y` is lit
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        return self.pixels[y * self.width + x];
+    }
+
+    /// The canonical hash of the screen, the SHA-1 of its text form
+    ///
+    /// Short enough to paste into a test when the picture itself would not help.
+    pub fn hash(&self) -> String {
+        return sha1_hex(self.to_string().as_bytes());
+    }
+
+    fn row(&self, y: usize) -> String {
+        return self.pixels[y * self.width..(y + 1) * self.width]
+            .iter()
+            .map(|p| if *p { '#' } else { '.' })
+            .collect();
+    }
+
+    /// Show two snapshots next to each other, rows that differ are marked with `<`
+    /// # Example
+    /// ```
+    /// use chip8::chip8_testing::Snapshot;
+    /// let expected = Snapshot::new(2, 2, &[1, 0, 0, 1]);
+    /// let actual = Snapshot::new(2, 2, &[1, 0, 1, 1]);
+    /// assert_eq!(expected.diff(&actual), "expected | actual\n#.       | #.\n.#       | ## <\n");
+    /// ```
+    pub fn diff(&self, actual: &Snapshot) -> String {
+        let column = self.width.max("expected".len());
+        let mut ret = format!("{:column$} | actual\n", "expected");
+        for y in 0..self.height.max(actual.height) {
+            let left = if y < self.height {
+                self.row(y)
+            } else {
+                String::new()
+            };
+            let right = if y < actual.height {
+                actual.row(y)
+            } else {
+                String::new()
+            };
+            let marker = if left != right { " <" } else { "" };
+            ret += &format!("{:column$} | {}{}\n", left, right, marker);
+        }
+        return ret;
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}x{}", self.width, self.height)?;
+        for y in 0..self.height {
+            writeln!(f, "{}", self.row(y))?;
+        }
+        return Ok(());
+    }
+}
+
+/// Whether snapshot files should be rewritten, see `UPDATE_SNAPSHOTS_VAR`
+pub fn updating_snapshots() -> bool {
+    return std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some_and(|v| !v.is_empty() && v != "0");
+}
+
+/// Compare a screen with a snapshot file, or write the file when updating snapshots
+///
+/// This is what `assert_screen!` calls, for tests that collect failures instead of panicking.
+/// # Arguments
+/// * `actual` - The screen the test produced
+/// * `path` - The snapshot file
+/// # Returns
+/// * A message with a side by side diff if the screen does not match, or if the file is missing or invalid
+pub fn check_snapshot_file(actual: &Snapshot, path: &Path) -> Result<(), String> {
+    let expected = match std::fs::read_to_string(path) {
+        Ok(text) => Snapshot::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    };
+    if expected.as_ref() == Ok(actual) {
+        return Ok(());
+    }
+    if updating_snapshots() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        return std::fs::write(path, actual.to_string())
+            .map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    return Err(match expected {
+        Ok(expected) => format!(
+            "the screen differs from {}, set {}=1 to update it\n{}",
+            path.display(),
+            UPDATE_SNAPSHOTS_VAR,
+            expected.diff(actual)
+        ),
+        Err(e) => format!(
+            "{}, set {}=1 to record this screen\n{}",
+            e, UPDATE_SNAPSHOTS_VAR, actual
+        ),
+    });
+}
+
+/// Compare a screen with its canonical hash
+/// # Returns
+/// * A message holding the screen if its hash differs
+pub fn check_snapshot_hash(actual: &Snapshot, hash: &str) -> Result<(), String> {
+    let found = actual.hash();
+    if found == hash {
+        return Ok(());
+    }
+    return Err(format!(
+        "the screen hash is {}, not {}\n{}",
+        found, hash, actual
+    ));
+}
+
+/// Assert that an interpreter's display matches a snapshot file or a screen hash
+///
+/// Relative paths are taken from the working directory, which `cargo test` sets to the
+/// package root. Run the tests with `CHIP8_BLESS=1` to write the files that are missing
+/// or out of date; hashes are never updated.
+/// # Example
+/// ```
+/// use chip8::{assert_screen, Chip8};
+/// let c = Chip8::new();
+/// assert_screen!(c, hash = "a71a50be6bb31f87163ff907e6c16e8b45f9b56c");
+/// ```
+/// ```no_run
+/// # use chip8::{assert_screen, Chip8};
+/// # let c = Chip8::new();
+/// assert_screen!(c, "tests/snapshots/blank.txt");
+/// ```
+#[macro_export]
+macro_rules! assert_screen {
+    ($chip8:expr, hash = $hash:expr) => {
+        if let Err(message) = $crate::chip8_testing::check_snapshot_hash(
+            &$crate::chip8_testing::Snapshot::from_chip8(&$chip8),
+            $hash,
+        ) {
+            panic!("{}", message);
+        }
+    };
+    ($chip8:expr, $path:expr) => {
+        if let Err(message) = $crate::chip8_testing::check_snapshot_file(
+            &$crate::chip8_testing::Snapshot::from_chip8(&$chip8),
+            ::std::path::Path::new(&$path),
+        ) {
+            panic!("{}", message);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip() {
+        let s = Snapshot::new(3, 2, &[1, 0, 0, 0, 1, 1]);
+        assert_eq!(s.to_string(), "3x2\n#..\n.##\n");
+        assert_eq!(Snapshot::parse(&s.to_string()), Ok(s.clone()));
+        assert!(s.get(0, 0));
+        assert!(!s.get(0, 1));
+        assert_eq!(s.hash(), sha1_hex(b"3x2\n#..\n.##\n"));
+    }
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Snapshot::parse("#.\n"), Err(SnapshotError::InvalidHeader));
+        assert_eq!(
+            Snapshot::parse("2x2\n#.\n"),
+            Err(SnapshotError::WrongHeight {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Snapshot::parse("2x1\n#..\n"),
+            Err(SnapshotError::WrongWidth { row: 0, found: 3 })
+        );
+        assert_eq!(
+            Snapshot::parse("2x1\n#o\n"),
+            Err(SnapshotError::InvalidPixel {
+                row: 0,
+                column: 1,
+                found: 'o'
+            })
+        );
+    }
+    #[test]
+    fn test_diff_sizes() {
+        let expected = Snapshot::new(1, 1, &[1]);
+        let actual = Snapshot::new(2, 2, &[1, 0, 0, 0]);
+        assert_eq!(
+            expected.diff(&actual),
+            "expected | actual\n#        | #. <\n         | .. <\n"
+        );
+    }
+    #[test]
+    fn test_check_snapshot_file() {
+        // Missing and stale files would be written instead
+        if updating_snapshots() {
+            return;
+        }
+        // LD I, 206 ; DRW V0, V0, 1 ; JP 204 ; sprite
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[0xA2, 0x06, 0xD0, 0x01, 0x12, 0x04, 0xF0])
+            .expect("fail");
+        c.run_frame();
+        let actual = Snapshot::from_chip8(&c);
+        let path = std::env::temp_dir().join(format!("chip8_snapshot_{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let missing = check_snapshot_file(&actual, &path).expect_err("fail");
+        assert!(missing.contains(UPDATE_SNAPSHOTS_VAR));
+        std::fs::write(&path, Snapshot::new(64, 32, &[0; 64 * 32]).to_string()).expect("fail");
+        let differs = check_snapshot_file(&actual, &path).expect_err("fail");
+        let row = format!("{} | ####{} <", ".".repeat(64), ".".repeat(60));
+        assert!(differs.contains(&row));
+        std::fs::write(&path, actual.to_string()).expect("fail");
+        assert_screen!(c, &path);
+        assert_screen!(c, hash = actual.hash().as_str());
+        let _ = std::fs::remove_file(&path);
+        assert!(check_snapshot_hash(&actual, "0").is_err());
+    }
+}
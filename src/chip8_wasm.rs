@@ -0,0 +1,164 @@
+use crate::chip8_frontend::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::Chip8;
+use wasm_bindgen::prelude::*;
+
+/// The interpreter as seen from JavaScript, exported as `Chip8`
+///
+/// ```js
+/// const chip8 = new Chip8();
+/// chip8.loadRom(new Uint8Array(await (await fetch("pong.ch8")).arrayBuffer()));
+/// const image = new ImageData(chip8.width, chip8.height);
+/// function frame() {
+///     chip8.runFrame();
+///     image.data.set(chip8.framebufferRgba());
+///     context.putImageData(image, 0, 0);
+///     requestAnimationFrame(frame);
+/// }
+/// ```
+#[wasm_bindgen(js_name = Chip8)]
+pub struct WasmChip8 {
+    chip8: Chip8,
+    foreground: [u8; 4],
+    background: [u8; 4],
+}
+
+impl Default for WasmChip8 {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[wasm_bindgen(js_class = Chip8)]
+impl WasmChip8 {
+    /// Create an interpreter that draws white pixels on black
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        return Self {
+            chip8: Chip8::new(),
+            foreground: [0xFF, 0xFF, 0xFF, 0xFF],
+            background: [0x00, 0x00, 0x00, 0xFF],
+        };
+    }
+
+    /// The display width in pixels
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        return SCREEN_WIDTH;
+    }
+
+    /// The display height in pixels
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        return SCREEN_HEIGHT;
+    }
+
+    /// Load a rom at the load address, throws if it is empty or too large
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        return self
+            .chip8
+            .load_game_from_slice(rom)
+            .map_err(|e| JsError::new(&e.to_string()));
+    }
+
+    /// Run one 60Hz frame worth of instructions, ending early after a draw with the vblank quirk
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) {
+        self.chip8.run_frame();
+    }
+
+    /// Press or release a key of the hex keypad, throws if `key` is over 15
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), JsError> {
+        if key > 0xF {
+            return Err(JsError::new("keys are numbered 0 to 15"));
+        }
+        self.chip8.set_key(key, pressed);
+        return Ok(());
+    }
+
+    /// Set the pixel colours as `0xRRGGBB`
+    #[wasm_bindgen(js_name = setColors)]
+    pub fn set_colors(&mut self, foreground: u32, background: u32) {
+        self.foreground = rgba(foreground);
+        self.background = rgba(background);
+    }
+
+    /// The display as RGBA bytes, ready for `ImageData.data.set`
+    #[wasm_bindgen(js_name = framebufferRgba)]
+    pub fn framebuffer_rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        for pixel in self.chip8.get_gfx().iter() {
+            if *pixel != 0 {
+                rgba.extend_from_slice(&self.foreground);
+            } else {
+                rgba.extend_from_slice(&self.background);
+            }
+        }
+        return rgba;
+    }
+
+    /// Whether the buzzer should be sounding
+    #[wasm_bindgen(js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        return self.chip8.get_sound_timer() > 0;
+    }
+
+    /// Save the whole machine
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self) -> Vec<u8> {
+        return self.chip8.save_state().to_vec();
+    }
+
+    /// Restore a state from `saveState`, throws and leaves the machine unchanged if it is invalid
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsError> {
+        return self
+            .chip8
+            .load_state(state)
+            .map_err(|e| JsError::new(&e.to_string()));
+    }
+}
+
+fn rgba(color: u32) -> [u8; 4] {
+    let [_, r, g, b] = color.to_be_bytes();
+    return [r, g, b, 0xFF];
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_frame_to_rgba() {
+        // LD I, 206 ; DRW V0, V0, 1 ; JP 204 ; sprite
+        let mut c = WasmChip8::new();
+        c.load_rom(&[0xA2, 0x06, 0xD0, 0x01, 0x12, 0x04, 0x80])
+            .expect("fail");
+        c.set_colors(0x112233, 0x445566);
+        c.run_frame();
+        let rgba = c.framebuffer_rgba();
+        assert_eq!(rgba.len(), c.width() * c.height() * 4);
+        assert_eq!(rgba[..8], [0x11, 0x22, 0x33, 0xFF, 0x44, 0x55, 0x66, 0xFF]);
+    }
+    #[wasm_bindgen_test]
+    fn test_keys_and_states() {
+        // LD V1, 3 ; LD ST, V1 ; SKP V0 ; JP 204 ; JP 208
+        let mut c = WasmChip8::new();
+        c.load_rom(&[0x61, 0x03, 0xF1, 0x18, 0xE0, 0x9E, 0x12, 0x04, 0x12, 0x08])
+            .expect("fail");
+        assert!(c.load_rom(&[]).is_err());
+        c.run_frame();
+        assert!(c.sound_active());
+        let state = c.save_state();
+        assert!(c.set_key(16, true).is_err());
+        c.set_key(0, true).expect("fail");
+        c.run_frame();
+        assert_eq!(c.chip8.pc, 0x208);
+        assert!(c.load_state(&state[1..]).is_err());
+        c.load_state(&state).expect("fail");
+        assert_eq!(c.chip8.pc, 0x206);
+        assert_eq!(c.save_state(), state);
+    }
+}
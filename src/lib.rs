@@ -1,5 +1,37 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(dead_code)]
+#[cfg(feature = "std")]
+pub mod chip8_assembler;
+#[cfg(feature = "std")]
+pub mod chip8_batch;
+#[cfg(feature = "std")]
+pub mod chip8_cfg;
+#[cfg(feature = "std")]
+pub mod chip8_cheats;
 pub mod chip8_disassembler;
+#[cfg(feature = "std")]
+pub mod chip8_env;
+#[cfg(feature = "std")]
+pub mod chip8_flags;
+pub mod chip8_font;
+#[cfg(feature = "std")]
+pub mod chip8_frontend;
+#[cfg(feature = "std")]
+pub mod chip8_loader;
+pub mod chip8_observer;
+#[cfg(feature = "std")]
+pub mod chip8_octo;
+#[cfg(feature = "std")]
+pub mod chip8_patch;
+#[cfg(feature = "std")]
+pub mod chip8_rom_database;
+pub mod chip8_state;
+#[cfg(feature = "std")]
+pub mod chip8_testing;
+#[cfg(feature = "wasm")]
+pub mod chip8_wasm;
+
+use chip8_observer::{NoObserver, Observer, Register, Timer};
 
 pub struct Chip8 {
     /*
@@ -49,9 +81,106 @@ pub struct Chip8 {
      A 0 B F
     */
     key: [u8; 16],
+    // The key FX0A saw pressed, the instruction completes once it is released
+    awaited_key: Option<u8>,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    load_address: u16,
+    font_address: u16,
+    // The RPL user flags saved by FX75, kept outside of memory as on the HP48
+    flags: [u8; FLAG_COUNT],
+    #[cfg(feature = "std")]
+    flag_store: Option<Box<dyn chip8_flags::FlagStore>>,
+    #[cfg(feature = "std")]
+    rom_hash: Option<String>,
+    rng_state: u32,
+}
+
+/// Behaviours that differ between Chip-8 interpreters.
+/// The names follow the community CHIP-8 database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX
+    pub shift: bool,
+    /// FX55 and FX65 increase I by X instead of X + 1
+    pub memory_increment_by_x: bool,
+    /// FX55 and FX65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the screen edges instead of being clipped
+    pub wrap: bool,
+    /// BNNN jumps to XNN plus VX instead of NNN plus V0
+    pub jump: bool,
+    /// Drawing waits for the vertical blank, frontends limit draws to one per frame
+    pub vblank: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0, or leave it unchanged when false. With `None` 8XY1
+    /// and 8XY2 reset VF and 8XY3 leaves it, as this interpreter always did.
+    pub logic: Option<bool>,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: None,
+        }
+    }
+}
+
+/// The reasons a rom can fail to load
+#[derive(Debug)]
+pub enum LoadError {
+    /// The rom has no bytes
+    Empty,
+    /// The rom does not fit between the load address and the end of memory
+    TooLarge { size: usize, max: usize },
+    /// The rom could not be read
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// The rom file is in a container format that could not be decoded
+    #[cfg(feature = "std")]
+    InvalidFormat(String),
 }
 
-const CHIP8_FONTSET: [u8; 240] = [
+impl core::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            LoadError::Empty => write!(f, "the rom is empty"),
+            LoadError::TooLarge { size, max } => write!(
+                f,
+                "the rom is {} bytes but only {} bytes fit in memory",
+                size, max
+            ),
+            #[cfg(feature = "std")]
+            LoadError::Io(e) => write!(f, "could not read the rom: {}", e),
+            #[cfg(feature = "std")]
+            LoadError::InvalidFormat(message) => write!(f, "invalid rom file: {}", message),
+        };
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            LoadError::Io(e) => Some(e),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        return LoadError::Io(e);
+    }
+}
+
+pub(crate) const CHIP8_FONTSET: [u8; 240] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -87,6 +216,45 @@ const CHIP8_FONTSET: [u8; 240] = [
     0xF0, 0xF0, 0x80, 0x80, 0xF0, 0xF0, 0x80, 0x80, 0x80, 0x80, //F
 ];
 
+/// The number of instructions per 60Hz frame used when a rom does not ask for another speed
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 15;
+
+/// The address most Chip-8 programs are loaded at
+pub const DEFAULT_LOAD_ADDRESS: u16 = 0x200;
+
+/// The address ETI 660 Chip-8 programs are loaded at
+pub const ETI_660_LOAD_ADDRESS: u16 = 0x600;
+
+/// The number of RPL user flags, 8 on SCHIP and 16 on XO-CHIP
+pub const FLAG_COUNT: usize = 16;
+
+/// Pick a seed for the CXNN random number generator
+#[cfg(feature = "std")]
+fn default_rng_seed() -> u32 {
+    return rand::random();
+}
+
+/// Without `std` there is no entropy source, call `set_rng_seed` to vary the numbers
+#[cfg(not(feature = "std"))]
+fn default_rng_seed() -> u32 {
+    return 0x2545_F491;
+}
+
+/// Check that a game is not empty and fits between a load address and the end of memory
+fn check_game_fits(game: &[u8], load_address: u16) -> Result<(), LoadError> {
+    let max = 4096usize.saturating_sub(load_address as usize);
+    if game.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    if game.len() > max {
+        return Err(LoadError::TooLarge {
+            size: game.len(),
+            max,
+        });
+    }
+    return Ok(());
+}
+
 impl Chip8 {
     /// Create a new Chip-8 instance
     /// # Example
@@ -99,7 +267,7 @@ impl Chip8 {
             memory: [0; 4096],
             v: [0; 16],
             i: 0,
-            pc: 0x200,
+            pc: DEFAULT_LOAD_ADDRESS,
             gfx: [0; 64 * 32],
             extended_gfx_mode: false,
             delay_timer: 0,
@@ -107,41 +275,173 @@ impl Chip8 {
             stack: [0; 16],
             sp: 0,
             key: [0; 16],
+            awaited_key: None,
+            quirks: Quirks::default(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            load_address: DEFAULT_LOAD_ADDRESS,
+            font_address: 0,
+            flags: [0; FLAG_COUNT],
+            #[cfg(feature = "std")]
+            flag_store: None,
+            #[cfg(feature = "std")]
+            rom_hash: None,
+            rng_state: 0,
         };
+        c.set_rng_seed(default_rng_seed());
         c.memory[..CHIP8_FONTSET.len()].copy_from_slice(&CHIP8_FONTSET);
         return c;
     }
 
+    /// Set the address games are loaded at, which is also where execution starts
+    /// # Arguments
+    /// * `address` - The load address, such as `ETI_660_LOAD_ADDRESS`
+    /// # Example
+    /// ```
+    /// use chip8::{Chip8, ETI_660_LOAD_ADDRESS};
+    /// let mut c = Chip8::new();
+    /// c.set_load_address(ETI_660_LOAD_ADDRESS);
+    /// c.load_game_from_slice(&[0x12, 0x34]).expect("fail");
+    /// assert_eq!(c.get_load_address(), 0x600);
+    /// ```
+    pub fn set_load_address(&mut self, address: u16) {
+        self.load_address = address;
+        self.pc = address;
+    }
+
+    /// Get the address games are loaded at
+    pub fn get_load_address(&self) -> u16 {
+        return self.load_address;
+    }
+
+    /// Load a game into the memory at the load address and start execution there
+    /// # Arguments
+    /// * `game` - The game to load
+    /// # Returns
+    /// An error if the game is empty or does not fit in memory
+    /// # Example
+    /// ```
+    /// use chip8::{Chip8, LoadError};
+    /// let mut c = Chip8::new();
+    /// c.load_game_from_slice(&[0x12, 0x34]).expect("fail");
+    /// assert!(matches!(c.load_game_from_slice(&[0; 4096]), Err(LoadError::TooLarge { .. })));
+    /// ```
+    pub fn load_game_from_slice(&mut self, game: &[u8]) -> Result<(), LoadError> {
+        check_game_fits(game, self.load_address)?;
+        let start = self.load_address as usize;
+        self.memory[start..start + game.len()].copy_from_slice(game);
+        self.pc = self.load_address;
+        #[cfg(feature = "std")]
+        {
+            self.rom_hash = Some(chip8_rom_database::sha1_hex(game));
+            self.restore_flags();
+        }
+        return Ok(());
+    }
+
     /// Load a game into the memory
     /// # Arguments
     /// * `game` - The game to load
+    /// # Returns
+    /// An error if the game is empty or does not fit in memory
     /// # Example
     /// ```
     /// use chip8::Chip8;
     /// let mut c = Chip8::new();
-    /// c.load_game(vec![0x12, 0x34]);
+    /// c.load_game(vec![0x12, 0x34]).expect("fail");
     /// ```
-    pub fn load_game(&mut self, game: Vec<u8>) {
-        for (i, byte) in game.iter().enumerate() {
-            self.memory[i + 0x200] = *byte;
-        }
+    #[cfg(feature = "std")]
+    pub fn load_game(&mut self, game: Vec<u8>) -> Result<(), LoadError> {
+        return self.load_game_from_slice(&game);
+    }
+
+    /// Load a game from a reader
+    /// # Arguments
+    /// * `reader` - The source to read the game from
+    /// # Returns
+    /// An error if the game could not be read, is empty or does not fit in memory
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// let file = std::fs::File::open("c8_test.c8").expect("fail");
+    /// c.load_game_from_reader(file).expect("fail");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn load_game_from_reader(
+        &mut self,
+        mut reader: impl std::io::Read,
+    ) -> Result<(), LoadError> {
+        let mut game = Vec::new();
+        reader.read_to_end(&mut game)?;
+        return self.load_game_from_slice(&game);
     }
 
     /// Load a game from a file
     /// # Arguments
     /// * `path` - The path to the file to load
     /// # Returns
-    /// An error if the file could not be read
+    /// An error if the file could not be read, is empty or does not fit in memory
     /// # Example
     /// ```
     /// use chip8::Chip8;
     /// let mut c = Chip8::new();
     /// c.load_game_from_file("game.ch8");
     /// ```
-    pub fn load_game_from_file(&mut self, path: &str) -> Result<(), std::io::Error> {
+    #[cfg(feature = "std")]
+    pub fn load_game_from_file(&mut self, path: &str) -> Result<(), LoadError> {
         let game = std::fs::read(path)?;
-        self.load_game(game);
-        return Ok(());
+        return self.load_game_from_slice(&game);
+    }
+
+    /// Keep the RPL user flags of each rom in a store, so they survive restarts
+    ///
+    /// The flags of the loaded rom are restored from the store straight away, and again
+    /// whenever a rom is loaded. `FX75` saves them.
+    /// # Arguments
+    /// * `store` - Where to keep the flags
+    #[cfg(feature = "std")]
+    pub fn set_flag_store(&mut self, store: Box<dyn chip8_flags::FlagStore>) {
+        self.flag_store = Some(store);
+        self.restore_flags();
+    }
+
+    /// Get the SHA-1 hash of the loaded rom, as used by the rom database, flag stores and cheats
+    #[cfg(feature = "std")]
+    pub fn get_rom_hash(&self) -> Option<&str> {
+        return self.rom_hash.as_deref();
+    }
+
+    /// Get the RPL user flags
+    pub fn get_flags(&self) -> &[u8; FLAG_COUNT] {
+        return &self.flags;
+    }
+
+    /// Set the RPL user flags
+    /// # Arguments
+    /// * `flags` - The flags, as `FX85` will read them
+    pub fn set_flags(&mut self, flags: [u8; FLAG_COUNT]) {
+        self.flags = flags;
+    }
+
+    #[cfg(feature = "std")]
+    fn restore_flags(&mut self) {
+        if let (Some(store), Some(hash)) = (self.flag_store.as_mut(), self.rom_hash.as_ref()) {
+            self.flags = store.load(hash).unwrap_or([0; FLAG_COUNT]);
+        }
+    }
+
+    /// Seed the random number generator used by CXNN, so runs can be repeated
+    /// # Arguments
+    /// * `seed` - Any number
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// c.set_rng_seed(42);
+    /// ```
+    pub fn set_rng_seed(&mut self, seed: u32) {
+        // xorshift never leaves 0
+        self.rng_state = if seed == 0 { 0x2545_F491 } else { seed };
     }
 
     /// Set the state of a key
@@ -188,6 +488,144 @@ impl Chip8 {
         return &self.gfx;
     }
 
+    /// Get the delay timer
+    pub fn get_delay_timer(&self) -> u8 {
+        return self.delay_timer;
+    }
+
+    /// Get the sound timer, the buzzer sounds while it is above zero
+    pub fn get_sound_timer(&self) -> u8 {
+        return self.sound_timer;
+    }
+
+    /// Get the 4KB of memory
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// c.load_game_from_slice(&[0x12, 0x00]).expect("fail");
+    /// assert_eq!(c.get_memory()[0x200..0x202], [0x12, 0x00]);
+    /// ```
+    pub fn get_memory(&self) -> &[u8] {
+        return &self.memory;
+    }
+
+    /// Set a byte of memory, the address wraps around the 4KB
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// c.set_memory(0x1FF, 4);
+    /// assert_eq!(c.get_memory()[0x1FF], 4);
+    /// ```
+    pub fn set_memory(&mut self, address: u16, value: u8) {
+        self.memory[(address & 0xFFF) as usize] = value;
+    }
+
+    /// Get the registers V0 to VF
+    pub fn get_registers(&self) -> &[u8; 16] {
+        return &self.v;
+    }
+
+    /// Set the registers V0 to VF
+    pub fn set_registers(&mut self, v: [u8; 16]) {
+        self.v = v;
+    }
+
+    /// Get the index register I
+    pub fn get_i(&self) -> u16 {
+        return self.i;
+    }
+
+    /// Set the index register I
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    /// Get the program counter
+    pub fn get_pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    /// Get the return addresses on the stack, the most recent call last
+    pub fn get_stack(&self) -> &[u16] {
+        return &self.stack[..self.sp as usize];
+    }
+
+    /// Set the interpreter quirks
+    /// # Arguments
+    /// * `quirks` - The quirks to emulate
+    /// # Example
+    /// ```
+    /// use chip8::{Chip8, Quirks};
+    /// let mut c = Chip8::new();
+    /// c.set_quirks(Quirks { wrap: true, ..Quirks::default() });
+    /// assert!(c.get_quirks().wrap);
+    /// ```
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Get the interpreter quirks
+    pub fn get_quirks(&self) -> Quirks {
+        return self.quirks;
+    }
+
+    /// Set how many instructions a frontend should run per 60Hz frame
+    /// # Arguments
+    /// * `count` - The number of instructions per frame
+    pub fn set_instructions_per_frame(&mut self, count: u32) {
+        self.instructions_per_frame = count;
+    }
+
+    /// Get how many instructions a frontend should run per 60Hz frame
+    pub fn get_instructions_per_frame(&self) -> u32 {
+        return self.instructions_per_frame;
+    }
+
+    /// Run one 60Hz frame worth of instructions, then decrement the delay and sound timers
+    ///
+    /// With the `vblank` quirk the frame ends after its first draw.
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// c.load_game_from_slice(&[0x70, 0x01, 0x12, 0x00]).expect("fail");
+    /// c.set_instructions_per_frame(4);
+    /// c.run_frame();
+    /// assert_eq!(c.get_registers()[0], 2);
+    /// ```
+    /// # Panics
+    /// This function will panic if it encounters an unknown opcode
+    pub fn run_frame(&mut self) {
+        self.run_frame_with(&mut NoObserver);
+    }
+
+    /// Run one frame like `run_frame`, telling an observer what each instruction does
+    /// # Panics
+    /// This function will panic if it encounters an unknown opcode
+    pub fn run_frame_with<O: Observer + ?Sized>(&mut self, observer: &mut O) {
+        self.core().run_frame_with(observer);
+    }
+
+    /// Decrement the delay and sound timers once, as happens at the end of every `run_frame`
+    ///
+    /// Frontends that step with `emulate_cycle` call this at 60Hz.
+    /// # Example
+    /// ```
+    /// use chip8::Chip8;
+    /// let mut c = Chip8::new();
+    /// // LD V0, 2 ; LD ST, V0
+    /// c.load_game_from_slice(&[0x60, 0x02, 0xF0, 0x18]).expect("fail");
+    /// c.emulate_cycle();
+    /// c.emulate_cycle();
+    /// c.tick_timers();
+    /// assert_eq!(c.get_sound_timer(), 1);
+    /// ```
+    pub fn tick_timers(&mut self) {
+        self.core().tick_timers();
+    }
+
     /**********************************************************************************************
     * CHIP-8 has 35 opcodes, which are all two bytes long and stored big-endian.
     * The opcodes are listed below, in hexadecimal and with the following symbols:
@@ -237,69 +675,168 @@ impl Chip8 {
     * FX33     Stores the binary-coded decimal representation of VX, with the most significant of three digits at the address in I.
     * FX55     Stores V0 to VX (including VX) in memory starting at address I.[4]
     * FX65     Fills V0 to VX (including VX) with values from memory starting at address I.
-    * FX75*    Store V0..VX in RPL user flags (X <= 7)
-    * FX85*    Read V0..VX from RPL user flags (X <= 7)
+    * FX75*    Store V0..VX in RPL user flags (X <= 7, or F on XO-CHIP)
+    * FX85*    Read V0..VX from RPL user flags (X <= 7, or F on XO-CHIP)
 
     **********************************************************************************************/
     /// Emulate one cycle of the Chip-8 CPU
     /// This function will read the opcode from the memory, decode it and execute it
-    /// The function will also decrement the delay and sound timers
+    /// The timers are left alone, they count down once per frame in `run_frame`
     /// # Example
     /// ```
     /// use chip8::Chip8;
     /// let mut c = Chip8::new();
-    /// c.load_game(vec![0x12, 0x34]);
+    /// c.load_game_from_slice(&[0x12, 0x34]).expect("fail");
     /// c.emulate_cycle();
     ///
     /// ```
     /// # Panics
     /// This function will panic if it encounters an unknown opcode
     /// # Note
+    /// Up to saved state version 1 every cycle also decremented the timers, which ran them at the
+    /// instruction rate instead of 60Hz. Code that steps with `emulate_cycle` now calls `tick_timers`.
     pub fn emulate_cycle(&mut self) {
-        let opcode =
-            (self.memory[self.pc as usize] as u16) << 8 | self.memory[self.pc as usize + 1] as u16;
-        self.pc += 2;
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+        self.emulate_cycle_with(&mut NoObserver);
+    }
+
+    /// Emulate one cycle, telling an observer what the instruction does
+    /// # Arguments
+    /// * `observer` - Receives the callbacks of `chip8_observer::Observer`
+    /// # Panics
+    /// This function will panic if it encounters an unknown opcode
+    pub fn emulate_cycle_with<O: Observer + ?Sized>(&mut self, observer: &mut O) {
+        self.core().emulate_cycle_with(observer);
+    }
+
+    /// Borrow the fields instructions work on
+    fn core(&mut self) -> Core<'_> {
+        return Core {
+            memory: &mut self.memory,
+            v: &mut self.v,
+            i: &mut self.i,
+            pc: &mut self.pc,
+            gfx: &mut self.gfx,
+            extended_gfx_mode: &mut self.extended_gfx_mode,
+            delay_timer: &mut self.delay_timer,
+            sound_timer: &mut self.sound_timer,
+            stack: &mut self.stack,
+            sp: &mut self.sp,
+            key: &self.key,
+            awaited_key: &mut self.awaited_key,
+            quirks: self.quirks,
+            instructions_per_frame: self.instructions_per_frame,
+            load_address: self.load_address,
+            font_address: self.font_address,
+            flags: &mut self.flags,
+            #[cfg(feature = "std")]
+            flag_store: &mut self.flag_store,
+            #[cfg(feature = "std")]
+            rom_hash: &self.rom_hash,
+            rng_state: &mut self.rng_state,
+        };
+    }
+}
+
+/// The fields an instruction can read or change, borrowed from a `Chip8` or from a batch that
+/// keeps each field of many machines in its own array
+struct Core<'a> {
+    memory: &'a mut [u8; 4096],
+    v: &'a mut [u8; 16],
+    i: &'a mut u16,
+    pc: &'a mut u16,
+    gfx: &'a mut [u8; 64 * 32],
+    extended_gfx_mode: &'a mut bool,
+    delay_timer: &'a mut u8,
+    sound_timer: &'a mut u8,
+    stack: &'a mut [u16; 16],
+    sp: &'a mut u16,
+    key: &'a [u8; 16],
+    awaited_key: &'a mut Option<u8>,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    load_address: u16,
+    font_address: u16,
+    flags: &'a mut [u8; FLAG_COUNT],
+    #[cfg(feature = "std")]
+    flag_store: &'a mut Option<Box<dyn chip8_flags::FlagStore>>,
+    #[cfg(feature = "std")]
+    rom_hash: &'a Option<String>,
+    rng_state: &'a mut u32,
+}
+
+impl Core<'_> {
+    fn run_frame_with<O: Observer + ?Sized>(&mut self, observer: &mut O) {
+        for _ in 0..self.instructions_per_frame {
+            let draws = self.memory[(*self.pc & 0xFFF) as usize] & 0xF0 == 0xD0;
+            self.emulate_cycle_with(observer);
+            if draws && self.quirks.vblank {
+                break;
+            }
+        }
+        self.tick_timers();
+    }
+
+    fn tick_timers(&mut self) {
+        if *self.delay_timer > 0 {
+            *self.delay_timer -= 1;
         }
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
+        if *self.sound_timer > 0 {
+            *self.sound_timer -= 1;
         }
+    }
+
+    fn emulate_cycle_with<O: Observer + ?Sized>(&mut self, observer: &mut O) {
+        // Addresses wrap around the 4KB of memory
+        let pc = *self.pc & 0xFFF;
+        let opcode =
+            (self.memory[pc as usize] as u16) << 8 | self.memory[(pc as usize + 1) & 0xFFF] as u16;
+        *self.pc = pc + 2;
+        observer.fetch(pc, opcode);
+        self.execute(observer, pc, opcode);
+        observer.execute(pc, opcode);
+    }
+
+    fn execute<O: Observer + ?Sized>(&mut self, o: &mut O, pc: u16, opcode: u16) {
         match opcode & 0xF000 {
             0x0000 => {
                 if (opcode & 0x00F0) == 0x00C0 {
-                    let screen_width = if self.extended_gfx_mode { 128 } else { 64 };
-                    let screen_height = if self.extended_gfx_mode { 64 } else { 32 };
-                    let n = (opcode & 0x000F) as u16;
-                    for y in screen_width..n {
-                        let start = y * screen_height;
+                    // The display buffer stays 64x32 in extended mode
+                    let (screen_width, screen_height) = (64, 32);
+                    let n = (opcode & 0x000F) as usize;
+                    // Bottom up, so each row moves before it is overwritten
+                    for y in (n..screen_height).rev() {
+                        let start = y * screen_width;
                         for x in start..start + screen_width {
-                            self.gfx[x as usize] = self.gfx[(x - screen_width * n) as usize];
+                            self.gfx[x] = self.gfx[x - screen_width * n];
                         }
                     }
-                    self.gfx[..(screen_width * n) as usize].fill(0);
+                    self.gfx[..screen_width * n].fill(0);
                     return;
                 }
                 match opcode & 0x00FF {
-                    0x00E0 => self.gfx = [0; 64 * 32],
+                    0x00E0 => *self.gfx = [0; 64 * 32],
                     0x00EE => {
-                        self.sp -= 1;
-                        self.pc = self.stack[self.sp as usize];
+                        // A return with an empty stack is ignored
+                        if *self.sp > 0 {
+                            *self.sp -= 1;
+                            *self.pc = self.stack[*self.sp as usize];
+                        }
                     }
                     0x00FB => {
-                        let screen_width = if self.extended_gfx_mode { 128 } else { 64 };
-                        let screen_height = if self.extended_gfx_mode { 64 } else { 32 };
+                        // The display buffer stays 64x32 in extended mode
+                        let (screen_width, screen_height) = (64, 32);
                         for y in 0..screen_height {
                             let start = y * screen_width;
-                            for x in start..start + screen_width - 4 {
-                                self.gfx[x as usize] = self.gfx[(x + 4) as usize];
+                            // Right to left, so each pixel moves before it is overwritten
+                            for x in (start + 4..start + screen_width).rev() {
+                                self.gfx[x as usize] = self.gfx[(x - 4) as usize];
                             }
                             self.gfx[start as usize..(start + 4) as usize].fill(0);
                         }
                     }
                     0x00FC => {
-                        let screen_width = if self.extended_gfx_mode { 128 } else { 64 };
-                        let screen_height = if self.extended_gfx_mode { 64 } else { 32 };
+                        // The display buffer stays 64x32 in extended mode
+                        let (screen_width, screen_height) = (64, 32);
                         for y in 0..screen_height {
                             let start = y * screen_width;
                             for x in start..start + screen_width - 4 {
@@ -309,100 +846,124 @@ impl Chip8 {
                             self.gfx[ms as usize..(ms + 4) as usize].fill(0);
                         }
                     }
-                    0x00FD => self.pc = 0x200,
-                    0x00FE => self.extended_gfx_mode = false,
-                    0x00FF => self.extended_gfx_mode = true,
+                    0x00FD => *self.pc = self.load_address,
+                    0x00FE => *self.extended_gfx_mode = false,
+                    0x00FF => *self.extended_gfx_mode = true,
                     _ => panic!("Unknown opcode: {:#X}", opcode),
                 }
             }
-            0x1000 => self.pc = opcode & 0x0FFF,
+            0x1000 => *self.pc = opcode & 0x0FFF,
             0x2000 => {
-                self.stack[self.sp as usize] = self.pc;
-                self.sp += 1;
-                self.pc = opcode & 0x0FFF;
+                // A call with a full stack is ignored
+                if (*self.sp as usize) < self.stack.len() {
+                    self.stack[*self.sp as usize] = *self.pc;
+                    *self.sp += 1;
+                    *self.pc = opcode & 0x0FFF;
+                }
             }
             0x3000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let byte = (opcode & 0x00FF) as u8;
                 if self.v[x] == byte {
-                    self.pc += 2;
+                    *self.pc += 2;
                 }
             }
             0x4000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let byte = (opcode & 0x00FF) as u8;
                 if self.v[x] != byte {
-                    self.pc += 2;
+                    *self.pc += 2;
                 }
             }
             0x5000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let y = ((opcode & 0x00F0) >> 4) as usize;
                 if self.v[x] == self.v[y] {
-                    self.pc += 2;
+                    *self.pc += 2;
                 }
             }
             0x6000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let byte = (opcode & 0x00FF) as u8;
-                self.v[x] = byte;
+                self.write_v(o, pc, x, byte);
             }
             0x7000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let byte = (opcode & 0x00FF) as u8;
-                self.v[x] = self.v[x].wrapping_add(byte);
+                self.write_v(o, pc, x, self.v[x].wrapping_add(byte));
             }
             0x8000 => match opcode & 0x000F {
                 0x0000 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
-                    self.v[x] = self.v[y];
+                    self.write_v(o, pc, x, self.v[y]);
                 }
                 0x0001 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
-                    self.v[x] |= self.v[y];
-                    self.v[0xF] = 0;
+                    self.write_v(o, pc, x, self.v[x] | self.v[y]);
+                    if self.quirks.logic != Some(false) {
+                        self.write_v(o, pc, 0xF, 0);
+                    }
                 }
                 0x0002 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
-                    self.v[x] &= self.v[y];
-                    self.v[0xF] = 0;
+                    self.write_v(o, pc, x, self.v[x] & self.v[y]);
+                    if self.quirks.logic != Some(false) {
+                        self.write_v(o, pc, 0xF, 0);
+                    }
                 }
                 0x0003 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
-                    self.v[x] ^= self.v[y];
+                    self.write_v(o, pc, x, self.v[x] ^ self.v[y]);
+                    if self.quirks.logic == Some(true) {
+                        self.write_v(o, pc, 0xF, 0);
+                    }
                 }
                 0x0004 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
                     let sum = self.v[x] as u16 + self.v[y] as u16;
-                    self.v[0xF] = if sum > 0xFF { 1 } else { 0 };
-                    self.v[x] = sum as u8;
+                    self.write_v(o, pc, x, sum as u8);
+                    self.write_v(o, pc, 0xF, if sum > 0xFF { 1 } else { 0 });
                 }
                 0x0005 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
-                    self.v[0xF] = if self.v[x] > self.v[y] { 1 } else { 0 };
-                    self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                    let flag = if self.v[x] >= self.v[y] { 1 } else { 0 };
+                    self.write_v(o, pc, x, self.v[x].wrapping_sub(self.v[y]));
+                    self.write_v(o, pc, 0xF, flag);
                 }
                 0x0006 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.v[0xF] = self.v[x] & 0x1;
-                    self.v[x] >>= 1;
+                    let y = ((opcode & 0x00F0) >> 4) as usize;
+                    let source = if self.quirks.shift {
+                        self.v[x]
+                    } else {
+                        self.v[y]
+                    };
+                    self.write_v(o, pc, x, source >> 1);
+                    self.write_v(o, pc, 0xF, source & 0x1);
                 }
                 0x0007 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     let y = ((opcode & 0x00F0) >> 4) as usize;
-                    self.v[0xF] = if self.v[x] > self.v[y] { 0 } else { 1 };
-                    self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                    let flag = if self.v[y] >= self.v[x] { 1 } else { 0 };
+                    self.write_v(o, pc, x, self.v[y].wrapping_sub(self.v[x]));
+                    self.write_v(o, pc, 0xF, flag);
                 }
                 0x000E => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.v[0xF] = (self.v[x] & 0x80) >> 7;
-                    self.v[x] <<= 1;
+                    let y = ((opcode & 0x00F0) >> 4) as usize;
+                    let source = if self.quirks.shift {
+                        self.v[x]
+                    } else {
+                        self.v[y]
+                    };
+                    self.write_v(o, pc, x, source << 1);
+                    self.write_v(o, pc, 0xF, (source & 0x80) >> 7);
                 }
                 _ => panic!("Unknown opcode: {:#X}", opcode),
             },
@@ -410,44 +971,74 @@ impl Chip8 {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let y = ((opcode & 0x00F0) >> 4) as usize;
                 if self.v[x] != self.v[y] {
-                    self.pc += 2;
+                    *self.pc += 2;
                 }
             }
-            0xA000 => self.i = opcode & 0x0FFF,
-            0xB000 => self.pc = (opcode & 0x0FFF) + self.v[0] as u16,
+            0xA000 => self.write_i(o, pc, opcode & 0x0FFF),
+            0xB000 => {
+                let register = if self.quirks.jump {
+                    ((opcode & 0x0F00) >> 8) as usize
+                } else {
+                    0
+                };
+                *self.pc = (opcode & 0x0FFF) + self.v[register] as u16;
+            }
             0xC000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let byte = (opcode & 0x00FF) as u8;
-                self.v[x] = byte & rand::random::<u8>();
+                let value = byte & self.random_byte();
+                self.write_v(o, pc, x, value);
             }
             0xD000 => {
-                let x = self.v[((opcode & 0x0F00) >> 8) as usize] as u16;
-                let y = self.v[((opcode & 0x00F0) >> 4) as usize] as u16;
+                let x = self.v[((opcode & 0x0F00) >> 8) as usize] as usize % 64;
+                let y = self.v[((opcode & 0x00F0) >> 4) as usize] as usize % 32;
                 let height = opcode & 0x000F;
-                self.v[0xF] = 0;
-                for yline in 0..height {
-                    let pixel = self.memory[self.i as usize + yline as usize];
-                    for xline in 0..8 as u16 {
-                        if (pixel & (0x80 >> xline)) != 0 {
-                            if self.gfx[(x + xline + ((y + yline) * 64)) as usize] == 1 {
-                                self.v[0xF] = 1;
+                let mut collision = false;
+                for yline in 0..height as usize {
+                    let pixel = self.read_memory(o, pc, *self.i as usize + yline);
+                    for xline in 0..8 {
+                        if (pixel & (0x80 >> xline)) == 0 {
+                            continue;
+                        }
+                        let (mut px, mut py) = (x + xline, y + yline);
+                        if px >= 64 || py >= 32 {
+                            if !self.quirks.wrap {
+                                continue;
                             }
-                            self.gfx[(x + xline + ((y + yline) * 64)) as usize] ^= 1;
+                            px %= 64;
+                            py %= 32;
+                        }
+                        if self.gfx[px + py * 64] == 1 {
+                            collision = true;
                         }
+                        self.gfx[px + py * 64] ^= 1;
                     }
                 }
+                self.write_v(o, pc, 0xF, collision as u8);
+                o.sprite_draw(
+                    pc,
+                    x as u8,
+                    y as u8,
+                    *self.i & 0xFFF,
+                    height as u8,
+                    collision,
+                );
             }
             0xE000 => match opcode & 0x00FF {
                 0x009E => {
-                    let x = ((opcode & 0x0F00) >> 8) as usize;
-                    if self.key[self.v[x] as usize] == 1 {
-                        self.pc += 2;
+                    let key = self.v[((opcode & 0x0F00) >> 8) as usize] & 0xF;
+                    let pressed = self.key[key as usize] == 1;
+                    o.key_query(pc, key, pressed);
+                    if pressed {
+                        *self.pc += 2;
                     }
                 }
                 0x00A1 => {
-                    let x = ((opcode & 0x0F00) >> 8) as usize;
-                    if self.key[self.v[x] as usize] == 0 {
-                        self.pc += 2;
+                    let key = self.v[((opcode & 0x0F00) >> 8) as usize] & 0xF;
+                    let pressed = self.key[key as usize] == 1;
+                    o.key_query(pc, key, pressed);
+                    if !pressed {
+                        *self.pc += 2;
                     }
                 }
                 _ => panic!("Unknown opcode: {:#X}", opcode),
@@ -455,69 +1046,94 @@ impl Chip8 {
             0xF000 => match opcode & 0x00FF {
                 0x0007 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.v[x] = self.delay_timer;
+                    self.write_v(o, pc, x, *self.delay_timer);
                 }
                 0x000A => {
+                    // As on the COSMAC VIP the key is stored when it is released
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    let mut key_press = false;
-                    for i in 0..16 {
-                        if self.key[i] != 0 {
-                            self.v[x] = i as u8;
-                            key_press = true;
+                    match *self.awaited_key {
+                        Some(k) if self.key[k as usize] == 0 => {
+                            o.key_query(pc, k, false);
+                            self.write_v(o, pc, x, k);
+                            *self.awaited_key = None;
+                        }
+                        Some(k) => {
+                            o.key_query(pc, k, true);
+                            *self.pc -= 2;
+                        }
+                        None => {
+                            *self.awaited_key =
+                                (0..16).find(|k| self.key[*k] != 0).map(|k| k as u8);
+                            if let Some(k) = *self.awaited_key {
+                                o.key_query(pc, k, true);
+                            }
+                            *self.pc -= 2;
                         }
-                    }
-                    if !key_press {
-                        self.pc -= 2;
                     }
                 }
                 0x0015 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.delay_timer = self.v[x];
+                    *self.delay_timer = self.v[x];
+                    o.timer_set(pc, Timer::Delay, *self.delay_timer);
                 }
                 0x0018 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.sound_timer = self.v[x];
+                    *self.sound_timer = self.v[x];
+                    o.timer_set(pc, Timer::Sound, *self.sound_timer);
                 }
                 0x001E => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.i += self.v[x] as u16;
+                    self.write_i(o, pc, self.i.wrapping_add(self.v[x] as u16));
                 }
                 0x0029 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.i = self.v[x] as u16 * 5;
+                    self.write_i(o, pc, self.font_address + (self.v[x] & 0xF) as u16 * 5);
                 }
                 0x0030 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.i = 0x50 + self.v[x] as u16 * 10;
+                    self.write_i(
+                        o,
+                        pc,
+                        self.font_address + 0x50 + (self.v[x] & 0xF) as u16 * 10,
+                    );
                 }
                 0x0033 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    self.memory[self.i as usize] = self.v[x] / 100;
-                    self.memory[self.i as usize + 1] = (self.v[x] / 10) % 10;
-                    self.memory[self.i as usize + 2] = self.v[x] % 10;
+                    let i = *self.i as usize;
+                    self.write_memory(o, pc, i, self.v[x] / 100);
+                    self.write_memory(o, pc, i + 1, (self.v[x] / 10) % 10);
+                    self.write_memory(o, pc, i + 2, self.v[x] % 10);
                 }
                 0x0055 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     for i in 0..=x {
-                        self.memory[self.i as usize + i] = self.v[i];
+                        self.write_memory(o, pc, *self.i as usize + i, self.v[i]);
                     }
+                    self.advance_i_after_memory_access(o, pc, x);
                 }
                 0x0065 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     for i in 0..=x {
-                        self.v[i] = self.memory[self.i as usize + i];
+                        let value = self.read_memory(o, pc, *self.i as usize + i);
+                        self.write_v(o, pc, i, value);
                     }
+                    self.advance_i_after_memory_access(o, pc, x);
                 }
                 0x0075 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
-                    for i in 0..=x {
-                        self.memory[0x5F0 + i] = self.v[i];
+                    self.flags[..=x].copy_from_slice(&self.v[..=x]);
+                    #[cfg(feature = "std")]
+                    if let (Some(store), Some(hash)) =
+                        (self.flag_store.as_mut(), self.rom_hash.as_ref())
+                    {
+                        // A failed save only loses the flags, so the game keeps running
+                        let _ = store.save(hash, self.flags);
                     }
                 }
                 0x0085 => {
                     let x = ((opcode & 0x0F00) >> 8) as usize;
                     for i in 0..=x {
-                        self.v[i] = self.memory[0x5F0 + i];
+                        self.write_v(o, pc, i, self.flags[i]);
                     }
                 }
                 _ => panic!("Unknown opcode: {:#X}", opcode),
@@ -525,9 +1141,74 @@ impl Chip8 {
             _ => panic!("Unknown opcode: {:#X}", opcode),
         }
     }
+
+    /// Move I past the registers stored or loaded by FX55 and FX65, as the quirks ask
+    fn advance_i_after_memory_access<O: Observer + ?Sized>(
+        &mut self,
+        o: &mut O,
+        pc: u16,
+        x: usize,
+    ) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let step = if self.quirks.memory_increment_by_x {
+            x
+        } else {
+            x + 1
+        };
+        self.write_i(o, pc, self.i.wrapping_add(step as u16));
+    }
+
+    fn write_v<O: Observer + ?Sized>(&mut self, o: &mut O, pc: u16, x: usize, value: u8) {
+        self.v[x] = value;
+        o.register_write(pc, Register::V(x as u8), value as u16);
+    }
+
+    fn write_i<O: Observer + ?Sized>(&mut self, o: &mut O, pc: u16, value: u16) {
+        *self.i = value;
+        o.register_write(pc, Register::I, value);
+    }
+
+    /// Read a byte of data, the address wraps around memory
+    fn read_memory<O: Observer + ?Sized>(&self, o: &mut O, pc: u16, address: usize) -> u8 {
+        let address = address & 0xFFF;
+        let value = self.memory[address];
+        o.memory_read(pc, address as u16, value);
+        return value;
+    }
+
+    /// Write a byte, the address wraps around memory
+    fn write_memory<O: Observer + ?Sized>(
+        &mut self,
+        o: &mut O,
+        pc: u16,
+        address: usize,
+        value: u8,
+    ) {
+        let address = address & 0xFFF;
+        self.memory[address] = value;
+        o.memory_write(pc, address as u16, value);
+    }
+
+    /// The next number from the xorshift generator
+    fn random_byte(&mut self) -> u8 {
+        let mut x = *self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        *self.rng_state = x;
+        return (x >> 24) as u8;
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
 
@@ -539,11 +1220,46 @@ mod tests {
     #[test]
     fn load_game() {
         let mut c = Chip8::new();
-        c.load_game(vec![0x12, 0x34]);
+        c.load_game(vec![0x12, 0x34]).expect("fail");
         assert_eq!(c.memory[0x200], 0x12);
         assert_eq!(c.memory[0x201], 0x34);
     }
     #[test]
+    fn test_load_errors() {
+        let mut c = Chip8::new();
+        assert!(matches!(c.load_game(vec![]), Err(LoadError::Empty)));
+        c.load_game(vec![0xAA; 4096 - 0x200]).expect("fail");
+        assert!(matches!(
+            c.load_game(vec![0; 4096 - 0x200 + 1]),
+            Err(LoadError::TooLarge {
+                size: 3585,
+                max: 3584
+            })
+        ));
+        assert!(matches!(
+            c.load_game_from_file("missing.ch8"),
+            Err(LoadError::Io(_))
+        ));
+    }
+    #[test]
+    fn test_load_address() {
+        let mut c = Chip8::new();
+        c.set_load_address(ETI_660_LOAD_ADDRESS);
+        c.load_game_from_reader(&[0x60, 0x05, 0x00, 0xFD][..])
+            .expect("fail");
+        assert_eq!(c.memory[0x600], 0x60);
+        assert_eq!(c.memory[0x200], 0);
+        assert_eq!(c.pc, 0x600);
+        c.emulate_cycle();
+        assert_eq!(c.v[0], 5);
+        c.emulate_cycle();
+        assert_eq!(c.pc, 0x600);
+        assert!(matches!(
+            c.load_game(vec![0; 4096 - 0x600 + 1]),
+            Err(LoadError::TooLarge { max: 2560, .. })
+        ));
+    }
+    #[test]
     fn test_key() {
         let mut c = Chip8::new();
         c.set_key(0, true);
@@ -567,6 +1283,22 @@ mod tests {
         assert_eq!(c.gfx, [0; 64 * 32]);
     }
     #[test]
+    fn test_opcode_00cn_moves_rows_down() {
+        let mut c = Chip8::new();
+        c.memory[0x200..0x202].copy_from_slice(&[0x00, 0xC3]);
+        for (y, row) in c.gfx.chunks_mut(64).enumerate() {
+            row[y] = 1;
+        }
+        c.emulate_cycle();
+        for (y, row) in c.gfx.chunks(64).enumerate() {
+            let mut exp = [0; 64];
+            if y >= 3 {
+                exp[y - 3] = 1;
+            }
+            assert_eq!(row, exp, "row {}", y);
+        }
+    }
+    #[test]
     fn test_opcode_00e0() {
         let mut c = Chip8::new();
         let opcode = 0x00E0;
@@ -604,6 +1336,17 @@ mod tests {
         assert_eq!(c.gfx, ex);
     }
     #[test]
+    fn test_opcode_00fb_moves_pixels_right() {
+        let mut c = Chip8::new();
+        c.memory[0x200..0x202].copy_from_slice(&[0x00, 0xFB]);
+        c.gfx[0] = 1;
+        c.gfx[64 + 62] = 1;
+        c.emulate_cycle();
+        let mut ex = [0; 64 * 32];
+        ex[4] = 1;
+        assert_eq!(c.gfx, ex);
+    }
+    #[test]
     fn test_opcode_00fc() {
         let mut c = Chip8::new();
         let opcode = 0x00FC;
@@ -814,6 +1557,64 @@ mod tests {
         assert_eq!(c.v[0xF], 0);
     }
     #[test]
+    fn test_opcode_8xy5_flag_written_last() {
+        let mut c = Chip8::new();
+        c.v[0xF] = 0x05;
+        c.v[1] = 0x05;
+        let opcode = 0x8F15;
+        c.memory[0x200] = (opcode >> 8) as u8;
+        c.memory[0x201] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+        assert_eq!(c.v[0xF], 1);
+    }
+    #[test]
+    fn test_opcode_8xy5_equal_values_do_not_borrow() {
+        // 5 - 5 does not borrow, so VF is 1 as on the COSMAC VIP, where it was 0 with `>`
+        let mut c = Chip8::new();
+        c.v[0] = 0x05;
+        c.v[1] = 0x05;
+        let opcode = 0x8015;
+        c.memory[0x200] = (opcode >> 8) as u8;
+        c.memory[0x201] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+        assert_eq!(c.v[0], 0);
+        assert_eq!(c.v[0xF], 1);
+    }
+    #[test]
+    fn test_opcode_8xy7_flag_written_last() {
+        // 5 - 1 leaves 4, but VF as the target ends up holding the flag
+        let mut c = Chip8::new();
+        c.v[0xF] = 0x01;
+        c.v[1] = 0x05;
+        let opcode = 0x8F17;
+        c.memory[0x200] = (opcode >> 8) as u8;
+        c.memory[0x201] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+        assert_eq!(c.v[0xF], 1);
+    }
+    #[test]
+    fn test_opcode_8xy4_flag_written_last() {
+        // With VF as the target the carry wins, writing it first left the sum 0 instead
+        let mut c = Chip8::new();
+        c.v[0xF] = 0xFF;
+        c.v[1] = 0x01;
+        let opcode = 0x8F14;
+        c.memory[0x200] = (opcode >> 8) as u8;
+        c.memory[0x201] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+        assert_eq!(c.v[0xF], 1);
+    }
+    #[test]
+    fn test_opcode_8xye_flag_written_last() {
+        let mut c = Chip8::new();
+        c.v[0xF] = 0xC0;
+        let opcode = 0x8FFE;
+        c.memory[0x200] = (opcode >> 8) as u8;
+        c.memory[0x201] = (opcode & 0xFF) as u8;
+        c.emulate_cycle();
+        assert_eq!(c.v[0xF], 1);
+    }
+    #[test]
     fn test_opcode_8xy6() {
         let mut c = Chip8::new();
         c.v[0] = 0b00001010;
@@ -967,28 +1768,54 @@ mod tests {
         c.memory[0x200] = (opcode >> 8) as u8;
         c.memory[0x201] = (opcode & 0xFF) as u8;
         c.emulate_cycle();
-        assert_eq!(c.v[0], 0x11);
+        assert_eq!(c.v[0], 0x12);
     }
     #[test]
     fn test_opcode_fx0a() {
         let mut c = Chip8::new();
-        c.key[0] = 1;
         let opcode = 0xF00A;
         c.memory[0x200] = (opcode >> 8) as u8;
         c.memory[0x201] = (opcode & 0xFF) as u8;
         c.emulate_cycle();
-        assert_eq!(c.v[0], 0);
-        c.pc = 0x200;
+        assert_eq!(c.pc, 0x200);
+        c.key[5] = 1;
         c.emulate_cycle();
-        assert_eq!(c.v[0], 0);
-        c.key[0] = 0;
-        c.pc = 0x200;
         c.emulate_cycle();
+        assert_eq!(c.pc, 0x200);
         assert_eq!(c.v[0], 0);
-        c.key[0] = 1;
-        c.pc = 0x200;
+        c.key[5] = 0;
         c.emulate_cycle();
-        assert_eq!(c.v[0], 0);
+        assert_eq!(c.pc, 0x202);
+        assert_eq!(c.v[0], 5);
+    }
+    #[test]
+    fn test_timers_count_frames() {
+        // LD V0, 2 ; LD DT, V0 ; LD ST, V0 ; JP 206
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[0x60, 0x02, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06])
+            .expect("fail");
+        c.run_frame();
+        assert_eq!(c.delay_timer, 1);
+        assert_eq!(c.sound_timer, 1);
+        c.run_frame();
+        c.run_frame();
+        assert_eq!(c.delay_timer, 0);
+        assert_eq!(c.sound_timer, 0);
+    }
+    #[test]
+    fn test_tick_timers() {
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&[0x12, 0x00]).expect("fail");
+        c.delay_timer = 2;
+        c.sound_timer = 1;
+        c.emulate_cycle();
+        assert_eq!(c.delay_timer, 2);
+        c.tick_timers();
+        assert_eq!(c.delay_timer, 1);
+        assert_eq!(c.sound_timer, 0);
+        c.tick_timers();
+        assert_eq!(c.delay_timer, 0);
+        assert_eq!(c.sound_timer, 0);
     }
     #[test]
     fn test_opcode_fx15() {
@@ -1085,6 +1912,23 @@ mod tests {
         assert_eq!(c.v[2], 0x56);
     }
     #[test]
+    fn test_opcode_cxnn() {
+        let mut a = Chip8::new();
+        let mut b = Chip8::new();
+        a.set_rng_seed(42);
+        b.set_rng_seed(42);
+        for c in [&mut a, &mut b] {
+            c.load_game_from_slice(&[0xC0, 0xFF, 0xC1, 0x0F, 0xC2, 0xFF])
+                .expect("fail");
+            for _ in 0..3 {
+                c.emulate_cycle();
+            }
+        }
+        assert_eq!(a.v, b.v);
+        assert_eq!(a.v[1] & 0xF0, 0);
+        assert_ne!(a.v[0], a.v[2]);
+    }
+    #[test]
     fn test_opcode_fx75() {
         let mut c = Chip8::new();
         c.v[0] = 0x12;
@@ -1093,40 +1937,203 @@ mod tests {
         let opcode = 0xF275;
         c.memory[0x200] = (opcode >> 8) as u8;
         c.memory[0x201] = (opcode & 0xFF) as u8;
-        c.i = 0x5F0;
         c.emulate_cycle();
-        assert_eq!(c.memory[0x5F0], 0x12);
-        assert_eq!(c.memory[0x5F1], 0x34);
-        assert_eq!(c.memory[0x5F2], 0x56);
+        assert_eq!(c.flags[..4], [0x12, 0x34, 0x56, 0]);
+        assert_eq!(c.memory[0x5F0..0x5F3], [0; 3]);
     }
     #[test]
     fn test_opcode_fx85() {
         let mut c = Chip8::new();
-        c.memory[0x5F0] = 0x12;
-        c.memory[0x5F1] = 0x34;
-        c.memory[0x5F2] = 0x56;
+        c.set_flags([0x12, 0x34, 0x56, 0x78, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         let opcode = 0xF285;
         c.memory[0x200] = (opcode >> 8) as u8;
         c.memory[0x201] = (opcode & 0xFF) as u8;
-        c.i = 0x5F0;
         c.emulate_cycle();
         assert_eq!(c.v[0], 0x12);
         assert_eq!(c.v[1], 0x34);
         assert_eq!(c.v[2], 0x56);
+        assert_eq!(c.v[3], 0);
+    }
+    #[test]
+    fn test_quirk_shift() {
+        let mut c = Chip8::new();
+        c.v[0] = 0b0100;
+        c.v[1] = 0b0011;
+        c.memory[0x200] = 0x80;
+        c.memory[0x201] = 0x16;
+        c.emulate_cycle();
+        assert_eq!(c.v[0], 0b0010);
+        assert_eq!(c.v[0xF], 0);
+        c.set_quirks(Quirks {
+            shift: false,
+            ..Quirks::default()
+        });
+        c.pc = 0x200;
+        c.emulate_cycle();
+        assert_eq!(c.v[0], 0b0001);
+        assert_eq!(c.v[0xF], 1);
+    }
+    #[test]
+    fn test_quirk_memory() {
+        let mut c = Chip8::new();
+        c.memory[0x200] = 0xF2;
+        c.memory[0x201] = 0x55;
+        c.i = 0x300;
+        c.emulate_cycle();
+        assert_eq!(c.i, 0x300);
+        c.quirks.memory_leave_i_unchanged = false;
+        c.pc = 0x200;
+        c.emulate_cycle();
+        assert_eq!(c.i, 0x303);
+        c.quirks.memory_increment_by_x = true;
+        c.pc = 0x200;
+        c.emulate_cycle();
+        assert_eq!(c.i, 0x305);
+    }
+    #[test]
+    fn test_quirk_jump() {
+        let mut c = Chip8::new();
+        c.v[0] = 0x01;
+        c.v[2] = 0x10;
+        c.memory[0x200] = 0xB2;
+        c.memory[0x201] = 0x34;
+        c.emulate_cycle();
+        assert_eq!(c.pc, 0x235);
+        c.quirks.jump = true;
+        c.pc = 0x200;
+        c.emulate_cycle();
+        assert_eq!(c.pc, 0x244);
+    }
+    #[test]
+    fn test_quirk_logic() {
+        // 8011 ; 8013, with VF set before each
+        let vf_after = |logic: Option<bool>| {
+            let mut c = Chip8::new();
+            c.quirks.logic = logic;
+            c.memory[0x200..0x204].copy_from_slice(&[0x80, 0x11, 0x80, 0x13]);
+            c.v[0xF] = 1;
+            c.emulate_cycle();
+            let or = c.v[0xF];
+            c.v[0xF] = 1;
+            c.emulate_cycle();
+            return (or, c.v[0xF]);
+        };
+        assert_eq!(Quirks::default().logic, None);
+        assert_eq!(vf_after(None), (0, 1));
+        assert_eq!(vf_after(Some(true)), (0, 0));
+        assert_eq!(vf_after(Some(false)), (1, 1));
+    }
+    #[test]
+    fn test_quirk_wrap() {
+        let mut c = Chip8::new();
+        c.i = 0x300;
+        c.memory[0x300] = 0xFF;
+        c.memory[0x301] = 0xFF;
+        c.v[0] = 60;
+        c.v[1] = 31;
+        c.memory[0x200] = 0xD0;
+        c.memory[0x201] = 0x12;
+        c.emulate_cycle();
+        assert_eq!(c.gfx.iter().filter(|p| **p == 1).count(), 4);
+        assert_eq!(c.gfx[31 * 64 + 63], 1);
+        c.gfx = [0; 64 * 32];
+        c.quirks.wrap = true;
+        c.pc = 0x200;
+        c.emulate_cycle();
+        assert_eq!(c.gfx.iter().filter(|p| **p == 1).count(), 16);
+        assert_eq!(c.gfx[3], 1);
+        assert_eq!(c.gfx[31 * 64], 1);
+    }
+    #[test]
+    fn test_out_of_range_accesses() {
+        // Each case used to panic, they were found by the fuzz targets in fuzz/
+        let mut c = Chip8::new();
+        // 00EE with an empty stack
+        c.memory[0x200..0x202].copy_from_slice(&[0x00, 0xEE]);
+        c.emulate_cycle();
+        assert_eq!((c.pc, c.sp), (0x202, 0));
+        // 2NNN with a full stack
+        c.sp = 16;
+        c.memory[0x202..0x204].copy_from_slice(&[0x23, 0x00]);
+        c.emulate_cycle();
+        assert_eq!((c.pc, c.sp), (0x204, 16));
+        // 00FC in extended mode
+        c.extended_gfx_mode = true;
+        c.gfx[4] = 1;
+        c.memory[0x204..0x206].copy_from_slice(&[0x00, 0xFC]);
+        c.emulate_cycle();
+        assert_eq!(c.gfx[0], 1);
+        // EX9E with a register above 15
+        c.v[1] = 0x12;
+        c.key[2] = 1;
+        c.memory[0x206..0x208].copy_from_slice(&[0xE1, 0x9E]);
+        c.emulate_cycle();
+        assert_eq!(c.pc, 0x20A);
+        // FX1E past 0xFFFF
+        c.i = 0xFFFF;
+        c.v[0] = 1;
+        c.memory[0x20A..0x20C].copy_from_slice(&[0xF0, 0x1E]);
+        c.emulate_cycle();
+        assert_eq!(c.i, 0);
+        // FX33 and FX65 at the end of memory
+        c.i = 0xFFE;
+        c.v[0] = 255;
+        c.memory[0x20C..0x210].copy_from_slice(&[0xF0, 0x33, 0xF1, 0x65]);
+        c.emulate_cycle();
+        assert_eq!([c.memory[0xFFE], c.memory[0xFFF], c.memory[0]], [2, 5, 5]);
+        c.emulate_cycle();
+        assert_eq!(c.v[..2], [2, 5]);
+        // BNNN past the end of memory, then an instruction split across it
+        c.v[0] = 0xFF;
+        c.memory[0x210..0x212].copy_from_slice(&[0xBF, 0xFF]);
+        c.memory[0xFFF] = 0x61;
+        c.memory[0] = 0x42;
+        c.emulate_cycle();
+        assert_eq!(c.pc, 0x10FE);
+        c.pc = 0xFFF;
+        c.emulate_cycle();
+        assert_eq!((c.v[1], c.pc), (0x42, 0x1001));
+    }
+    #[test]
+    fn test_corrupt_state() {
+        // Found by the state fuzz target: the state loaded, then moving the font indexed
+        // memory from F561 and FX30 overflowed
+        let mut c = Chip8::new();
+        let mut state = c.save_state();
+        let end = state.len();
+        // The load and font addresses, before the flags and the rng state
+        state[end - 24..end - 20].copy_from_slice(&[0xB0, 0x45, 0xF5, 0x61]);
+        assert_eq!(c.load_state(&state), Err(chip8_state::StateError::Corrupt));
+        c.set_font_address(0x50).expect("fail");
+        c.v[0] = 0xF;
+        c.memory[0x200..0x202].copy_from_slice(&[0xF0, 0x30]);
+        c.emulate_cycle();
+        assert_eq!(c.i, 0x50 + 0x50 + 0xF * 10);
     }
     #[test]
     fn test_test_rom() {
         let mut c = Chip8::new();
         c.load_game_from_file("c8_test.c8")
             .expect("Failed to load game");
-        for _i in 0..500 {
-            c.emulate_cycle();
+        for _i in 0..40 {
+            c.run_frame();
         }
-        for y in 0..32 {
-            for x in 0..64 {
-                print!("{}", c.gfx[y * 64 + x]);
-            }
-            println!();
+        // Every check passed when the rom draws OK at (24, 16) and nothing else
+        let ok = [
+            "..##....#..#....",
+            ".#..#...#.#.....",
+            ".#..#...##......",
+            ".#..#...#.#.....",
+            "..##....#..#....",
+        ];
+        for (y, row) in ok.iter().enumerate() {
+            let drawn: String = c.gfx[(16 + y) * 64 + 24..(16 + y) * 64 + 40]
+                .iter()
+                .map(|p| if *p != 0 { '#' } else { '.' })
+                .collect();
+            assert_eq!(drawn, *row);
         }
+        let lit = ok.iter().map(|row| row.matches('#').count()).sum::<usize>();
+        assert_eq!(c.gfx.iter().filter(|p| **p != 0).count(), lit);
     }
 }
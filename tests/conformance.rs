@@ -0,0 +1,208 @@
+//! Runs the community CHIP-8 test suite by Timendus (https://github.com/Timendus/chip8-test-suite)
+//! under each platform profile and compares the final screens with `tests/conformance/*.txt`.
+//!
+//! The roms are bundled in `tests/roms`, see the README there. Roms are recognised by SHA-1 hash
+//! or by file name, and the Octo sources there are compiled when the tests run. Run with `CHIP8_BLESS=1` to write the snapshots of new cases, then check the
+//! screens show passing results before committing them.
+
+use chip8::chip8_octo;
+use chip8::chip8_rom_database::{sha1_hex, Platform};
+use chip8::chip8_testing::{check_snapshot_file, Snapshot};
+use chip8::Chip8;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How to find a rom in the suite directory
+enum Rom {
+    /// The combined menu driven suite of release 3.1, `chip8-test-suite.ch8`
+    Hash(&'static str),
+    /// One of the separate roms of release 4, such as `2-ibm-logo.ch8`
+    File(&'static str),
+    /// An Octo source written for this crate, in place of a release 4 rom that is not bundled
+    Octo(&'static str),
+}
+
+const SUITE_V3: Rom = Rom::Hash("5a8e1cada60dddd388ac954852aac63f284589ff");
+
+struct Case {
+    name: String,
+    rom: &'static Rom,
+    platform: Platform,
+    /// Bytes written to memory after loading, the suite reads its autostart options from 0x1FE and 0x1FF
+    pokes: Vec<(u16, u8)>,
+    /// The key held in each frame from `KEY_FRAME` on
+    keys: &'static [u8],
+}
+
+const KEY_FRAME: usize = 60;
+const FRAMES: usize = 600;
+
+const PLATFORMS: [(&str, Platform); 4] = [
+    ("chip8", Platform::OriginalChip8),
+    ("modern", Platform::ModernChip8),
+    ("schip", Platform::SuperChip),
+    ("xochip", Platform::XoChip),
+];
+
+type Test = (&'static str, Rom, &'static [(u16, u8)], &'static [u8]);
+
+/// The tests that pass on every platform, FX0A waits for key 5 to be pressed and released
+///
+/// Only release 3.1 and the IBM logo of release 4 are bundled. `scrolling.8o` stands in for the
+/// scrolling rom, the other release 4 roms have no cases until they are added to `tests/roms`.
+static TESTS: [Test; 6] = [
+    ("v3-ibm-logo", SUITE_V3, &[(0x1FF, 1)], &[]),
+    ("v3-corax+", SUITE_V3, &[(0x1FF, 2)], &[]),
+    ("v3-flags", SUITE_V3, &[(0x1FF, 3)], &[]),
+    (
+        "v3-keypad-fx0a",
+        SUITE_V3,
+        &[(0x1FF, 5), (0x1FE, 3)],
+        &[5; 10],
+    ),
+    ("ibm-logo", Rom::File("2-ibm-logo.ch8"), &[], &[]),
+    ("scrolling", Rom::Octo("scrolling.8o"), &[], &[]),
+];
+
+fn cases() -> Vec<Case> {
+    let mut cases = Vec::new();
+    for (id, platform) in PLATFORMS {
+        // The quirks test checks a platform chosen from its own menu, which has no modern profile
+        let quirks_choice = match platform {
+            Platform::OriginalChip8 => Some(1),
+            Platform::SuperChip => Some(2),
+            Platform::XoChip => Some(3),
+            _ => None,
+        };
+        for (name, rom, pokes, keys) in TESTS.iter() {
+            cases.push(Case {
+                name: format!("{}-{}", name, id),
+                rom,
+                platform: platform.clone(),
+                pokes: pokes.to_vec(),
+                keys,
+            });
+        }
+        if let Some(choice) = quirks_choice {
+            cases.push(Case {
+                name: format!("v3-quirks-{}", id),
+                rom: &SUITE_V3,
+                platform: platform.clone(),
+                pokes: vec![(0x1FF, 4), (0x1FE, choice)],
+                keys: &[],
+            });
+        }
+    }
+    return cases;
+}
+
+/// Every file in the suite directory, by file name and by SHA-1 hash
+fn read_suite(dir: &Path) -> HashMap<String, Vec<u8>> {
+    let mut roms = HashMap::new();
+    let entries = std::fs::read_dir(dir).expect("the suite roms are bundled");
+    for entry in entries.flatten() {
+        let Ok(bytes) = std::fs::read(entry.path()) else {
+            continue;
+        };
+        roms.insert(sha1_hex(&bytes), bytes.clone());
+        roms.insert(entry.file_name().to_string_lossy().into_owned(), bytes);
+    }
+    return roms;
+}
+
+/// The bytes of a rom, compiling Octo sources
+fn rom_bytes(roms: &HashMap<String, Vec<u8>>, rom: &Rom) -> Result<Vec<u8>, String> {
+    let key = match rom {
+        Rom::Hash(key) | Rom::File(key) | Rom::Octo(key) => key,
+    };
+    let Some(bytes) = roms.get(*key) else {
+        return Err(format!("{} is not in tests/roms", key));
+    };
+    if let Rom::Octo(_) = rom {
+        let source = String::from_utf8_lossy(bytes);
+        return chip8_octo::compile(&source).map_err(|e| format!("{}: {}", key, e));
+    }
+    return Ok(bytes.clone());
+}
+
+/// Run a case, returning the final screen
+fn run(case: &Case, rom: &[u8]) -> Snapshot {
+    let mut c = Chip8::new();
+    c.load_game_from_slice(rom)
+        .expect("the suite roms fit in memory");
+    c.set_quirks(case.platform.quirks());
+    for (address, value) in case.pokes.iter() {
+        c.set_memory(*address, *value);
+    }
+    for frame in 0..FRAMES {
+        let held = frame
+            .checked_sub(KEY_FRAME)
+            .and_then(|i| case.keys.get(i))
+            .copied();
+        for key in 0..16 {
+            c.set_key(key, held == Some(key));
+        }
+        c.run_frame();
+    }
+    return Snapshot::from_chip8(&c);
+}
+
+#[test]
+fn test_conformance() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let roms = read_suite(&manifest.join("tests/roms"));
+    let snapshots = manifest.join("tests/conformance");
+    let mut failures = Vec::new();
+    for case in cases() {
+        let rom = match rom_bytes(&roms, case.rom) {
+            Ok(rom) => rom,
+            Err(message) => {
+                failures.push(format!("{}: {}", case.name, message));
+                continue;
+            }
+        };
+        let screen = run(&case, &rom);
+        let path = snapshots.join(format!("{}.txt", case.name));
+        if let Err(message) = check_snapshot_file(&screen, &path) {
+            failures.push(format!("{}: {}", case.name, message));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// `beep.8o` keeps the sound timer running while key B is held, and lets it run out after
+#[test]
+fn test_beep() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let roms = read_suite(&manifest.join("tests/roms"));
+    let rom = rom_bytes(&roms, &Rom::Octo("beep.8o")).unwrap();
+    for (id, platform) in PLATFORMS {
+        let mut c = Chip8::new();
+        c.load_game_from_slice(&rom).unwrap();
+        c.set_quirks(platform.quirks());
+        for _ in 0..10 {
+            c.run_frame();
+        }
+        assert_eq!(c.get_sound_timer(), 0, "{}: beeps before key B", id);
+        c.set_key(0xB, true);
+        for frame in 0..30 {
+            c.run_frame();
+            assert!(
+                c.get_sound_timer() > 0,
+                "{}: silent in frame {} with key B held",
+                id,
+                frame
+            );
+        }
+        c.set_key(0xB, false);
+        for _ in 0..10 {
+            c.run_frame();
+        }
+        assert_eq!(
+            c.get_sound_timer(),
+            0,
+            "{}: beeps after key B is released",
+            id
+        );
+    }
+}